@@ -0,0 +1,31 @@
+//! Parsing benchmarks, run with `cargo bench`
+//!
+//! Compares copying every entry into a CacheEntry against borrowing them with CacheEntryRef and only summarizing their headers, on a database the size of a busy thumbcache_256.db.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use thumbscache::{parse_database, parse_database_ref, scan_database_headers, CacheType, ReadOptions, ThumbscacheWriter, WindowsVersion};
+
+// 2000 entries of 32 KiB, about 64 MiB
+fn large_database() -> Vec<u8> {
+    let mut writer = ThumbscacheWriter::new(WindowsVersion::Win10, CacheType::Res256).unwrap();
+    let data: Vec<u8> = (0..32 * 1024).map(|x| x as u8).collect();
+    for hash in 0..2000 {
+        writer.append(hash, &data);
+    }
+    writer.into_bytes()
+}
+
+fn parse(c: &mut Criterion) {
+    let bytes = large_database();
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+    group.sample_size(20);
+    group.bench_function("owned", |b| b.iter(|| parse_database(black_box(&bytes), ReadOptions::default()).unwrap()));
+    group.bench_function("owned_lazy", |b| b.iter(|| parse_database(black_box(&bytes), ReadOptions { load_data: false, ..ReadOptions::default() }).unwrap()));
+    group.bench_function("borrowed", |b| b.iter(|| parse_database_ref(black_box(&bytes), ReadOptions::default()).unwrap()));
+    group.bench_function("headers", |b| b.iter(|| scan_database_headers(black_box(&bytes), ReadOptions::default()).unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -1,15 +1,13 @@
-use thumbscache::open_thumbscache;
+use thumbscache::Thumbscache;
 use std::io;
 use std::io::*;
-use std::path::Path;
 
 fn main() {
     print!("Thumbscache path : ");
     let _ = io::stdout().flush();
     let mut input = String::new();
     let _ = io::stdin().read_line(&mut input);
-    input = input.trim().to_string();
-    let mut thumbscache_file = open_thumbscache(input);
+    let mut thumbscache_file = Thumbscache::from_path(input.trim()).unwrap();
     println!("{:?}",thumbscache_file.read());
     println!("{:?}",thumbscache_file);
 }
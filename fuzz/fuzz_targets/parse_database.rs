@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use thumbscache::{parse_database, ReadOptions, ThumbcacheIndex};
+
+// Any input has to end in an error or a parsed database, never in a panic
+fuzz_target!(|data: &[u8]| {
+    let _ = parse_database(data, ReadOptions::default());
+    let _ = parse_database(data, ReadOptions::lazy());
+    let _ = ThumbcacheIndex::parse(data);
+});
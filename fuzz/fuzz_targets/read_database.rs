@@ -0,0 +1,21 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use thumbscache::{HeaderPolicy, Strictness, ThumbscacheBuilder, Thumbscache};
+
+// Reading, recovering and describing the entries of any input must not panic either
+fuzz_target!(|data: &[u8]| {
+    let mut database = Thumbscache::from_bytes(data.to_vec());
+    let _ = database.read();
+    let _ = database.validate_header();
+    let _ = database.consistency_check();
+    let _ = database.verify_all();
+    for entry in database.iter() {
+        let _ = (entry.identifier(), entry.dimensions(), entry.reconstructed_bmp(), entry.exif());
+    }
+    let _ = database.carve();
+    let _ = database.recover_unreferenced();
+    let _ = Thumbscache::from_bytes(data.to_vec()).read_lossy();
+    let _ = Thumbscache::from_bytes(data.to_vec()).carve_headerless();
+    let _ = ThumbscacheBuilder::new().strictness(Strictness::Lossy).carve(true).header_policy(HeaderPolicy::SkipAndCarve).read(&mut Thumbscache::from_bytes(data.to_vec()));
+});
@@ -0,0 +1,137 @@
+/* Generated with cbindgen from src/ffi.rs, see cbindgen.toml */
+
+#ifndef THUMBSCACHE_H
+#define THUMBSCACHE_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The call succeeded
+ */
+#define THUMBSCACHE_OK 0
+
+/**
+ * A pointer argument was null
+ */
+#define THUMBSCACHE_ERROR_NULL -1
+
+/**
+ * The file couldn't be opened or read
+ */
+#define THUMBSCACHE_ERROR_IO -2
+
+/**
+ * The database is malformed
+ */
+#define THUMBSCACHE_ERROR_PARSE -3
+
+/**
+ * There is no entry at the index
+ */
+#define THUMBSCACHE_ERROR_OUT_OF_RANGE -4
+
+/**
+ * The buffer is too small for the data
+ */
+#define THUMBSCACHE_ERROR_BUFFER_TOO_SMALL -5
+
+/**
+ * An opened database, created by thumbscache_open() or thumbscache_open_bytes() and released by thumbscache_free()
+ */
+typedef struct ThumbscacheHandle ThumbscacheHandle;
+
+/**
+ * The metadata of a cache entry, filled by thumbscache_entry_metadata()
+ */
+typedef struct ThumbscacheEntryMetadata {
+  /**
+   * The 64-bit hash identifying the cached item
+   */
+  uint64_t entry_hash;
+  /**
+   * Offset of the entry in the database
+   */
+  uint64_t offset;
+  /**
+   * Offset of the data in the database
+   */
+  uint64_t data_offset;
+  /**
+   * Size of the data in bytes, the buffer size needed by thumbscache_copy_data()
+   */
+  uint32_t data_size;
+  /**
+   * 0 for unknown data, 1 for BMP, 2 for JPEG and 3 for PNG
+   */
+  uint32_t format;
+  /**
+   * Width of the thumbnail, 0 when unknown
+   */
+  uint32_t width;
+  /**
+   * Height of the thumbnail, 0 when unknown
+   */
+  uint32_t height;
+  /**
+   * CRC-64 of the data, as stored in the entry header
+   */
+  uint64_t data_checksum;
+  /**
+   * CRC-64 of the entry header, as stored in it
+   */
+  uint64_t header_checksum;
+} ThumbscacheEntryMetadata;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Opens the database at the path, a null-terminated UTF-8 string. Returns null if it can't be opened.
+ */
+ThumbscacheHandle *thumbscache_open(const char *path);
+
+/**
+ * Copies the bytes of a whole database into a new handle. Returns null if `bytes` is null.
+ */
+ThumbscacheHandle *thumbscache_open_bytes(const uint8_t *bytes, size_t len);
+
+/**
+ * Reads the cache entries of the database, like Thumbscache::read()
+ */
+int32_t thumbscache_read(ThumbscacheHandle *handle);
+
+/**
+ * The number of cache entries read, 0 for a null handle
+ */
+size_t thumbscache_entry_count(const ThumbscacheHandle *handle);
+
+/**
+ * Fills `metadata` with the metadata of the entry at the index
+ */
+int32_t thumbscache_entry_metadata(const ThumbscacheHandle *handle,
+                                   size_t index,
+                                   ThumbscacheEntryMetadata *metadata);
+
+/**
+ * Copies the data of the entry at the index into the buffer, which has to hold at least its data_size bytes
+ */
+int32_t thumbscache_copy_data(const ThumbscacheHandle *handle,
+                              size_t index,
+                              uint8_t *buffer,
+                              size_t buffer_len);
+
+/**
+ * Releases the handle, null is ignored
+ */
+void thumbscache_free(ThumbscacheHandle *handle);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* THUMBSCACHE_H */
@@ -0,0 +1,85 @@
+//! Command line interface to list, extract and verify the contents of a thumbnail cache database
+
+use std::process::ExitCode;
+
+use thumbscache::{NamingStrategy, Thumbscache, ThumbsError};
+
+const USAGE: &str = "Usage :
+    thumbscache info <database>
+    thumbscache list <database>
+    thumbscache verify <database>
+    thumbscache extract <database> <output directory> [--index]";
+
+fn open(path: &str) -> Result<Thumbscache, ThumbsError> {
+    let mut database = Thumbscache::from_path(path)?;
+    database.read()?;
+    Ok(database)
+}
+
+fn run(args: &[String]) -> Result<ExitCode, ThumbsError> {
+    match args {
+        [command, path] if command == "info" => {
+            let database = open(path)?;
+            match database.windows_version {
+                Some(windows_version) => println!("Windows version : {:?}, {}", windows_version, windows_version.detail()),
+                None => println!("Windows version : unknown")
+            }
+            println!("Cache type : {:?}", database.cache_type);
+            println!("Kind : {:?}", database.kind);
+            println!("Entries : {}", database.len());
+            println!("Data size : {} bytes", database.iter().map(|x| x.data_size as u64).sum::<u64>());
+        },
+        [command, path] if command == "list" => {
+            let database = open(path)?;
+            for entry in database.iter() {
+                println!("{}\t{}\t{:?}", entry.identifier_string, entry.data_size, entry.detect_format());
+            }
+        },
+        [command, path] if command == "verify" => {
+            let report = open(path)?.verify_all();
+            for corrupted in &report.corrupted {
+                println!("{}\tdata checksum {}\theader checksum {}", corrupted.identifier_string,
+                    if corrupted.status.data_valid { "ok" } else { "mismatch" },
+                    if corrupted.status.header_valid { "ok" } else { "mismatch" });
+            }
+            println!("{} entries checked, {} corrupted", report.checked, report.corrupted.len());
+            if !report.is_clean() {
+                return Ok(ExitCode::FAILURE);
+            }
+        },
+        [command, path, dir, options @ ..] if command == "extract" => {
+            let naming = match options {
+                [] => NamingStrategy::Identifier,
+                [option] if option == "--index" => NamingStrategy::Index,
+                _ => {
+                    eprintln!("{}", USAGE);
+                    return Ok(ExitCode::FAILURE);
+                }
+            };
+            let summary = open(path)?.export_all(dir, naming)?;
+            for (index, error) in &summary.failed {
+                eprintln!("Entry {} : {}", index, error);
+            }
+            println!("{} written, {} skipped, {} failed", summary.written.len(), summary.skipped.len(), summary.failed.len());
+            if !summary.failed.is_empty() {
+                return Ok(ExitCode::FAILURE);
+            }
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            return Ok(ExitCode::FAILURE);
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! Rebuilding bitmaps from headerless pixel data
+//!
+//! Some entries hold the raw 32-bit BGRA pixels of the thumbnail without any image header, image viewers can't open them as-is.
+//! Prepending a BITMAPFILEHEADER and a BITMAPINFOHEADER turns them back into regular BMP files.
+
+use alloc::{borrow::Cow, vec::Vec};
+
+use crate::{CacheEntry, ImageFormat, PayloadKind, VersionFields};
+
+// BITMAPFILEHEADER followed by BITMAPINFOHEADER
+const FILE_HEADER_SIZE: u32 = 14;
+const INFO_HEADER_SIZE: u32 = 40;
+
+// Builds a 32-bit BMP around the pixels, returns None if their length doesn't match the dimensions
+pub(crate) fn bmp_from_bgra(pixels: &[u8], width: u32, height: u32) -> Option<Vec<u8>> {
+    let size = (width as usize).checked_mul(height as usize)?.checked_mul(4)?;
+    if width == 0 || height == 0 || size != pixels.len() || width > i32::MAX as u32 || height > i32::MAX as u32 {
+        return None;
+    }
+    let offset = FILE_HEADER_SIZE + INFO_HEADER_SIZE;
+    let file_size = u32::try_from(size).ok()?.checked_add(offset)?;
+    let mut bytes = Vec::with_capacity(file_size as usize);
+    bytes.extend_from_slice(b"BM");
+    bytes.extend_from_slice(&file_size.to_le_bytes());
+    bytes.extend_from_slice(&[0; 4]);
+    bytes.extend_from_slice(&offset.to_le_bytes());
+    bytes.extend_from_slice(&INFO_HEADER_SIZE.to_le_bytes());
+    bytes.extend_from_slice(&(width as i32).to_le_bytes());
+    // A positive height keeps the rows bottom-up, the way device independent bitmaps store them
+    bytes.extend_from_slice(&(height as i32).to_le_bytes());
+    // One plane, 32 bits per pixel, BI_RGB
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&32u16.to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+    bytes.extend_from_slice(&(size as u32).to_le_bytes());
+    // 96 DPI, no palette
+    bytes.extend_from_slice(&3780i32.to_le_bytes());
+    bytes.extend_from_slice(&3780i32.to_le_bytes());
+    bytes.extend_from_slice(&[0; 8]);
+    bytes.extend_from_slice(pixels);
+    Some(bytes)
+}
+
+impl CacheEntry {
+    /// Wraps the data in a BMP header, treating it as raw 32-bit BGRA pixels of the given dimensions.
+    ///
+    /// Returns None if the data size isn't width * height * 4 bytes.
+    pub fn reconstruct_bmp(&self, width: u32, height: u32) -> Option<Vec<u8>> {
+        bmp_from_bgra(&self.data, width, height)
+    }
+
+    /// The dimensions of headerless pixel data, when they can be derived.
+    ///
+    /// They come from the entry header on Windows 8 and later, otherwise the thumbnail is assumed to be a square
+    /// of the resolution of its cache type. Returns None if the data is in a known format or its size doesn't match.
+    pub fn raw_pixel_dimensions(&self) -> Option<(u32, u32)> {
+        if self.payload_kind() != PayloadKind::Unknown {
+            return None;
+        }
+        let matches = |(width, height): (u32, u32)| (width as u64).checked_mul(height as u64).and_then(|x| x.checked_mul(4)) == Some(self.data.len() as u64) && width != 0;
+        let from_header = match self.version_fields {
+            VersionFields::Win8 { width, height, .. } => Some((width, height)),
+            _ => None
+        };
+        let resolution = self.cache_type.and_then(|x| x.max_dimension()).map(|x| (x, x));
+        from_header.filter(|x| matches(*x)).or(resolution.filter(|x| matches(*x)))
+    }
+
+    /// Rebuilds a BMP file from headerless pixel data, using the dimensions from `raw_pixel_dimensions()`
+    pub fn reconstructed_bmp(&self) -> Option<Vec<u8>> {
+        let (width, height) = self.raw_pixel_dimensions()?;
+        self.reconstruct_bmp(width, height)
+    }
+
+    // The bytes written when exporting the entry along with their payload kind, headerless pixel data is turned into a BMP
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
+    pub(crate) fn viewable_data(&self) -> (Cow<'_, [u8]>, PayloadKind) {
+        match self.reconstructed_bmp() {
+            Some(bmp) => (Cow::Owned(bmp), PayloadKind::Image(ImageFormat::Bmp)),
+            None => (Cow::Borrowed(&self.data), self.payload_kind())
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::{tests::build_database, CacheEntry, ImageFormat, PayloadKind, Thumbscache};
+
+    #[test]
+    fn rebuilds_headerless_pixels() {
+        // Two by two pixels for the Windows 10 96px cache, whose headers don't store dimensions in these fixtures
+        let pixels: Vec<u8> = (0..16).collect();
+        let mut database = Thumbscache::from_bytes(build_database(32, 3, &[(1, &pixels), (2, b"BMalready")]));
+        database.read().unwrap();
+        let entry: &CacheEntry = &database.entries()[0];
+
+        let bmp = entry.reconstruct_bmp(2, 2).unwrap();
+        assert_eq!(ImageFormat::detect(&bmp), ImageFormat::Bmp);
+        assert_eq!(bmp.len(), 54 + 16);
+        assert_eq!(&bmp[54..], pixels.as_slice());
+        assert_eq!(crate::dimensions::sniff_dimensions(&bmp), Some((2, 2)));
+        assert_eq!(entry.reconstruct_bmp(4, 2), None);
+
+        // Neither the header nor the 96px resolution match 16 bytes
+        assert_eq!(entry.raw_pixel_dimensions(), None);
+        assert_eq!(entry.viewable_data().1.extension(), "bin");
+        let mut square = entry.clone();
+        square.data = vec![0; 96 * 96 * 4];
+        assert_eq!(square.raw_pixel_dimensions(), Some((96, 96)));
+        assert_eq!(square.viewable_data().1, PayloadKind::Image(ImageFormat::Bmp));
+        // Data in a known format is never wrapped
+        assert_eq!(database.entries()[1].reconstructed_bmp(), None);
+    }
+}
@@ -0,0 +1,134 @@
+//! Databases borrowed from bytes the caller already holds
+//!
+//! Unlike `Thumbscache`, a `ThumbscacheRef` doesn't take or copy the bytes, so databases carved from a disk image or held
+//! in a larger buffer can be parsed in place. It only needs `alloc`.
+
+use alloc::vec::Vec;
+use core::slice::Iter;
+
+use crate::{parse::{parse_database_ref, parse_header}, CacheEntryRef, CacheHeader, CacheType, ReadOptions, ThumbsError, WindowsVersion};
+
+/// A database borrowing its bytes, see `Thumbscache::from_slice()`
+///
+/// ```
+/// use thumbscache::ThumbscacheRef;
+/// # let bytes = {
+/// #     let mut writer = thumbscache::ThumbscacheWriter::new(thumbscache::WindowsVersion::Win10, thumbscache::CacheType::Res256).unwrap();
+/// #     writer.append(1, b"BM...");
+/// #     writer.into_bytes()
+/// # };
+/// let mut database = ThumbscacheRef::new(&bytes);
+/// database.read().unwrap();
+/// assert_eq!(database.entries()[0].data, b"BM...");
+/// ```
+#[derive(Debug, Clone)]
+pub struct ThumbscacheRef<'a> {
+    bytes: &'a [u8],
+    pub windows_version: Option<WindowsVersion>,
+    pub cache_type: Option<CacheType>,
+    entries: Vec<CacheEntryRef<'a>>
+}
+
+impl<'a> ThumbscacheRef<'a> {
+    /// Borrows the bytes of a whole database.
+    /// Additional parsing is neccessary using the .read() function.
+    pub fn new(bytes: &'a [u8]) -> ThumbscacheRef<'a> {
+        ThumbscacheRef {
+            bytes,
+            windows_version: None,
+            cache_type: None,
+            entries: Vec::new()
+        }
+    }
+
+    /// Determines the Windows version and the cache type, then parses every entry, replacing the entries parsed before
+    pub fn read(&mut self) -> Result<u32, ThumbsError> {
+        self.read_with_options(ReadOptions::default())
+    }
+
+    /// Parses the entries like .read() does, following the options
+    pub fn read_with_options(&mut self, options: ReadOptions) -> Result<u32, ThumbsError> {
+        let (header, entries) = parse_database_ref(self.bytes, options)?;
+        self.windows_version = header.windows_version;
+        self.cache_type = header.cache_type;
+        self.entries = entries;
+        Ok(self.entries.len() as u32)
+    }
+
+    /// Parses the header of the database
+    pub fn header(&self) -> Result<CacheHeader, ThumbsError> {
+        parse_header(self.bytes)
+    }
+
+    /// Returns the parsed cache entries, in file order
+    pub fn entries(&self) -> &[CacheEntryRef<'a>] {
+        &self.entries
+    }
+
+    /// Takes the parsed cache entries, which keep borrowing the bytes
+    pub fn into_entries(self) -> Vec<CacheEntryRef<'a>> {
+        self.entries
+    }
+
+    /// Iterates over the parsed cache entries, in file order
+    pub fn iter(&self) -> Iter<'_, CacheEntryRef<'a>> {
+        self.entries.iter()
+    }
+
+    /// The number of parsed cache entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if no cache entries were parsed
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the raw bytes of the database
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+}
+
+#[cfg(feature = "std")]
+impl crate::Thumbscache {
+    /// Takes the bytes of a whole database without copying them, like `from_bytes()` does
+    pub fn from_vec(bytes: Vec<u8>) -> crate::Thumbscache {
+        crate::Thumbscache::from_bytes(bytes)
+    }
+
+    /// Borrows the bytes of a whole database, for callers that already hold them and don't want to give them up or copy them
+    pub fn from_slice(bytes: &[u8]) -> ThumbscacheRef<'_> {
+        ThumbscacheRef::new(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_database;
+
+    #[test]
+    fn parses_borrowed_bytes() {
+        // A database in the middle of a larger buffer, as carved from a disk image
+        let mut image = vec![0xAA; 100];
+        image.extend_from_slice(&build_database(31, 2, &[(1, b"first"), (2, b"")]));
+        image.extend_from_slice(&[0xBB; 100]);
+        let mut database = ThumbscacheRef::new(&image[100..image.len() - 100]);
+        assert!(database.is_empty());
+        assert_eq!(database.read().unwrap(), 2);
+        assert_eq!((database.windows_version, database.cache_type), (Some(WindowsVersion::Win81), Some(CacheType::Res48)));
+        assert_eq!(database.read_with_options(ReadOptions { skip_empty: true, ..ReadOptions::default() }).unwrap(), 1);
+        assert_eq!(database.iter().map(|x| x.data).collect::<Vec<_>>(), [b"first"]);
+        assert_eq!(database.header().unwrap().entry_count, Some(2));
+        assert!(ThumbscacheRef::new(b"CMMM").read().is_err());
+
+        #[cfg(feature = "std")]
+        {
+            let mut owned = crate::Thumbscache::from_vec(database.bytes().to_vec());
+            owned.read().unwrap();
+            assert_eq!(owned.len(), crate::Thumbscache::from_slice(database.bytes()).read().unwrap() as usize);
+        }
+    }
+}
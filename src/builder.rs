@@ -0,0 +1,268 @@
+//! Configuring how a database is parsed, instead of picking between the read functions
+
+use crate::{OffsetStatus, ParseWarning, ReadOptions, Thumbscache, ThumbsError, WindowsVersion};
+
+/// How damaged entries are handled, see `ThumbscacheBuilder::strictness()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strictness {
+    /// Parsing fails on the first damaged entry, like `Thumbscache::read()`
+    #[default]
+    Strict,
+    /// Damaged entries are reported as warnings and parsing resumes at the next entry, like `Thumbscache::read_lossy()`
+    Lossy
+}
+
+/// What happens when the database header can't be read, see `ThumbscacheBuilder::header_policy()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HeaderPolicy {
+    /// Reading fails with the error of the header
+    #[default]
+    Fail,
+    /// The entries are recovered by scanning for their signature instead, see `Thumbscache::carve_headerless()`
+    SkipAndCarve
+}
+
+/// The outcome of `ThumbscacheBuilder::read()`
+#[derive(Debug, Default)]
+pub struct ReadReport {
+    /// Number of entries added by walking the chain of entries
+    pub entries: u32,
+    /// Number of stale entries recovered past the live ones, only when carving is enabled
+    pub recovered: u32,
+    /// Problems found in lossy mode
+    pub warnings: Vec<ParseWarning>,
+    /// Whether the header couldn't be read and the entries were carved instead, see `HeaderPolicy::SkipAndCarve`
+    pub header_skipped: bool
+}
+
+/// Builder for parsing a database
+///
+/// ```
+/// use thumbscache::{Strictness, Thumbscache, ThumbscacheBuilder};
+/// # let bytes = {
+/// #     let mut writer = thumbscache::ThumbscacheWriter::new(thumbscache::WindowsVersion::Win10, thumbscache::CacheType::Res256).unwrap();
+/// #     writer.append(1, b"BM...");
+/// #     writer.into_bytes()
+/// # };
+/// let mut database = Thumbscache::from_bytes(bytes);
+/// let report = ThumbscacheBuilder::new()
+///     .strictness(Strictness::Lossy)
+///     .verify_checksums(true)
+///     .carve(true)
+///     .read(&mut database)
+///     .unwrap();
+/// assert!(report.warnings.is_empty());
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThumbscacheBuilder {
+    options: ReadOptions,
+    strictness: Strictness,
+    verify_checksums: bool,
+    carve: bool,
+    header_policy: HeaderPolicy,
+    assume_version: Option<WindowsVersion>
+}
+
+impl ThumbscacheBuilder {
+    /// A builder reading the entries the way `Thumbscache::read()` does
+    pub fn new() -> ThumbscacheBuilder {
+        ThumbscacheBuilder::default()
+    }
+
+    /// Starts from existing read options
+    pub fn with_options(options: ReadOptions) -> ThumbscacheBuilder {
+        ThumbscacheBuilder {
+            options,
+            ..ThumbscacheBuilder::default()
+        }
+    }
+
+    /// Whether parsing stops on the first damaged entry, strict by default
+    pub fn strictness(mut self, strictness: Strictness) -> ThumbscacheBuilder {
+        self.strictness = strictness;
+        self
+    }
+
+    /// Verifies the checksums of every entry read, disabled by default.
+    ///
+    /// A mismatch fails a strict read with ThumbsError::ChecksumMismatch and is reported as a warning by a lossy read.
+    pub fn verify_checksums(mut self, verify_checksums: bool) -> ThumbscacheBuilder {
+        self.verify_checksums = verify_checksums;
+        self
+    }
+
+    /// Whether the data of every entry is copied, enabled by default (see `ReadOptions::load_data`)
+    pub fn load_data(mut self, load_data: bool) -> ThumbscacheBuilder {
+        self.options.load_data = load_data;
+        self
+    }
+
+    /// Leaves out the entries without data
+    pub fn skip_empty(mut self, skip_empty: bool) -> ThumbscacheBuilder {
+        self.options.skip_empty = skip_empty;
+        self
+    }
+
+    /// Stops after this many entries were added
+    pub fn max_entries(mut self, max_entries: Option<u32>) -> ThumbscacheBuilder {
+        self.options.max_entries = max_entries;
+        self
+    }
+
+    /// The largest data size accepted for an entry (see `ReadOptions::max_data_size`)
+    pub fn max_data_size(mut self, max_data_size: u32) -> ThumbscacheBuilder {
+        self.options.max_data_size = max_data_size;
+        self
+    }
+
+    /// Skips damaged entries and resumes at the next valid one, lossy reads always do (see `ReadOptions::resync`)
+    pub fn resync(mut self, resync: bool) -> ThumbscacheBuilder {
+        self.options.resync = resync;
+        self
+    }
+
+    /// Also recovers the stale entries left past the live ones, disabled by default (see `Thumbscache::recover_unreferenced()`)
+    pub fn carve(mut self, carve: bool) -> ThumbscacheBuilder {
+        self.carve = carve;
+        self
+    }
+
+    /// What happens when the header is unreadable, reading fails by default.
+    ///
+    /// With `HeaderPolicy::SkipAndCarve`, a header without the CMMM signature, with an unknown format version or whose first entry
+    /// lies past the end of the file is skipped, and the entries are found by scanning for their signature.
+    pub fn header_policy(mut self, header_policy: HeaderPolicy) -> ThumbscacheBuilder {
+        self.header_policy = header_policy;
+        self
+    }
+
+    /// Parses the entries with the layout of this Windows version, see `Thumbscache::assume_version()`
+    pub fn assume_version(mut self, version: WindowsVersion) -> ThumbscacheBuilder {
+        self.assume_version = Some(version);
+        self
+    }
+
+    /// The read options used for strict reads
+    pub fn options(&self) -> ReadOptions {
+        self.options
+    }
+
+    /// Reads the cache entries of the database following the configuration, replacing the entries read before
+    pub fn read(&self, database: &mut Thumbscache) -> Result<ReadReport, ThumbsError> {
+        if let Some(version) = self.assume_version {
+            database.assume_version(version);
+        }
+        let mut report = ReadReport::default();
+        let header_readable = database.header().and_then(|x| x.version()).is_ok() && database.validate_header().is_ok_and(|x| x.first_entry == OffsetStatus::Valid);
+        if self.header_policy == HeaderPolicy::SkipAndCarve && !header_readable {
+            database.entry_list().clear();
+            report.entries = database.carve_headerless()?;
+            report.header_skipped = true;
+            if !self.options.load_data {
+                for entry in database.entries_mut() {
+                    entry.data = Vec::new();
+                }
+            }
+            return Ok(report);
+        }
+        match self.strictness {
+            Strictness::Strict => {
+                report.entries = database.read_with_options(self.options)?;
+                if self.verify_checksums {
+                    if let Some(error) = database.entries().iter().enumerate().find_map(|(index, entry)| {
+                        // Entries read without their data are checked against the data in the database
+                        entry.checksum_mismatch(index, database.entry_data(entry).unwrap_or(&entry.data))
+                    }) {
+                        return Err(error);
+                    }
+                }
+            },
+            Strictness::Lossy => {
+                report.warnings = database.read_lossy_with_options(self.options)?;
+                report.entries = database.len() as u32;
+                if !self.verify_checksums {
+                    report.warnings.retain(|x| !matches!(x.error, ThumbsError::ChecksumMismatch { .. }));
+                }
+            }
+        }
+        if self.carve {
+            report.recovered = database.recover_unreferenced()?;
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::build_database, EntryOrigin};
+
+    #[test]
+    fn reads_following_the_configuration() {
+        let mut bytes = build_database(32, 1, &[(1, b"first"), (2, b""), (3, b"third")]);
+        // Corrupt the data of the first entry, then leave a stale entry past the live ones
+        bytes[24 + 56 + 32] ^= 0xFF;
+        let stale = build_database(32, 1, &[(4, b"stale")]);
+        bytes.extend_from_slice(&stale[24..]);
+
+        let mut database = Thumbscache::from_bytes(bytes.clone());
+        let report = ThumbscacheBuilder::new().skip_empty(true).load_data(false).read(&mut database).unwrap();
+        assert_eq!((report.entries, report.recovered), (2, 0));
+        assert!(database.entries()[1].data.is_empty());
+
+        let mut database = Thumbscache::from_bytes(bytes.clone());
+        let result = ThumbscacheBuilder::new().verify_checksums(true).read(&mut database);
+        assert!(matches!(result, Err(ThumbsError::ChecksumMismatch { entry: 0, .. })));
+
+        // Entries read without their data are verified against the database
+        let mut database = Thumbscache::from_bytes(bytes.clone());
+        let result = ThumbscacheBuilder::new().load_data(false).verify_checksums(true).read(&mut database);
+        assert!(matches!(result, Err(ThumbsError::ChecksumMismatch { entry: 0, expected, actual }) if expected == crate::checksum::crc64(b"first") && actual != u64::MAX));
+        let mut database = Thumbscache::from_bytes(build_database(32, 1, &[(1, b"first"), (2, b""), (3, b"third")]));
+        let report = ThumbscacheBuilder::new().load_data(false).verify_checksums(true).read(&mut database).unwrap();
+        assert_eq!(report.entries, 3);
+        assert!(!database.entries()[0].is_data_loaded());
+
+        let mut database = Thumbscache::from_bytes(bytes.clone());
+        let report = ThumbscacheBuilder::new().strictness(Strictness::Lossy).verify_checksums(true).carve(true).read(&mut database).unwrap();
+        assert_eq!((report.entries, report.recovered, report.warnings.len()), (3, 1, 1));
+        assert_eq!(database.entries()[3].origin, EntryOrigin::Unallocated);
+
+        let mut database = Thumbscache::from_bytes(bytes.clone());
+        let report = ThumbscacheBuilder::new().strictness(Strictness::Lossy).read(&mut database).unwrap();
+        assert!(report.warnings.is_empty());
+
+        // Lossy reads follow the read options, entries read without their data are still verified
+        let mut database = Thumbscache::from_bytes(bytes);
+        let report = ThumbscacheBuilder::new().strictness(Strictness::Lossy).skip_empty(true).max_entries(Some(2)).load_data(false).verify_checksums(true)
+            .read(&mut database).unwrap();
+        assert_eq!(report.entries, 2);
+        assert_eq!(database.iter().map(|x| x.entry_hash()).collect::<Vec<_>>(), [1, 3]);
+        assert!(database.iter().all(|x| x.data.is_empty() && !x.is_data_loaded()));
+        assert_eq!(report.warnings.len(), 1);
+        assert!(matches!(report.warnings[0].error, ThumbsError::ChecksumMismatch { entry: 0, .. }));
+        assert_eq!(database.entry_data(&database.entries()[1]), Some(&b"third"[..]));
+    }
+
+    #[test]
+    fn carves_past_an_unreadable_header() {
+        for format_version in [20, 21, 32] {
+            let mut bytes = build_database(format_version, 1, &[(1, b"first"), (2, b"second")]);
+            bytes[..24].fill(0);
+            assert!(ThumbscacheBuilder::new().read(&mut Thumbscache::from_bytes(bytes.clone())).is_err());
+
+            let mut database = Thumbscache::from_bytes(bytes);
+            let report = ThumbscacheBuilder::new().header_policy(HeaderPolicy::SkipAndCarve).read(&mut database).unwrap();
+            assert_eq!((report.entries, report.header_skipped), (2, true), "{}", format_version);
+            assert_eq!(database.entries()[1].data, b"second");
+            assert_eq!(database.entries()[1].origin, EntryOrigin::Carved);
+            assert_eq!(database.windows_version, None);
+        }
+
+        // A readable header is parsed as usual
+        let mut database = Thumbscache::from_bytes(build_database(32, 1, &[(1, b"first")]));
+        let report = ThumbscacheBuilder::new().header_policy(HeaderPolicy::SkipAndCarve).read(&mut database).unwrap();
+        assert_eq!((report.entries, report.header_skipped), (1, false));
+        assert_eq!(database.entries()[0].origin, EntryOrigin::Live);
+    }
+}
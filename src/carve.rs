@@ -0,0 +1,188 @@
+//! Recovering entries by scanning for their signature
+//!
+//! Instead of following the entries one after another, carving looks for every "CMMM" signature in the database,
+//! so entries past a damaged or overwritten region can still be recovered.
+
+use std::collections::HashSet;
+
+use crate::{parse::{decode_identifier, entry_header_at}, CacheEntry, CacheType, EntryOrigin, ReadOptions, Thumbscache, ThumbsError, WindowsVersion};
+
+// Parses the entry starting at the offset, data cut short by the end of the bytes or longer than the limit is kept truncated
+pub(crate) fn carve_entry_at(bytes: &[u8], offset: usize, version: WindowsVersion, max_data_size: u32) -> Option<CacheEntry> {
+    let (fields, temp_bytes) = entry_header_at(bytes, offset, version)?;
+    let identifier_start = offset + fields.header_len;
+    let data_offset = identifier_start.checked_add(fields.identifier_string_size as usize)?.checked_add(fields.padding_size as usize)?;
+    let data_end = data_offset.checked_add(fields.data_size as usize)?;
+    // Reject headers whose sizes don't add up, they are most likely not an entry
+    fields.next_entry_offset(offset as u64).ok()?;
+    let identifier_string_vec = bytes.get(identifier_start..identifier_start + fields.identifier_string_size as usize)?;
+    let data_end = data_end.min(data_offset.saturating_add(max_data_size as usize)).min(bytes.len());
+    let data = bytes.get(data_offset.min(bytes.len())..data_end)?.to_vec();
+    Some(CacheEntry {
+        offset: offset as u64,
+        size: fields.size,
+        entry_hash: fields.entry_hash,
+        version_fields: fields.version_fields,
+        identifier_string_size: fields.identifier_string_size,
+        padding_size: fields.padding_size,
+        data_size: fields.data_size,
+        data_checksum: fields.data_checksum,
+        header_checksum: fields.header_checksum,
+        header: temp_bytes[..fields.header_len].to_vec(),
+        data_offset: data_offset as u64,
+        identifier_string: decode_identifier(identifier_string_vec),
+        identifier: identifier_string_vec.to_vec(),
+        origin: EntryOrigin::Live,
+        cache_type: None,
+        data
+    })
+}
+
+impl Thumbscache {
+    /// Recovers the entries by scanning the whole database for entry signatures instead of walking the chain of entries.
+    ///
+    /// The Windows version is taken from the database header. Entries cut short by the end of the file are kept with the data that is left,
+    /// so the data of these entries is shorter than their data_size.
+    /// The recovered entries are added after the parsed ones, entries at an offset already listed (by .read() for instance) are left out.
+    /// Returns the number of entries added.
+    pub fn carve(&mut self) -> Result<u32, ThumbsError> {
+        let header = self.header()?;
+        self.windows_version = header.windows_version;
+        self.cache_type = header.cache_type;
+        let version = header.version()?;
+        // The database header starts with the same signature
+        let mut carved = carve_range(self.bytes(), 4, version);
+        for entry in &mut carved {
+            entry.origin = EntryOrigin::Carved;
+            entry.cache_type = header.cache_type;
+        }
+        Ok(self.add_carved(carved))
+    }
+
+    /// Recovers the entries by scanning the whole database for entry signatures, without reading the database header.
+    ///
+    /// Meant for databases whose header is zeroed or overwritten while their entries are intact. The entries use the layout
+    /// of the version passed to `assume_version()`, otherwise every layout is tried and the one recovering the most entries with valid checksums is kept.
+    /// The Windows version stays unknown unless it was assumed, the cache type is taken from the file name when opened from a path.
+    /// Like .carve(), entries at an offset already listed are left out. Returns the number of entries added.
+    pub fn carve_headerless(&mut self) -> Result<u32, ThumbsError> {
+        // One version for each entry layout
+        let versions = match self.assumed_version {
+            Some(version) => vec![version],
+            None => vec![WindowsVersion::Win10, WindowsVersion::Win7, WindowsVersion::WinVista]
+        };
+        // The layouts of Windows Vista and Windows 8 are the same size, reading the data at the wrong place fails its checksum
+        let mut carved = versions.into_iter().enumerate().map(|(i, version)| (i, carve_range(self.bytes(), 0, version)))
+            .max_by_key(|(i, x)| (x.iter().filter(|x| x.verify().is_valid()).count(), x.len(), core::cmp::Reverse(*i)))
+            .map(|(_, x)| x).unwrap_or_default();
+        self.windows_version = self.assumed_version;
+        #[cfg(feature = "fs")]
+        if self.cache_type.is_none() {
+            self.cache_type = self.path.as_ref().and_then(|x| CacheType::from_filename(&x.to_string_lossy()));
+        }
+        for entry in &mut carved {
+            entry.origin = EntryOrigin::Carved;
+            entry.cache_type = self.cache_type;
+        }
+        Ok(self.add_carved(carved))
+    }
+
+    /// Recovers the entries Windows no longer references, left between the first available entry and the end of the file.
+    ///
+    /// Windows reuses the space of a database without clearing it, so stale entries can remain past the live ones.
+    /// They are added after the parsed entries with the origin set to `EntryOrigin::Unallocated`.
+    /// Nothing is recovered when the first available entry of the header can't be trusted, entries at an offset already listed are left out.
+    /// Returns the number of entries added.
+    pub fn recover_unreferenced(&mut self) -> Result<u32, ThumbsError> {
+        let header = self.header()?;
+        let version = header.version()?;
+        let (end, true) = header.entries_end(self.bytes().len() as u64) else {
+            return Ok(0);
+        };
+        let mut recovered = carve_range(self.bytes(), end as usize, version);
+        for entry in &mut recovered {
+            entry.origin = EntryOrigin::Unallocated;
+            entry.cache_type = header.cache_type;
+        }
+        Ok(self.add_carved(recovered))
+    }
+
+    // Adds the carved entries after the parsed ones, leaving out the ones at an offset already listed
+    fn add_carved(&mut self, carved: Vec<CacheEntry>) -> u32 {
+        let listed: HashSet<u64> = self.iter().map(|x| x.offset).collect();
+        let before = self.len();
+        self.entry_list().extend(carved.into_iter().filter(|x| !listed.contains(&x.offset)));
+        (self.len() - before) as u32
+    }
+}
+
+// Carves every entry found from the offset to the end of the bytes
+fn carve_range(bytes: &[u8], mut offset: usize, version: WindowsVersion) -> Vec<CacheEntry> {
+    let mut carved = Vec::new();
+    while offset < bytes.len().saturating_sub(3) {
+        if &bytes[offset..offset + 4] != b"CMMM" {
+            offset += 1;
+            continue;
+        }
+        match carve_entry_at(bytes, offset, version, ReadOptions::default().max_data_size) {
+            Some(entry) => {
+                offset = offset.saturating_add(entry.size as usize);
+                carved.push(entry);
+            },
+            None => offset += 4
+        }
+    }
+    carved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_database;
+    use std::io::Cursor;
+
+    #[test]
+    fn recovers_entries_after_damaged_region() {
+        let mut bytes = build_database(32, 1, &[(1, b"first"), (2, b"second"), (3, b"third")]);
+        // Wipe the signature of the first entry, walking the chain stops right there
+        bytes[24..28].copy_from_slice(b"\0\0\0\0");
+        // And cut the last entry short
+        bytes.truncate(bytes.len() - 2);
+        let mut database = Thumbscache::from_reader(Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(database.read().unwrap(), 0);
+
+        let mut database = Thumbscache::from_reader(Cursor::new(bytes)).unwrap();
+        assert_eq!(database.carve().unwrap(), 2);
+        assert_eq!(database.entries()[0].data, b"second");
+        assert_eq!(database.entries()[1].data, b"thi");
+        assert_eq!(database.entries()[1].data_size, 5);
+        assert!(database.iter().all(|x| x.origin == EntryOrigin::Carved));
+        assert_eq!(database.carve().unwrap(), 0);
+        assert_eq!(database.len(), 2);
+    }
+
+    #[test]
+    fn leaves_out_the_entries_already_parsed() {
+        let mut database = Thumbscache::from_bytes(build_database(32, 1, &[(1, b"first"), (2, b"second")]));
+        assert_eq!(database.read().unwrap(), 2);
+        assert_eq!(database.carve().unwrap(), 0);
+        assert_eq!(database.iter().map(|x| x.entry_hash()).collect::<Vec<_>>(), [1, 2]);
+        assert!(database.iter().all(|x| x.origin == EntryOrigin::Live));
+    }
+
+    #[test]
+    fn recovers_stale_entries_past_the_live_ones() {
+        let stale = build_database(32, 1, &[(1, b"first"), (2, b"stale")]);
+        let mut bytes = build_database(32, 1, &[(1, b"first")]);
+        // The second entry was dropped from the chain but its bytes are still there
+        let live_end = bytes.len();
+        bytes.extend_from_slice(&stale[live_end..]);
+        let mut database = Thumbscache::from_bytes(bytes);
+        assert_eq!(database.read().unwrap(), 1);
+        assert_eq!(database.recover_unreferenced().unwrap(), 1);
+        assert_eq!(database.entries()[0].origin, EntryOrigin::Live);
+        assert_eq!(database.entries()[1].origin, EntryOrigin::Unallocated);
+        assert_eq!(database.entries()[1].offset(), live_end as u64);
+        assert_eq!(database.entries()[1].data, b"stale");
+    }
+}
@@ -0,0 +1,186 @@
+//! CRC-64 verification of cache entries
+//!
+//! Windows uses the ECMA-182 polynomial in its reflected form, with an initial value of all ones and no final XOR.
+//! The data checksum covers the thumbnail data, the header checksum covers the entry header up to the header checksum itself.
+
+use alloc::{string::String, vec::Vec};
+
+use crate::CacheEntry;
+#[cfg(feature = "std")]
+use crate::{Thumbscache, ThumbsError};
+
+const POLYNOMIAL: u64 = 0xC96C_5795_D787_0F42;
+
+const TABLE: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u64;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ POLYNOMIAL } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Computes the CRC-64 checksum used by the thumbnail cache
+pub(crate) fn crc64(bytes: &[u8]) -> u64 {
+    let mut crc = u64::MAX;
+    for byte in bytes {
+        crc = TABLE[((crc ^ *byte as u64) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+/// The outcome of verifying the checksums of a cache entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChecksumStatus {
+    pub data_valid: bool,
+    pub header_valid: bool
+}
+
+impl ChecksumStatus {
+    /// Returns true if both checksums match
+    pub fn is_valid(&self) -> bool {
+        self.data_valid && self.header_valid
+    }
+}
+
+/// A cache entry that failed verification
+#[derive(Debug, Clone)]
+pub struct CorruptedEntry {
+    /// Position of the entry in the list of cache entries
+    pub index: usize,
+    pub identifier_string: String,
+    pub status: ChecksumStatus
+}
+
+/// Verification report
+///
+/// Returned by `Thumbscache::verify_all()`, lists every entry whose checksums don't match.
+#[derive(Debug, Clone, Default)]
+pub struct VerificationReport {
+    pub checked: usize,
+    pub corrupted: Vec<CorruptedEntry>
+}
+
+impl VerificationReport {
+    /// Returns true if no corrupted entries were found
+    pub fn is_clean(&self) -> bool {
+        self.corrupted.is_empty()
+    }
+}
+
+impl CacheEntry {
+    /// Recomputes the data and header checksums and compares them to the stored values.
+    ///
+    /// Entries without data are considered valid if their stored data checksum is zero.
+    pub fn verify(&self) -> ChecksumStatus {
+        self.verify_data(&self.data)
+    }
+
+    // Verifies the entry as if it held this data, for entries whose data wasn't loaded
+    pub(crate) fn verify_data(&self, data: &[u8]) -> ChecksumStatus {
+        let data_valid = if data.is_empty() && self.data_checksum == 0 {
+            true
+        }else {
+            crc64(data) == self.data_checksum
+        };
+        let header_valid = self.header.len() >= 8 && crc64(&self.header[..self.header.len() - 8]) == self.header_checksum;
+        ChecksumStatus {
+            data_valid,
+            header_valid
+        }
+    }
+
+    // Describes the first checksum that doesn't match, the index is the position of the entry in its database.
+    // The data is passed in so entries read without their data can be checked against the database.
+    #[cfg(feature = "std")]
+    pub(crate) fn checksum_mismatch(&self, index: usize, data: &[u8]) -> Option<ThumbsError> {
+        let status = self.verify_data(data);
+        if !status.is_valid() {
+            event!(warn, offset = self.offset, data_valid = status.data_valid, header_valid = status.header_valid, "checksum mismatch");
+        }
+        if !status.data_valid {
+            Some(ThumbsError::ChecksumMismatch { entry: index, expected: self.data_checksum, actual: crc64(data) })
+        }else if !status.header_valid {
+            let actual = crc64(self.header.get(..self.header.len().saturating_sub(8)).unwrap_or_default());
+            Some(ThumbsError::ChecksumMismatch { entry: index, expected: self.header_checksum, actual })
+        }else {
+            None
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Thumbscache {
+    /// Verifies every parsed cache entry and reports the ones that are corrupted.
+    ///
+    /// Entries read without their data are verified against the data in the database.
+    pub fn verify_all(&self) -> VerificationReport {
+        let mut report = VerificationReport::default();
+        for (index, entry) in self.iter().enumerate() {
+            report.checked += 1;
+            let status = entry.verify_data(self.loaded_data(entry));
+            if !status.is_valid() {
+                report.corrupted.push(CorruptedEntry {
+                    index,
+                    identifier_string: entry.identifier_string.clone(),
+                    status
+                });
+            }
+        }
+        report
+    }
+
+    /// Verifies every parsed cache entry, failing on the first checksum that doesn't match
+    pub fn check_all(&self) -> Result<(), ThumbsError> {
+        match self.iter().enumerate().find_map(|(index, entry)| entry.checksum_mismatch(index, self.loaded_data(entry))) {
+            Some(error) => Err(error),
+            None => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "std")]
+    use crate::tests::build_database;
+
+    #[test]
+    fn crc64_check_value() {
+        // The reflected ECMA-182 polynomial without a final XOR
+        assert_eq!(crc64(b"123456789"), !0x995D_C9BB_DF19_39FA);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn reports_corrupted_entries() {
+        let mut bytes = build_database(32, 1, &[(1, b"first"), (2, b"second")]);
+        // Flip a byte of the last entry's data
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+        let mut database = Thumbscache::from_bytes(bytes);
+        database.read().unwrap();
+        assert!(database.entries()[0].verify().is_valid());
+        let report = database.verify_all();
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.corrupted.len(), 1);
+        assert_eq!(report.corrupted[0].index, 1);
+        assert!(!report.corrupted[0].status.data_valid);
+        assert!(report.corrupted[0].status.header_valid);
+        match database.check_all() {
+            Err(ThumbsError::ChecksumMismatch { entry, expected, actual }) => {
+                assert_eq!(entry, 1);
+                assert_eq!(expected, crc64(b"second"));
+                assert_ne!(actual, expected);
+            },
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! Reading databases from compressed evidence, enabled with the `compressed-input` feature
+//!
+//! Acquisition tools often hand out caches as .gz files or inside .zip bundles, these are decompressed in memory.
+
+use std::io::{Read, Seek};
+
+use crate::{DatabaseKind, Thumbscache, ThumbsError};
+
+impl Thumbscache {
+    /// Decompresses a gzip compressed database.
+    /// Additional parsing is neccessary using the .read() function.
+    pub fn from_gzip(reader: impl Read) -> Result<Thumbscache, ThumbsError> {
+        let mut bytes = Vec::new();
+        flate2::read::GzDecoder::new(reader).read_to_end(&mut bytes).map_err(ThumbsError::IoError)?;
+        Ok(Thumbscache::from_bytes(bytes))
+    }
+
+    /// Decompresses the database stored under the path inside a zip archive.
+    /// Additional parsing is neccessary using the .read() function.
+    ///
+    /// Returns ThumbsError::InvalidFile if the archive has no file at this path.
+    pub fn from_zip(reader: impl Read + Seek, name: &str) -> Result<Thumbscache, ThumbsError> {
+        let mut archive = zip::ZipArchive::new(reader).map_err(|x| ThumbsError::IoError(x.into()))?;
+        let mut file = archive.by_name(name).map_err(|_| ThumbsError::InvalidFile)?;
+        zip_entry_database(&mut file)
+    }
+}
+
+/// Decompresses every thumbnail and icon cache database found inside a zip archive, along with their path in the archive.
+///
+/// Index files are left out, the databases still need to be parsed using the .read() function.
+pub fn thumbscaches_in_zip(reader: impl Read + Seek) -> Result<Vec<(String, Thumbscache)>, ThumbsError> {
+    let mut archive = zip::ZipArchive::new(reader).map_err(|x| ThumbsError::IoError(x.into()))?;
+    let mut databases = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(|x| ThumbsError::IoError(x.into()))?;
+        let name = file.name().to_string();
+        // Bundles collected on Windows may use either separator
+        let file_name = name.rsplit(['/', '\\']).next().unwrap_or_default().to_lowercase();
+        if !file.is_file() || !file_name.ends_with(".db") || file_name.ends_with("_idx.db") || DatabaseKind::from_path(&file_name).is_none() {
+            continue;
+        }
+        databases.push((name, zip_entry_database(&mut file)?));
+    }
+    Ok(databases)
+}
+
+// Decompresses a file of a zip archive, the kind of database comes from its name
+fn zip_entry_database(file: &mut zip::read::ZipFile<'_>) -> Result<Thumbscache, ThumbsError> {
+    let mut bytes = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut bytes).map_err(ThumbsError::IoError)?;
+    let mut database = Thumbscache::from_bytes(bytes);
+    database.kind = DatabaseKind::from_path(file.name().rsplit(['/', '\\']).next().unwrap_or_default()).unwrap_or_default();
+    Ok(database)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_database;
+    use std::io::{Cursor, Write};
+
+    #[test]
+    fn reads_gzip_and_zip_wrapped_databases() {
+        let bytes = build_database(32, 1, &[(1, b"first")]);
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&bytes).unwrap();
+        let mut database = Thumbscache::from_gzip(Cursor::new(encoder.finish().unwrap())).unwrap();
+        assert_eq!(database.read().unwrap(), 1);
+
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for name in ["Explorer/thumbcache_32.db", "Explorer/thumbcache_idx.db", "Explorer/iconcache_32.db", "notes.txt"] {
+            writer.start_file(name, zip::write::SimpleFileOptions::default()).unwrap();
+            writer.write_all(&bytes).unwrap();
+        }
+        let archive = writer.finish().unwrap().into_inner();
+        let mut database = Thumbscache::from_zip(Cursor::new(&archive), "Explorer/thumbcache_32.db").unwrap();
+        assert_eq!(database.read().unwrap(), 1);
+        assert!(matches!(Thumbscache::from_zip(Cursor::new(&archive), "thumbcache_96.db"), Err(ThumbsError::InvalidFile)));
+        let databases = thumbscaches_in_zip(Cursor::new(&archive)).unwrap();
+        let names: Vec<&str> = databases.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["Explorer/thumbcache_32.db", "Explorer/iconcache_32.db"]);
+        assert_eq!(databases[1].1.kind, DatabaseKind::IconCache);
+    }
+}
@@ -0,0 +1,86 @@
+//! Comparison of the counts stored in the database header with the parsed entries
+
+use crate::{EntryOrigin, Thumbscache, ThumbsError};
+
+/// What the header of a database announces next to what was parsed, returned by `Thumbscache::consistency_check()`
+///
+/// Only the live entries are counted, carved and recovered entries aren't part of the chain the header describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ConsistencyReport {
+    /// The number of entries stored in the header, None when the layout of the header isn't known
+    pub header_entry_count: Option<u32>,
+    /// The number of live entries parsed
+    pub parsed_entry_count: u32,
+    /// Where the header says the next entry would be written, the end of the live entries
+    pub header_entries_end: u64,
+    /// Where the last live entry parsed ends, the first entry offset if none was parsed
+    pub parsed_entries_end: u64,
+    /// Bytes between the end of the live entries and the end of the file
+    pub free_bytes: u64
+}
+
+impl ConsistencyReport {
+    /// Returns true if the header stores no count or the count matches the parsed entries
+    pub fn count_matches(&self) -> bool {
+        self.header_entry_count.is_none_or(|x| x == self.parsed_entry_count)
+    }
+
+    /// Returns true if the live entries end where the header says they do
+    pub fn end_matches(&self) -> bool {
+        self.header_entries_end == self.parsed_entries_end
+    }
+
+    /// Returns true if both the count and the end of the entries match the header
+    pub fn is_consistent(&self) -> bool {
+        self.count_matches() && self.end_matches()
+    }
+}
+
+impl Thumbscache {
+    /// Compares the entry count and the first available entry of the header with the parsed entries.
+    ///
+    /// A mismatch means entries were skipped, for instance with `ReadOptions::skip_empty` or because parsing stopped on a damaged entry,
+    /// or that the header was modified. The database has to be read beforehand.
+    pub fn consistency_check(&self) -> Result<ConsistencyReport, ThumbsError> {
+        let header = self.header()?;
+        let len = self.bytes().len() as u64;
+        let live = self.iter().filter(|x| x.origin == EntryOrigin::Live);
+        let header_entries_end = header.first_available_entry as u64;
+        Ok(ConsistencyReport {
+            header_entry_count: header.entry_count,
+            parsed_entry_count: live.clone().count() as u32,
+            header_entries_end,
+            parsed_entries_end: live.map(|x| x.record_range().end).max().unwrap_or(header.first_entry_offset()),
+            free_bytes: len.saturating_sub(header_entries_end)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::build_database, ReadOptions};
+
+    #[test]
+    fn compares_the_header_with_the_entries() {
+        let mut bytes = build_database(32, 1, &[(1, b"first"), (2, b""), (3, b"third")]);
+        bytes.extend_from_slice(&[0; 16]);
+        let mut database = Thumbscache::from_bytes(bytes.clone());
+        database.read().unwrap();
+        let report = database.consistency_check().unwrap();
+        assert!(report.is_consistent());
+        assert_eq!((report.header_entry_count, report.parsed_entry_count, report.free_bytes), (Some(3), 3, 16));
+
+        let mut database = Thumbscache::from_bytes(bytes.clone());
+        database.read_with_options(ReadOptions { skip_empty: true, ..ReadOptions::default() }).unwrap();
+        assert!(!database.consistency_check().unwrap().count_matches());
+
+        // A header claiming more entries than the file holds
+        bytes[20..24].copy_from_slice(&5u32.to_le_bytes());
+        let mut database = Thumbscache::from_bytes(bytes);
+        database.read().unwrap();
+        let report = database.consistency_check().unwrap();
+        assert_eq!((report.count_matches(), report.end_matches()), (false, true));
+    }
+}
@@ -0,0 +1,219 @@
+//! Decoding thumbnails with the image crate, enabled with the `image` feature
+
+#[cfg(feature = "fs")]
+use std::path::Path;
+use std::io::Cursor;
+
+use image::{DynamicImage, RgbaImage};
+
+#[cfg(feature = "fs")]
+use crate::{write_data, ExportSummary, NamingStrategy};
+use crate::{CacheEntry, ImageFormat, Thumbscache, ThumbsError};
+
+// Converts rows of 32-bit BGRA pixels, bottom-up unless stated otherwise.
+// Bitmaps whose alpha bytes are all zero don't use the alpha channel, they are made opaque.
+fn rgba_from_bgra(pixels: &[u8], width: u32, height: u32, top_down: bool) -> Option<RgbaImage> {
+    let stride = (width as usize).checked_mul(4)?;
+    let pixels = pixels.get(..stride.checked_mul(height as usize)?)?;
+    let opaque = pixels.chunks_exact(4).all(|x| x[3] == 0);
+    let mut rgba = Vec::with_capacity(pixels.len());
+    for row in 0..height as usize {
+        let row = if top_down { row } else { height as usize - 1 - row };
+        for x in pixels[row * stride..(row + 1) * stride].chunks_exact(4) {
+            rgba.extend_from_slice(&[x[2], x[1], x[0], if opaque { 255 } else { x[3] }]);
+        }
+    }
+    RgbaImage::from_raw(width, height, rgba)
+}
+
+// Decodes 32-bit uncompressed bitmaps with their alpha channel, which the image crate ignores for them
+fn decode_bgra_bmp(data: &[u8]) -> Option<RgbaImage> {
+    let u16_le = |offset: usize| data.get(offset..offset + 2).map(|x| u16::from_le_bytes([x[0], x[1]]));
+    let u32_le = |offset: usize| data.get(offset..offset + 4).map(|x| u32::from_le_bytes([x[0], x[1], x[2], x[3]]));
+    if ImageFormat::detect(data) != ImageFormat::Bmp || u32_le(14)? < 40 || u16_le(28)? != 32 || u32_le(30)? != 0 {
+        return None;
+    }
+    let width = u32_le(18)? as i32;
+    let height = u32_le(22)? as i32;
+    if width <= 0 || height == 0 {
+        return None;
+    }
+    rgba_from_bgra(data.get(u32_le(10)? as usize..)?, width as u32, height.unsigned_abs(), height < 0)
+}
+
+impl CacheEntry {
+    /// Decodes the data into an image, whatever format Windows stored it in.
+    ///
+    /// The alpha channel of 32-bit bitmaps is kept, and headerless pixel data is decoded when its dimensions can be derived (see `raw_pixel_dimensions()`).
+    pub fn decode_image(&self) -> Result<DynamicImage, ThumbsError> {
+        let pixels = match self.raw_pixel_dimensions() {
+            Some((width, height)) => rgba_from_bgra(&self.data, width, height, false),
+            None => decode_bgra_bmp(&self.data)
+        };
+        match pixels {
+            Some(pixels) => Ok(DynamicImage::ImageRgba8(pixels)),
+            None => image::load_from_memory(&self.data).map_err(|x| ThumbsError::ImageError(x.to_string()))
+        }
+    }
+
+    /// Decodes the data and encodes it as a PNG, keeping the alpha channel
+    pub fn to_png(&self) -> Result<Vec<u8>, ThumbsError> {
+        let mut png = Vec::new();
+        self.decode_image()?.write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png).map_err(|x| ThumbsError::ImageError(x.to_string()))?;
+        Ok(png)
+    }
+
+    /// Decodes the data and saves it into a file using the given format, like PNG, JPEG or WebP.
+    ///
+    /// The alpha channel is dropped for formats that can't store it.
+    #[cfg(feature = "fs")]
+    pub fn save_as(&self, file_path: impl AsRef<Path>, format: image::ImageFormat) -> Result<(), ThumbsError> {
+        let mut image = self.decode_image()?;
+        if format == image::ImageFormat::Jpeg {
+            image = image::DynamicImage::ImageRgb8(image.to_rgb8());
+        }
+        image.save_with_format(file_path, format).map_err(|x| ThumbsError::ImageError(x.to_string()))
+    }
+}
+
+impl Thumbscache {
+    /// Renders every decodable thumbnail into a single PNG montage, for a quick look at everything the cache holds.
+    ///
+    /// The thumbnails are laid out in file order, `columns` per row, scaled up or down to fit in square cells of `cell_size` pixels and centered in them.
+    /// Cells without a thumbnail stay transparent, entries that can't be decoded are left out.
+    pub fn render_contact_sheet(&self, columns: u32, cell_size: u32) -> Result<Vec<u8>, ThumbsError> {
+        if columns == 0 || cell_size == 0 {
+            return Err(ThumbsError::ImageError(String::from("the contact sheet needs at least one column and cells of at least one pixel")));
+        }
+        let thumbnails: Vec<RgbaImage> = self.iter().filter(|x| x.data_size != 0).filter_map(|entry| {
+            let image = if entry.is_data_loaded() {
+                entry.decode_image()
+            }else {
+                let mut entry = entry.clone();
+                entry.load_data(self).and_then(|_| entry.decode_image())
+            };
+            image.ok().map(|x| x.thumbnail(cell_size, cell_size).to_rgba8())
+        }).collect();
+        if thumbnails.is_empty() {
+            return Err(ThumbsError::ImageError(String::from("none of the entries could be decoded")));
+        }
+        let columns = columns.min(thumbnails.len() as u32);
+        let rows = (thumbnails.len() as u32).div_ceil(columns);
+        let too_large = || ThumbsError::ImageError(String::from("the contact sheet is too large"));
+        let mut sheet = RgbaImage::new(columns.checked_mul(cell_size).ok_or_else(too_large)?, rows.checked_mul(cell_size).ok_or_else(too_large)?);
+        for (index, thumbnail) in thumbnails.iter().enumerate() {
+            let x = (index as u32 % columns) * cell_size + (cell_size - thumbnail.width()) / 2;
+            let y = (index as u32 / columns) * cell_size + (cell_size - thumbnail.height()) / 2;
+            image::imageops::overlay(&mut sheet, thumbnail, x as i64, y as i64);
+        }
+        let mut png = Vec::new();
+        sheet.write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png).map_err(|x| ThumbsError::ImageError(x.to_string()))?;
+        Ok(png)
+    }
+}
+
+#[cfg(feature = "fs")]
+impl Thumbscache {
+    /// Converts every entry that has data into a PNG with its alpha channel intact, writing them into the directory.
+    ///
+    /// Unlike `export_all()` the files get the .png extension, entries that can't be decoded are reported as failed.
+    pub fn export_png(&self, dir: impl AsRef<Path>, naming: NamingStrategy) -> Result<ExportSummary, ThumbsError> {
+        std::fs::create_dir_all(&dir).map_err(ThumbsError::IoError)?;
+        let mut summary = ExportSummary::default();
+        for (index, entry) in self.iter().enumerate() {
+            if entry.data_size == 0 {
+                summary.skipped.push(index);
+                continue;
+            }
+            let path = dir.as_ref().join(naming.file_name(index, entry, "png"));
+            match self.loaded_entry(entry).and_then(|x| x.to_png()).and_then(|png| write_data(&path, &png)) {
+                Ok(()) => summary.written.push(path),
+                Err(error) => summary.failed.push((index, error))
+            }
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::build_database, ReadOptions};
+
+    #[test]
+    fn converts_between_formats() {
+        let mut bmp = Vec::new();
+        image::RgbaImage::from_pixel(4, 3, image::Rgba([10, 20, 30, 128])).write_to(&mut Cursor::new(&mut bmp), image::ImageFormat::Bmp).unwrap();
+        let mut database = Thumbscache::from_reader(Cursor::new(build_database(32, 0, &[(1, &bmp), (2, b"garbage")]))).unwrap();
+        database.read().unwrap();
+        let decoded = database.entries()[0].decode_image().unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (4, 3));
+        assert!(database.entries()[1].decode_image().is_err());
+
+        #[cfg(feature = "fs")]
+        let dir = crate::tests::temp_dir("save_as");
+        #[cfg(feature = "fs")]
+        for (name, format) in [("a.png", image::ImageFormat::Png), ("a.jpg", image::ImageFormat::Jpeg), ("a.webp", image::ImageFormat::WebP)] {
+            database.entries()[0].save_as(dir.join(name), format).unwrap();
+            assert_eq!(image::open(dir.join(name)).unwrap().width(), 4);
+        }
+    }
+
+    #[test]
+    fn keeps_the_alpha_channel() {
+        // A 2x1 bottom-up bitmap, one translucent red pixel and one transparent green one
+        let mut bmp = b"BM".to_vec();
+        bmp.extend_from_slice(&(54u32 + 8).to_le_bytes());
+        bmp.extend_from_slice(&[0; 4]);
+        bmp.extend_from_slice(&54u32.to_le_bytes());
+        bmp.extend_from_slice(&40u32.to_le_bytes());
+        bmp.extend_from_slice(&2i32.to_le_bytes());
+        bmp.extend_from_slice(&1i32.to_le_bytes());
+        bmp.extend_from_slice(&1u16.to_le_bytes());
+        bmp.extend_from_slice(&32u16.to_le_bytes());
+        bmp.resize(54, 0);
+        bmp.extend_from_slice(&[0, 0, 255, 128, 0, 255, 0, 0]);
+        // Headerless pixels filling a square of the 16px cache
+        let mut raw = vec![0u8; 16 * 16 * 4];
+        raw[3] = 7;
+        let mut database = Thumbscache::from_bytes(build_database(32, 0, &[(1, &bmp), (2, &raw)]));
+        database.read().unwrap();
+
+        let png = image::load_from_memory(&database.entries()[0].to_png().unwrap()).unwrap().to_rgba8();
+        assert_eq!(png.get_pixel(0, 0).0, [255, 0, 0, 128]);
+        assert_eq!(png.get_pixel(1, 0).0, [0, 255, 0, 0]);
+        let png = image::load_from_memory(&database.entries()[1].to_png().unwrap()).unwrap().to_rgba8();
+        assert_eq!(png.dimensions(), (16, 16));
+        // The first stored row is the bottom one
+        assert_eq!(png.get_pixel(0, 15).0[3], 7);
+
+        #[cfg(feature = "fs")]
+        {
+            let dir = crate::tests::temp_dir("export_png");
+            let summary = database.export_png(&dir, NamingStrategy::Index).unwrap();
+            assert_eq!(summary.written, [dir.join("0.png"), dir.join("1.png")]);
+        }
+    }
+
+    #[test]
+    fn renders_contact_sheets() {
+        let thumbnail = |width, height, color| {
+            let mut png = Vec::new();
+            image::RgbaImage::from_pixel(width, height, image::Rgba(color)).write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png).unwrap();
+            png
+        };
+        let (red, green, blue) = (thumbnail(8, 4, [255, 0, 0, 255]), thumbnail(2, 2, [0, 255, 0, 255]), thumbnail(4, 4, [0, 0, 255, 255]));
+        let mut database = Thumbscache::from_bytes(build_database(32, 0, &[(1, &red), (2, b""), (3, b"garbage"), (4, &green), (5, &blue)]));
+        database.read_with_options(ReadOptions::lazy()).unwrap();
+        let sheet = image::load_from_memory(&database.render_contact_sheet(2, 4).unwrap()).unwrap().to_rgba8();
+        assert_eq!(sheet.dimensions(), (8, 8));
+        // The wide thumbnail is scaled down and centered vertically
+        assert_eq!(sheet.get_pixel(0, 0).0, [0, 0, 0, 0]);
+        assert_eq!(sheet.get_pixel(0, 1).0, [255, 0, 0, 255]);
+        // Smaller thumbnails are scaled up to the cell
+        assert_eq!(sheet.get_pixel(4, 0).0, [0, 255, 0, 255]);
+        assert_eq!(sheet.get_pixel(1, 5).0, [0, 0, 255, 255]);
+        assert_eq!(sheet.get_pixel(5, 5).0, [0, 0, 0, 0]);
+        assert!(database.render_contact_sheet(0, 4).is_err());
+    }
+}
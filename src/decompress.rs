@@ -0,0 +1,112 @@
+//! Decompression of wrapped payloads, enabled with the `decompress` feature
+//!
+//! Some applications write compressed streams to the CustomStream cache instead of images.
+//! gzip and zlib streams are always decompressed, zstd frames need the `zstd` feature as well.
+//! The output is capped so a small entry can't expand into gigabytes.
+
+use std::{borrow::Cow, io::Read};
+
+use crate::{CacheEntry, PayloadKind, ThumbsError};
+
+// The most bytes a payload is allowed to decompress to
+const DECOMPRESSED_LIMIT: u64 = 256 * 1024 * 1024;
+
+/// A compression wrapping the data of an entry, see `CacheEntry::compression()`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub enum Compression {
+    Gzip,
+    Zlib,
+    Zstd
+}
+
+impl Compression {
+    /// Detects the compression of the data by looking at its magic number
+    pub fn detect(data: &[u8]) -> Option<Compression> {
+        match data {
+            [0x1F, 0x8B, 0x08, ..] => Some(Compression::Gzip),
+            [0x28, 0xB5, 0x2F, 0xFD, ..] => Some(Compression::Zstd),
+            // Deflate with a window of at most 32 KiB, the two header bytes being a multiple of 31
+            [cmf, flg, ..] if cmf & 0x0F == 8 && cmf >> 4 <= 7 && u16::from_be_bytes([*cmf, *flg]) % 31 == 0 => Some(Compression::Zlib),
+            _ => None
+        }
+    }
+
+    /// Decompresses the data, failing if it is damaged or expands past 256 MiB
+    pub fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, ThumbsError> {
+        let reader: Box<dyn Read + '_> = match self {
+            Compression::Gzip => Box::new(flate2::read::GzDecoder::new(data)),
+            Compression::Zlib => Box::new(flate2::read::ZlibDecoder::new(data)),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(data).map_err(ThumbsError::IoError)?),
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd => return Err(ThumbsError::IoError(std::io::Error::new(std::io::ErrorKind::Unsupported, "zstd payloads need the zstd feature")))
+        };
+        let mut bytes = Vec::new();
+        reader.take(DECOMPRESSED_LIMIT + 1).read_to_end(&mut bytes).map_err(ThumbsError::IoError)?;
+        if bytes.len() as u64 > DECOMPRESSED_LIMIT {
+            return Err(ThumbsError::IoError(std::io::Error::other("the payload decompresses to more than 256 MiB")));
+        }
+        Ok(bytes)
+    }
+}
+
+impl CacheEntry {
+    /// The compression wrapping the data, None if it isn't compressed
+    pub fn compression(&self) -> Option<Compression> {
+        Compression::detect(&self.data)
+    }
+
+    /// The data with its compression removed, borrowed as is when it isn't compressed
+    pub fn decompressed_data(&self) -> Result<Cow<'_, [u8]>, ThumbsError> {
+        match self.compression() {
+            Some(compression) => compression.decompress(&self.data).map(Cow::Owned),
+            None => Ok(Cow::Borrowed(&self.data))
+        }
+    }
+
+    /// Classifies the decompressed data like `payload_kind()` does, falling back to the data as stored if it can't be decompressed
+    pub fn decompressed_payload_kind(&self) -> PayloadKind {
+        match self.decompressed_data() {
+            Ok(data) => PayloadKind::detect(&data, self.cache_type),
+            Err(_) => self.payload_kind()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_database, tests::build_database, ImageFormat, ReadOptions};
+    use std::io::Write;
+
+    #[test]
+    fn unwraps_compressed_payloads() {
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(b"\x89PNG\r\n\x1a\nimage").unwrap();
+        let gzip = gzip.finish().unwrap();
+        let mut zlib = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+        zlib.write_all(b"custom stream").unwrap();
+        let zlib = zlib.finish().unwrap();
+        let bytes = build_database(32, 13, &[(1, &gzip), (2, &zlib), (3, b"BMplain"), (4, &gzip[..12])]);
+        let (_, entries) = parse_database(&bytes, ReadOptions::default()).unwrap();
+
+        assert_eq!(entries[0].compression(), Some(Compression::Gzip));
+        assert_eq!(entries[0].payload_kind(), PayloadKind::Stream);
+        assert_eq!(entries[0].decompressed_payload_kind(), PayloadKind::Image(ImageFormat::Png));
+        assert_eq!(entries[1].compression(), Some(Compression::Zlib));
+        assert_eq!(&*entries[1].decompressed_data().unwrap(), b"custom stream");
+        assert_eq!(entries[2].compression(), None);
+        assert!(matches!(entries[2].decompressed_data().unwrap(), Cow::Borrowed(b"BMplain")));
+        // A truncated stream
+        assert!(entries[3].decompressed_data().is_err());
+        assert_eq!(entries[3].decompressed_payload_kind(), PayloadKind::Stream);
+
+        #[cfg(feature = "zstd")]
+        {
+            let frame = zstd::encode_all(&b"frame"[..], 0).unwrap();
+            assert_eq!(Compression::detect(&frame).unwrap().decompress(&frame).unwrap(), b"frame");
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Comparing two snapshots of the same database
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{CacheEntry, Thumbscache};
+
+/// Differences between two snapshots of a database, see `Thumbscache::diff()`
+///
+/// Entries are matched by their entry hash, in the order of the snapshot they come from.
+#[derive(Debug, Clone, Default)]
+pub struct CacheDiff<'a> {
+    /// Entries only found in the newer snapshot
+    pub added: Vec<&'a CacheEntry>,
+    /// Entries only found in the older snapshot
+    pub removed: Vec<&'a CacheEntry>,
+    /// Entries found in both whose data changed, as (older, newer) pairs
+    pub changed: Vec<(&'a CacheEntry, &'a CacheEntry)>,
+    /// Number of entries found unchanged in both
+    pub unchanged: usize
+}
+
+impl CacheDiff<'_> {
+    /// Returns true if both snapshots hold the same entries
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl Thumbscache {
+    /// Compares the parsed entries against a newer snapshot of the same database.
+    ///
+    /// Entries are matched by entry hash, a matched entry changed if its data checksum or data size differs.
+    /// When a hash is stored more than once, only its first entry is compared.
+    pub fn diff<'a>(&'a self, newer: &'a Thumbscache) -> CacheDiff<'a> {
+        let mut older_entries: HashMap<u64, &CacheEntry> = HashMap::new();
+        for entry in self.iter() {
+            older_entries.entry(entry.entry_hash).or_insert(entry);
+        }
+        let mut diff = CacheDiff::default();
+        let mut seen: HashSet<u64> = HashSet::new();
+        for entry in newer.iter() {
+            if !seen.insert(entry.entry_hash) {
+                continue;
+            }
+            match older_entries.get(&entry.entry_hash) {
+                Some(older) if older.data_checksum != entry.data_checksum || older.data_size != entry.data_size => diff.changed.push((older, entry)),
+                Some(_) => diff.unchanged += 1,
+                None => diff.added.push(entry)
+            }
+        }
+        diff.removed = self.iter().filter(|x| !seen.contains(&x.entry_hash)).collect();
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_database;
+
+    #[test]
+    fn reports_added_removed_and_changed_entries() {
+        let mut older = Thumbscache::from_bytes(build_database(32, 1, &[(1, b"kept"), (2, b"removed"), (3, b"before")]));
+        older.read().unwrap();
+        let mut newer = Thumbscache::from_bytes(build_database(32, 1, &[(1, b"kept"), (3, b"after!"), (4, b"added")]));
+        newer.read().unwrap();
+        let diff = older.diff(&newer);
+        let hashes = |entries: &[&CacheEntry]| entries.iter().map(|x| x.entry_hash()).collect::<Vec<u64>>();
+        assert_eq!(hashes(&diff.added), [4]);
+        assert_eq!(hashes(&diff.removed), [2]);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!((diff.changed[0].0.data.as_slice(), diff.changed[0].1.data.as_slice()), (&b"before"[..], &b"after!"[..]));
+        assert_eq!(diff.unchanged, 1);
+        assert!(older.diff(&older).is_empty());
+    }
+}
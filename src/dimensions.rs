@@ -0,0 +1,111 @@
+//! Thumbnail dimensions, from the entry header or the image header of the data
+
+use crate::{CacheEntry, ImageFormat, VersionFields};
+
+// Reads the width and height from the image header, without decoding the image
+pub(crate) fn sniff_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let u16_be = |offset: usize| data.get(offset..offset + 2).map(|x| u16::from_be_bytes([x[0], x[1]]) as u32);
+    let u32_be = |offset: usize| data.get(offset..offset + 4).map(|x| u32::from_be_bytes([x[0], x[1], x[2], x[3]]));
+    let u16_le = |offset: usize| data.get(offset..offset + 2).map(|x| u16::from_le_bytes([x[0], x[1]]) as u32);
+    let i32_le = |offset: usize| data.get(offset..offset + 4).map(|x| i32::from_le_bytes([x[0], x[1], x[2], x[3]]));
+    match ImageFormat::detect(data) {
+        ImageFormat::Bmp => {
+            // The old OS/2 header stores 16-bit dimensions, the others store signed 32-bit ones (a negative height means top-down)
+            if i32_le(14)? == 12 {
+                Some((u16_le(18)?, u16_le(20)?))
+            }else {
+                Some((i32_le(18)?.unsigned_abs(), i32_le(22)?.unsigned_abs()))
+            }
+        },
+        // The IHDR chunk always comes first
+        ImageFormat::Png => Some((u32_be(16)?, u32_be(20)?)),
+        ImageFormat::Jpeg => {
+            let mut offset = 2;
+            loop {
+                if *data.get(offset)? != 0xFF {
+                    return None;
+                }
+                let marker = *data.get(offset + 1)?;
+                // Start of frame markers, except DHT, JPG and DAC which share the range
+                if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+                    return Some((u16_be(offset + 7)?, u16_be(offset + 5)?));
+                }
+                offset += 2 + u16_be(offset + 2)? as usize;
+            }
+        },
+        ImageFormat::Unknown => None
+    }
+}
+
+impl CacheEntry {
+    /// The width and height of the thumbnail.
+    ///
+    /// Windows 8 and later store them in the entry header, otherwise they are read from the image header of the data.
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        match self.version_fields {
+            VersionFields::Win8 { width, height, .. } if width != 0 && height != 0 => Some((width, height)),
+            _ => sniff_dimensions(&self.data)
+        }
+    }
+
+    /// Checks that the thumbnail fits the cache type of its database, its larger side being at most `CacheType::max_dimension()`.
+    ///
+    /// Returns None when the dimensions are unknown or the cache type isn't tied to a resolution.
+    /// A thumbnail too large for its database was likely planted or copied from another cache.
+    pub fn fits_cache_type(&self) -> Option<bool> {
+        let max_dimension = self.cache_type?.max_dimension()?;
+        let (width, height) = self.dimensions()?;
+        Some(width.max(height) <= max_dimension)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniffs_image_headers() {
+        let mut bmp = b"BM".to_vec();
+        bmp.resize(14, 0);
+        bmp.extend_from_slice(&40i32.to_le_bytes());
+        bmp.extend_from_slice(&96i32.to_le_bytes());
+        bmp.extend_from_slice(&(-64i32).to_le_bytes());
+        assert_eq!(sniff_dimensions(&bmp), Some((96, 64)));
+
+        let mut png = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 13];
+        png.extend_from_slice(b"IHDR");
+        png.extend_from_slice(&256u32.to_be_bytes());
+        png.extend_from_slice(&144u32.to_be_bytes());
+        assert_eq!(sniff_dimensions(&png), Some((256, 144)));
+
+        // An APP0 segment followed by a baseline start of frame
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE0, 0, 4, 0, 0, 0xFF, 0xC0, 0, 11, 8];
+        jpeg.extend_from_slice(&32u16.to_be_bytes());
+        jpeg.extend_from_slice(&48u16.to_be_bytes());
+        assert_eq!(sniff_dimensions(&jpeg), Some((48, 32)));
+
+        assert_eq!(sniff_dimensions(b"BM"), None);
+        assert_eq!(sniff_dimensions(b"data"), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn flags_thumbnails_too_large_for_their_database() {
+        let png = |width: u32, height: u32| {
+            let mut png = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 13];
+            png.extend_from_slice(b"IHDR");
+            png.extend_from_slice(&width.to_be_bytes());
+            png.extend_from_slice(&height.to_be_bytes());
+            png
+        };
+        let (fits, too_wide, square) = (png(32, 20), png(33, 20), png(32, 32));
+        let mut database = crate::Thumbscache::from_bytes(crate::tests::build_database(21, 0, &[(1, &fits), (2, &too_wide), (3, &square), (4, b"data")]));
+        database.read().unwrap();
+        let fits: Vec<Option<bool>> = database.iter().map(|x| x.fits_cache_type()).collect();
+        assert_eq!(fits, [Some(true), Some(false), Some(true), None]);
+        assert_eq!(database.dimension_mismatches().iter().map(|x| x.entry_hash()).collect::<Vec<_>>(), [2]);
+
+        assert_eq!(crate::CacheType::Res2560.max_dimension(), Some(2560));
+        assert!(crate::CacheType::Res16.is_resolution() && !crate::CacheType::SR.is_resolution());
+    }
+}
@@ -0,0 +1,55 @@
+//! Byte order of the on-disk formats
+//!
+//! Every number in the thumbnail cache, the index file and Thumbs.db is stored little-endian,
+//! so the fields are decoded explicitly instead of using the byte order of the machine running the parser.
+
+use alloc::{string::String, vec::Vec};
+
+/// Reads little-endian fields at a byte offset.
+///
+/// Panics if the field doesn't fit in the slice, callers read from buffers whose length they checked.
+// Only Thumbs.db has 16-bit fields
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub(crate) trait LittleEndian {
+    fn u16_le(&self, offset: usize) -> u16;
+    fn u32_le(&self, offset: usize) -> u32;
+    fn u64_le(&self, offset: usize) -> u64;
+}
+
+impl LittleEndian for [u8] {
+    fn u16_le(&self, offset: usize) -> u16 {
+        u16::from_le_bytes([self[offset], self[offset + 1]])
+    }
+
+    fn u32_le(&self, offset: usize) -> u32 {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(&self[offset..offset + 4]);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn u64_le(&self, offset: usize) -> u64 {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&self[offset..offset + 8]);
+        u64::from_le_bytes(bytes)
+    }
+}
+
+// Decodes UTF-16LE text, an odd trailing byte is ignored
+pub(crate) fn utf16_le(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|a| u16::from_le_bytes([a[0], a[1]])).collect();
+    String::from_utf16_lossy(&units)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_little_endian_on_any_target() {
+        let bytes = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09];
+        assert_eq!(bytes[1..].u16_le(0), 0x0302);
+        assert_eq!(bytes.u32_le(1), 0x0504_0302);
+        assert_eq!(bytes.u64_le(1), 0x0908_0706_0504_0302);
+        assert_eq!(utf16_le(b"a\0b\0c"), "ab");
+    }
+}
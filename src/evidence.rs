@@ -0,0 +1,71 @@
+//! Reading databases straight out of disk images
+//!
+//! Raw and E01 image libraries expose the disk as a seekable reader, so the cache can be read from the sectors holding it
+//! without extracting it into a temporary file first.
+
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::{Thumbscache, ThumbsError};
+
+/// A source of evidence holding a database somewhere inside it, such as a disk image
+///
+/// Every `Read + Seek` type is a source, implement it directly for readers that offer positioned reads.
+pub trait EvidenceSource {
+    /// Fills the buffer with the bytes starting at the offset
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> std::io::Result<()>;
+}
+
+impl<T: Read + Seek> EvidenceSource for T {
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        self.seek(SeekFrom::Start(offset))?;
+        self.read_exact(buf)
+    }
+}
+
+impl Thumbscache {
+    /// Reads the database stored in the len bytes starting at the offset of the source.
+    /// Additional parsing is neccessary using the .read() function.
+    pub fn from_evidence(source: &mut impl EvidenceSource, offset: u64, len: u64) -> Result<Thumbscache, ThumbsError> {
+        Thumbscache::from_evidence_extents(source, &[(offset, len)])
+    }
+
+    /// Reads a fragmented database, made of the (offset, length) extents of the source in order.
+    /// Additional parsing is neccessary using the .read() function.
+    pub fn from_evidence_extents(source: &mut impl EvidenceSource, extents: &[(u64, u64)]) -> Result<Thumbscache, ThumbsError> {
+        let mut bytes = Vec::new();
+        for (offset, len) in extents {
+            let start = bytes.len();
+            let len = usize::try_from(*len).map_err(|_| ThumbsError::IoError(std::io::ErrorKind::InvalidInput.into()))?;
+            bytes.resize(start + len, 0);
+            source.read_at(*offset, &mut bytes[start..]).map_err(ThumbsError::IoError)?;
+        }
+        Ok(Thumbscache::from_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_database;
+    use std::io::Cursor;
+
+    #[test]
+    fn reads_databases_out_of_an_image() {
+        let database = build_database(32, 1, &[(1, b"first"), (2, b"second")]);
+        let mut image = vec![0xAA; 512];
+        image.extend_from_slice(&database);
+        image.extend_from_slice(&[0xAA; 512]);
+        let mut source = Cursor::new(image);
+        let mut a = Thumbscache::from_evidence(&mut source, 512, database.len() as u64).unwrap();
+        assert_eq!(a.read().unwrap(), 2);
+
+        // The same database split in two fragments, stored in reverse order
+        let (first, second) = database.split_at(100);
+        let mut image = second.to_vec();
+        image.extend_from_slice(first);
+        let mut source = Cursor::new(image);
+        let mut a = Thumbscache::from_evidence_extents(&mut source, &[(second.len() as u64, 100), (0, second.len() as u64)]).unwrap();
+        assert_eq!(a.read().unwrap(), 2);
+        assert!(Thumbscache::from_evidence(&mut source, 0, 10_000).is_err());
+    }
+}
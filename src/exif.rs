@@ -0,0 +1,320 @@
+//! Parsing the EXIF metadata held by entries of the EXIF cache
+//!
+//! The payload is a TIFF structure, either on its own, after an "Exif\0\0" marker or inside the APP1 segment of a JPEG.
+//! Its directories are decoded into fields, the well-known ones are available through accessors.
+
+use alloc::{string::String, vec::Vec};
+
+use crate::CacheEntry;
+
+// Directory pointers of the TIFF structure
+const EXIF_POINTER: u16 = 0x8769;
+const GPS_POINTER: u16 = 0x8825;
+
+/// The directory of the EXIF data a field belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ExifDirectory {
+    /// IFD0, describing the image
+    Image,
+    /// The Exif sub-directory, describing how the picture was taken
+    Exif,
+    /// The GPS sub-directory
+    Gps,
+    /// IFD1, describing the embedded thumbnail
+    Thumbnail
+}
+
+/// The value of an EXIF field
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ExifValue {
+    Byte(Vec<u8>),
+    /// Text, without the terminating null
+    Ascii(String),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    /// Numerator and denominator pairs
+    Rational(Vec<(u32, u32)>),
+    SignedLong(Vec<i32>),
+    SignedRational(Vec<(i32, i32)>),
+    /// Fields of any other type, as raw bytes
+    Undefined(Vec<u8>)
+}
+
+impl ExifValue {
+    /// The first value as an unsigned integer, for byte, short and long fields
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            ExifValue::Byte(x) => x.first().map(|x| *x as u32),
+            ExifValue::Short(x) => x.first().map(|x| *x as u32),
+            ExifValue::Long(x) => x.first().copied(),
+            _ => None
+        }
+    }
+
+    /// The text of ASCII fields
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ExifValue::Ascii(x) => Some(x),
+            _ => None
+        }
+    }
+}
+
+/// A single EXIF field
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ExifField {
+    pub directory: ExifDirectory,
+    pub tag: u16,
+    pub value: ExifValue
+}
+
+/// EXIF data
+///
+/// Every field found in the directories of the payload, in file order.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ExifData {
+    pub fields: Vec<ExifField>
+}
+
+// Reads the numbers of a TIFF structure in the byte order given by its header
+struct Tiff<'a> {
+    bytes: &'a [u8],
+    big_endian: bool
+}
+
+impl Tiff<'_> {
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let x = self.bytes.get(offset..offset.checked_add(2)?)?;
+        Some(if self.big_endian { u16::from_be_bytes([x[0], x[1]]) } else { u16::from_le_bytes([x[0], x[1]]) })
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let x = self.bytes.get(offset..offset.checked_add(4)?)?;
+        Some(if self.big_endian { u32::from_be_bytes([x[0], x[1], x[2], x[3]]) } else { u32::from_le_bytes([x[0], x[1], x[2], x[3]]) })
+    }
+
+    // Decodes the value of the directory entry at the offset
+    fn value(&self, entry: usize) -> Option<ExifValue> {
+        let field_type = self.u16(entry + 2)?;
+        let count = self.u32(entry + 4)? as usize;
+        let size = match field_type {
+            3 | 8 => 2,
+            4 | 9 | 11 => 4,
+            5 | 10 | 12 => 8,
+            _ => 1
+        };
+        let len = count.checked_mul(size)?;
+        // Values of up to 4 bytes are stored in the entry itself
+        let start = if len <= 4 { entry + 8 } else { self.u32(entry + 8)? as usize };
+        let bytes = self.bytes.get(start..start.checked_add(len)?)?;
+        let offsets = (0..count).map(|i| start + i * size);
+        Some(match field_type {
+            1 => ExifValue::Byte(bytes.to_vec()),
+            2 => ExifValue::Ascii(String::from_utf8_lossy(bytes).trim_end_matches('\0').into()),
+            3 => ExifValue::Short(offsets.map(|x| self.u16(x)).collect::<Option<_>>()?),
+            4 => ExifValue::Long(offsets.map(|x| self.u32(x)).collect::<Option<_>>()?),
+            5 => ExifValue::Rational(offsets.map(|x| Some((self.u32(x)?, self.u32(x + 4)?))).collect::<Option<_>>()?),
+            9 => ExifValue::SignedLong(offsets.map(|x| self.u32(x).map(|x| x as i32)).collect::<Option<_>>()?),
+            10 => ExifValue::SignedRational(offsets.map(|x| Some((self.u32(x)? as i32, self.u32(x + 4)? as i32))).collect::<Option<_>>()?),
+            _ => ExifValue::Undefined(bytes.to_vec())
+        })
+    }
+
+    // Adds the fields of the directory at the offset, returning the offset of the next directory
+    fn read_directory(&self, offset: usize, directory: ExifDirectory, fields: &mut Vec<ExifField>) -> Option<usize> {
+        let count = self.u16(offset)? as usize;
+        for i in 0..count {
+            let entry = offset + 2 + i * 12;
+            let tag = self.u16(entry)?;
+            // A damaged value doesn't prevent reading the other fields
+            if let Some(value) = self.value(entry) {
+                fields.push(ExifField { directory, tag, value });
+            }
+        }
+        Some(self.u32(offset + 2 + count * 12)? as usize)
+    }
+}
+
+// Finds the TIFF structure inside the payload
+fn find_tiff(bytes: &[u8]) -> Option<&[u8]> {
+    if bytes.starts_with(b"II*\0") || bytes.starts_with(b"MM\0*") {
+        return Some(bytes);
+    }
+    if let Some(tiff) = bytes.strip_prefix(b"Exif\0\0") {
+        return Some(tiff);
+    }
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut offset = 2;
+    loop {
+        if *bytes.get(offset)? != 0xFF {
+            return None;
+        }
+        let marker = *bytes.get(offset + 1)?;
+        let len = u16::from_be_bytes([*bytes.get(offset + 2)?, *bytes.get(offset + 3)?]) as usize;
+        let segment = bytes.get(offset + 4..offset + 2 + len)?;
+        if marker == 0xE1 && segment.starts_with(b"Exif\0\0") {
+            return Some(&segment[6..]);
+        }
+        // The image data starts after the start of scan, no metadata past it
+        if marker == 0xDA {
+            return None;
+        }
+        offset += 2 + len;
+    }
+}
+
+impl ExifData {
+    /// Parses the EXIF metadata of a payload, returns None if no TIFF structure was found
+    pub fn parse(bytes: &[u8]) -> Option<ExifData> {
+        let bytes = find_tiff(bytes)?;
+        let tiff = Tiff {
+            bytes,
+            big_endian: bytes.starts_with(b"MM")
+        };
+        if tiff.u16(2)? != 42 {
+            return None;
+        }
+        let mut fields = Vec::new();
+        let next = tiff.read_directory(tiff.u32(4)? as usize, ExifDirectory::Image, &mut fields);
+        // The sub-directories are only followed from IFD0, so a loop of pointers can't keep us going
+        let pointers: Vec<(ExifDirectory, usize)> = fields.iter().filter_map(|field| match field.tag {
+            EXIF_POINTER => Some((ExifDirectory::Exif, field.value.as_u32()? as usize)),
+            GPS_POINTER => Some((ExifDirectory::Gps, field.value.as_u32()? as usize)),
+            _ => None
+        }).collect();
+        for (directory, offset) in pointers {
+            tiff.read_directory(offset, directory, &mut fields);
+        }
+        if let Some(next) = next.filter(|x| *x != 0) {
+            tiff.read_directory(next, ExifDirectory::Thumbnail, &mut fields);
+        }
+        Some(ExifData { fields })
+    }
+
+    /// Returns the value of the field with the tag in the directory
+    pub fn get(&self, directory: ExifDirectory, tag: u16) -> Option<&ExifValue> {
+        self.fields.iter().find(|x| x.directory == directory && x.tag == tag).map(|x| &x.value)
+    }
+
+    /// The manufacturer of the camera
+    pub fn make(&self) -> Option<&str> {
+        self.get(ExifDirectory::Image, 0x010F)?.as_str()
+    }
+
+    /// The model of the camera
+    pub fn model(&self) -> Option<&str> {
+        self.get(ExifDirectory::Image, 0x0110)?.as_str()
+    }
+
+    /// How the image is rotated or mirrored, from 1 to 8
+    pub fn orientation(&self) -> Option<u32> {
+        self.get(ExifDirectory::Image, 0x0112)?.as_u32()
+    }
+
+    /// When the picture was taken as "YYYY:MM:DD HH:MM:SS", falling back to when the file was last changed
+    pub fn date_time(&self) -> Option<&str> {
+        self.get(ExifDirectory::Exif, 0x9003).or_else(|| self.get(ExifDirectory::Image, 0x0132))?.as_str()
+    }
+
+    /// The latitude and longitude in degrees, negative in the southern and western hemispheres
+    pub fn gps_coordinates(&self) -> Option<(f64, f64)> {
+        let coordinate = |reference_tag: u16, tag: u16, negative: char| -> Option<f64> {
+            let ExifValue::Rational(parts) = self.get(ExifDirectory::Gps, tag)? else {
+                return None;
+            };
+            let mut degrees = 0.0;
+            for ((numerator, denominator), unit) in parts.iter().zip([1.0, 60.0, 3600.0]) {
+                if *denominator != 0 {
+                    degrees += *numerator as f64 / *denominator as f64 / unit;
+                }
+            }
+            let reference = self.get(ExifDirectory::Gps, reference_tag).and_then(|x| x.as_str()).unwrap_or_default();
+            Some(if reference.starts_with(negative) { -degrees } else { degrees })
+        };
+        Some((coordinate(1, 2, 'S')?, coordinate(3, 4, 'W')?))
+    }
+}
+
+impl CacheEntry {
+    /// Parses the data as EXIF metadata, the way the EXIF cache stores it
+    pub fn exif(&self) -> Option<ExifData> {
+        ExifData::parse(&self.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Builds a little-endian directory at the offset, the values larger than 4 bytes are placed right after it
+    fn directory(offset: u32, fields: &[(u16, u16, u32, &[u8])], next: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut data = Vec::new();
+        let data_offset = offset + 2 + fields.len() as u32 * 12 + 4;
+        bytes.extend_from_slice(&(fields.len() as u16).to_le_bytes());
+        for (tag, field_type, count, value) in fields {
+            bytes.extend_from_slice(&tag.to_le_bytes());
+            bytes.extend_from_slice(&field_type.to_le_bytes());
+            bytes.extend_from_slice(&count.to_le_bytes());
+            if value.len() <= 4 {
+                let mut inline = value.to_vec();
+                inline.resize(4, 0);
+                bytes.extend_from_slice(&inline);
+            }else {
+                bytes.extend_from_slice(&(data_offset + data.len() as u32).to_le_bytes());
+                data.extend_from_slice(value);
+            }
+        }
+        bytes.extend_from_slice(&next.to_le_bytes());
+        bytes.extend_from_slice(&data);
+        bytes
+    }
+
+    fn rationals(values: &[(u32, u32)]) -> Vec<u8> {
+        values.iter().flat_map(|(x, y)| x.to_le_bytes().into_iter().chain(y.to_le_bytes())).collect()
+    }
+
+    #[test]
+    fn decodes_exif_payloads() {
+        let mut tiff = b"II*\0\x08\0\0\0".to_vec();
+        // IFD0 holds 4 fields, only the make is stored after it
+        let exif_offset = 8 + 2 + 4 * 12 + 4 + 6;
+        let exif = directory(exif_offset, &[(0x9003, 2, 20, b"2023:05:01 12:30:00\0")], 0);
+        let gps_offset = exif_offset + exif.len() as u32;
+        let latitude = rationals(&[(47, 1), (30, 1), (0, 1)]);
+        let longitude = rationals(&[(19, 1), (3, 1), (0, 1)]);
+        let gps = directory(gps_offset, &[(1, 2, 2, b"N\0"), (2, 5, 3, &latitude), (3, 2, 2, b"W\0"), (4, 5, 3, &longitude)], 0);
+        tiff.extend_from_slice(&directory(8, &[(0x010F, 2, 6, b"Canon\0"), (0x0112, 3, 1, &6u16.to_le_bytes()), (EXIF_POINTER, 4, 1, &exif_offset.to_le_bytes()), (GPS_POINTER, 4, 1, &gps_offset.to_le_bytes())], 0));
+        tiff.extend_from_slice(&exif);
+        tiff.extend_from_slice(&gps);
+
+        let mut payload = b"Exif\0\0".to_vec();
+        payload.extend_from_slice(&tiff);
+        let data = ExifData::parse(&payload).unwrap();
+        assert_eq!(data.make(), Some("Canon"));
+        assert_eq!(data.model(), None);
+        assert_eq!(data.orientation(), Some(6));
+        assert_eq!(data.date_time(), Some("2023:05:01 12:30:00"));
+        let (latitude, longitude) = data.gps_coordinates().unwrap();
+        assert!((latitude - 47.5).abs() < 1e-9 && (longitude + 19.05).abs() < 1e-9);
+
+        // The same metadata inside a JPEG
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        jpeg.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+        jpeg.extend_from_slice(&payload);
+        jpeg.extend_from_slice(&[0xFF, 0xDA, 0, 2]);
+        assert_eq!(ExifData::parse(&jpeg), Some(data));
+
+        // Every truncation has to fail gracefully
+        for len in 0..tiff.len() {
+            let _ = ExifData::parse(&tiff[..len]);
+        }
+        assert_eq!(ExifData::parse(b"BM..."), None);
+    }
+}
@@ -0,0 +1,549 @@
+//! Bulk export of cache entries into a directory
+
+use std::{borrow::Cow, collections::HashMap, path::{Path, PathBuf}, sync::atomic::{AtomicBool, Ordering}};
+
+use crate::{checksum::crc64, write_data, CacheEntry, PayloadKind, ThumbcacheSet, Thumbscache, ThumbsError};
+
+// Keeps the characters of an untrusted name that are safe in a file name, so it can't point outside of the directory it's joined to.
+// Returns None when nothing usable is left.
+pub(crate) fn sanitize_file_name(name: &str) -> Option<String> {
+    let sanitized: String = name.chars().filter(|x| x.is_ascii_alphanumeric() || matches!(x, '.' | '_' | '-')).collect();
+    if sanitized.chars().all(|x| x == '.') {
+        return None;
+    }
+    Some(sanitized)
+}
+
+impl CacheEntry {
+    // The identifier string made safe to use as a file name, the entry hash when nothing of it is usable
+    pub(crate) fn file_stem(&self) -> String {
+        sanitize_file_name(&self.identifier_string).unwrap_or_else(|| self.entry_hash_hex())
+    }
+}
+
+/// A closure naming an exported file from the position of the entry and the entry itself
+pub type NameFn = Box<dyn Fn(usize, &CacheEntry) -> String>;
+
+/// How exported files are named by `Thumbscache::export_all()`
+pub enum NamingStrategy {
+    /// Named after the identifier string (the entry hash), with the extension of its payload kind.
+    /// Characters other than ASCII letters, digits, '.', '_' and '-' are left out, the entry hash is used when nothing is left
+    Identifier,
+    /// Named after the position of the entry in the database, with the extension of its payload kind
+    Index,
+    /// Named by a closure, the returned name is relative to the export directory
+    Custom(NameFn)
+}
+
+impl NamingStrategy {
+    pub(crate) fn file_name(&self, index: usize, entry: &CacheEntry, extension: &str) -> String {
+        match self {
+            NamingStrategy::Identifier => format!("{}.{}", entry.file_stem(), extension),
+            NamingStrategy::Index => format!("{}.{}", index, extension),
+            NamingStrategy::Custom(name) => name(index, entry)
+        }
+    }
+}
+
+/// What happens when the file an entry is written to already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CollisionPolicy {
+    /// The existing file is replaced, like `export_all()` does
+    #[default]
+    Overwrite,
+    /// The existing file is kept and the entry isn't written
+    Skip,
+    /// The entry is written next to it with a numbered suffix, name_1.ext, name_2.ext and so on
+    RenameWithSuffix,
+    /// Writing fails with an IoError of kind AlreadyExists
+    Error
+}
+
+/// Where an entry was written and how a collision was handled, see `CollisionPolicy`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteOutcome {
+    /// No file existed at the path
+    Created(PathBuf),
+    /// The existing file was replaced
+    Overwritten(PathBuf),
+    /// The existing file was kept, nothing was written
+    Skipped(PathBuf),
+    /// The entry was written to this path instead
+    Renamed(PathBuf)
+}
+
+impl WriteOutcome {
+    /// The path written to, or the path of the existing file that was kept
+    pub fn path(&self) -> &Path {
+        match self {
+            WriteOutcome::Created(path) | WriteOutcome::Overwritten(path) | WriteOutcome::Skipped(path) | WriteOutcome::Renamed(path) => path
+        }
+    }
+}
+
+// Writes the bytes into a file that doesn't exist yet, failing with an IoError of kind AlreadyExists otherwise.
+// Checking and creating the file is a single step, so a file created by another writer in between isn't replaced.
+fn write_new(path: &Path, data: &[u8]) -> Result<(), ThumbsError> {
+    use std::io::Write;
+    let mut file = std::fs::OpenOptions::new().write(true).create_new(true).open(path).map_err(ThumbsError::IoError)?;
+    file.write_all(data).map_err(ThumbsError::IoError)
+}
+
+fn already_exists(error: &ThumbsError) -> bool {
+    matches!(error, ThumbsError::IoError(x) if x.kind() == std::io::ErrorKind::AlreadyExists)
+}
+
+// Writes the bytes to the path, following the policy if a file already exists there
+pub(crate) fn write_with_policy(path: &Path, data: &[u8], policy: CollisionPolicy) -> Result<WriteOutcome, ThumbsError> {
+    if policy == CollisionPolicy::Overwrite {
+        let existed = path.exists();
+        write_data(path, data)?;
+        return Ok(if existed { WriteOutcome::Overwritten(path.to_path_buf()) } else { WriteOutcome::Created(path.to_path_buf()) });
+    }
+    match write_new(path, data) {
+        Ok(()) => return Ok(WriteOutcome::Created(path.to_path_buf())),
+        Err(error) if !already_exists(&error) => return Err(error),
+        Err(error) => match policy {
+            CollisionPolicy::Skip => return Ok(WriteOutcome::Skipped(path.to_path_buf())),
+            CollisionPolicy::Error => return Err(error),
+            _ => ()
+        }
+    }
+    // Renamed with the first suffix no file has, trying the next one when another writer takes it first
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().map(|x| format!(".{}", x.to_string_lossy())).unwrap_or_default();
+    let mut suffix = 1u64;
+    loop {
+        let renamed = path.with_file_name(format!("{}_{}{}", stem, suffix, extension));
+        match write_new(&renamed, data) {
+            Ok(()) => return Ok(WriteOutcome::Renamed(renamed)),
+            Err(error) if already_exists(&error) => suffix += 1,
+            Err(error) => return Err(error)
+        }
+    }
+}
+
+/// Limits of a bulk export, see `Thumbscache::export_all_with_options()`. Every limit is off by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExportOptions {
+    /// The export stops before a file that would bring the bytes written past this total
+    pub max_total_bytes: Option<u64>,
+    /// The export stops once this many files were written
+    pub max_files: Option<usize>,
+    /// Entries holding fewer bytes of data are skipped, leaving out the smallest thumbnails
+    pub min_entry_size: Option<u32>,
+    /// Entries holding more bytes of data are skipped
+    pub max_entry_size: Option<u32>
+}
+
+impl ExportOptions {
+    fn accepts(&self, data_size: u32) -> bool {
+        self.min_entry_size.is_none_or(|x| data_size >= x) && self.max_entry_size.is_none_or(|x| data_size <= x)
+    }
+}
+
+/// Summary of a bulk export
+#[derive(Debug, Default)]
+pub struct ExportSummary {
+    /// Paths of the files that were written
+    pub written: Vec<PathBuf>,
+    /// Positions of the entries that were skipped because they have no data or their size is outside the limits of the options
+    pub skipped: Vec<usize>,
+    /// Positions of the entries that couldn't be written, along with the reason
+    pub failed: Vec<(usize, ThumbsError)>,
+    /// Positions of the entries that were handled, along with the collision policy applied to their file
+    pub outcomes: Vec<(usize, WriteOutcome)>,
+    /// Bytes written into the files
+    pub bytes_written: u64,
+    /// True if the export stopped at the file count or the total size of the options, leaving entries out
+    pub limit_reached: bool
+}
+
+/// The outcome of extracting a single hash, see `Thumbscache::extract_hashes()`
+#[derive(Debug)]
+pub enum HashExtraction {
+    /// Paths of the files written, one per entry with this hash
+    Written(Vec<PathBuf>),
+    /// The hash was found but none of its entries hold data
+    Empty,
+    /// No entry has this hash
+    NotFound,
+    /// Writing the data of some entries failed, the files written for the other entries are listed along with the errors
+    Failed {
+        written: Vec<PathBuf>,
+        errors: Vec<ThumbsError>
+    }
+}
+
+impl HashExtraction {
+    /// Returns true if at least one file was written for the hash
+    pub fn is_hit(&self) -> bool {
+        match self {
+            HashExtraction::Written(_) => true,
+            HashExtraction::Failed { written, .. } => !written.is_empty(),
+            _ => false
+        }
+    }
+}
+
+/// A file written by `CacheEntry::write_to_file()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportedFile {
+    /// The path written to, the default one when no path was given
+    pub path: PathBuf,
+    pub bytes_written: u64,
+    /// What the file holds, a BMP for headerless pixel data
+    pub format: PayloadKind
+}
+
+/// Summary of `ThumbcacheSet::export_unique()`
+#[derive(Debug, Default)]
+pub struct UniqueExport {
+    /// Paths of the files that were written, one per distinct image
+    pub written: Vec<PathBuf>,
+    /// Number of entries left out because another copy of the same image was written
+    pub duplicates: usize,
+    /// Identifier strings of the items that couldn't be written, along with the reason
+    pub failed: Vec<(String, ThumbsError)>
+}
+
+impl CacheEntry {
+    /// Writes the contents of the cache entry into a file.
+    /// The file path defaults to the current directory unless stated otherwise, named after the identifier string with the extension of its payload kind,
+    /// keeping only the characters `NamingStrategy::Identifier` keeps.
+    /// Headerless pixel data is written as a BMP when its dimensions can be derived, see `reconstructed_bmp()`.
+    ///
+    /// Returns the path written to, the number of bytes written and the kind of payload they hold.
+    pub fn write_to_file(&self, file_path: Option<&Path>) -> Result<ExportedFile, ThumbsError> {
+        let (data, format) = self.viewable_data();
+        let path = file_path.map(Path::to_path_buf).unwrap_or_else(|| PathBuf::from(format!("./{}.{}", self.file_stem(), format.extension())));
+        write_data(&path, &data)?;
+        Ok(ExportedFile {
+            path,
+            bytes_written: data.len() as u64,
+            format
+        })
+    }
+
+    /// Writes the contents of the cache entry into a file like `write_to_file()` does, following the policy when the file already exists
+    pub fn write_to_file_with_policy(&self, file_path: Option<&Path>, policy: CollisionPolicy) -> Result<WriteOutcome, ThumbsError> {
+        let (data, format) = self.viewable_data();
+        let path = file_path.map(Path::to_path_buf).unwrap_or_else(|| PathBuf::from(format!("./{}.{}", self.file_stem(), format.extension())));
+        write_with_policy(&path, &data, policy)
+    }
+}
+
+impl Thumbscache {
+    /// Writes every entry that has data into the directory, creating it if needed.
+    ///
+    /// Headerless pixel data is written as a BMP when its dimensions can be derived.
+    /// A failing entry doesn't stop the export, it's reported in the summary instead.
+    pub fn export_all(&self, dir: impl AsRef<Path>, naming: NamingStrategy) -> Result<ExportSummary, ThumbsError> {
+        self.export_entries(dir.as_ref(), &naming, CollisionPolicy::Overwrite, ExportOptions::default(), &AtomicBool::new(false), |_, _, _| ())
+    }
+
+    /// Exports the entries like `export_all()` does within the limits of the options, to cap the disk space used on collection systems.
+    ///
+    /// Entries whose data size is outside the limits are listed as skipped. Once a limit on the files is reached the export stops
+    /// and `ExportSummary::limit_reached` is set.
+    pub fn export_all_with_options(&self, dir: impl AsRef<Path>, naming: NamingStrategy, options: ExportOptions) -> Result<ExportSummary, ThumbsError> {
+        self.export_entries(dir.as_ref(), &naming, CollisionPolicy::Overwrite, options, &AtomicBool::new(false), |_, _, _| ())
+    }
+
+    /// Exports the entries like `export_all()` does, following the policy when a file already exists.
+    ///
+    /// Entries mapping to the same file name collide with each other as well, the outcome of every entry is listed in the summary.
+    pub fn export_all_with_policy(&self, dir: impl AsRef<Path>, naming: NamingStrategy, policy: CollisionPolicy) -> Result<ExportSummary, ThumbsError> {
+        self.export_entries(dir.as_ref(), &naming, policy, ExportOptions::default(), &AtomicBool::new(false), |_, _, _| ())
+    }
+
+    /// Exports the entries like `export_all()` does, stopping before the next entry once the flag is set, possibly from another thread.
+    ///
+    /// The summary lists the files written until then.
+    pub fn export_all_cancellable(&self, dir: impl AsRef<Path>, naming: NamingStrategy, cancel: &AtomicBool) -> Result<ExportSummary, ThumbsError> {
+        self.export_entries(dir.as_ref(), &naming, CollisionPolicy::Overwrite, ExportOptions::default(), cancel, |_, _, _| ())
+    }
+
+    // Exports like export_all(), calling back with every entry written, its path and the bytes written
+    pub(crate) fn export_entries(&self, dir: &Path, naming: &NamingStrategy, policy: CollisionPolicy, options: ExportOptions, cancel: &AtomicBool, mut written: impl FnMut(&CacheEntry, &Path, &[u8])) -> Result<ExportSummary, ThumbsError> {
+        std::fs::create_dir_all(dir).map_err(ThumbsError::IoError)?;
+        let mut summary = ExportSummary::default();
+        for (index, entry) in self.iter().enumerate() {
+            if cancel.load(Ordering::Relaxed) {
+                break;
+            }
+            if entry.data_size == 0 || !options.accepts(entry.data_size) {
+                summary.skipped.push(index);
+                continue;
+            }
+            let entry = match self.loaded_entry(entry) {
+                Ok(entry) => entry,
+                Err(error) => {
+                    summary.failed.push((index, error));
+                    continue;
+                }
+            };
+            let (data, kind) = entry.viewable_data();
+            if options.max_files.is_some_and(|x| summary.written.len() >= x) || options.max_total_bytes.is_some_and(|x| summary.bytes_written + data.len() as u64 > x) {
+                summary.limit_reached = true;
+                break;
+            }
+            let path = dir.join(naming.file_name(index, &entry, kind.extension()));
+            match write_with_policy(&path, &data, policy) {
+                Ok(WriteOutcome::Skipped(path)) => summary.outcomes.push((index, WriteOutcome::Skipped(path))),
+                Ok(outcome) => {
+                    written(&entry, outcome.path(), &data);
+                    summary.bytes_written += data.len() as u64;
+                    summary.written.push(outcome.path().to_path_buf());
+                    summary.outcomes.push((index, outcome));
+                },
+                Err(error) => summary.failed.push((index, error))
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Writes the data of the entries matching a list of entry hashes into the directory, creating it if needed.
+    ///
+    /// Meant for target lists of ThumbnailCacheIds, such as the ones found in Windows Search. Files are named after the hash,
+    /// further entries with the same hash get a numbered suffix. Entries read without their data are extracted from the database bytes,
+    /// headerless pixel data is written as a BMP like `export_all()` does. A failing entry doesn't stop the extraction.
+    /// Returns the outcome of every hash, in the order of the list.
+    pub fn extract_hashes(&self, hashes: &[u64], dir: impl AsRef<Path>) -> Result<Vec<(u64, HashExtraction)>, ThumbsError> {
+        std::fs::create_dir_all(&dir).map_err(ThumbsError::IoError)?;
+        let mut results = Vec::with_capacity(hashes.len());
+        for hash in hashes {
+            let mut found = false;
+            let mut written = Vec::new();
+            let mut errors = Vec::new();
+            for entry in self.iter().filter(|x| x.entry_hash() == *hash) {
+                found = true;
+                if entry.data_size == 0 {
+                    continue;
+                }
+                let copy = written.len() + errors.len();
+                let result = self.loaded_entry(entry).and_then(|entry| {
+                    let (data, kind) = entry.viewable_data();
+                    let suffix = if copy == 0 { String::new() } else { format!("_{}", copy) };
+                    let path = dir.as_ref().join(format!("{}{}.{}", entry.entry_hash_hex(), suffix, kind.extension()));
+                    write_data(&path, &data).map(|_| path)
+                });
+                match result {
+                    Ok(path) => written.push(path),
+                    Err(error) => errors.push(error)
+                }
+            }
+            let result = if !errors.is_empty() {
+                HashExtraction::Failed { written, errors }
+            }else if !written.is_empty() {
+                HashExtraction::Written(written)
+            }else if found {
+                HashExtraction::Empty
+            }else {
+                HashExtraction::NotFound
+            };
+            results.push((*hash, result));
+        }
+        Ok(results)
+    }
+}
+
+// The resolution of the copy, compared to pick the best copy of an item
+fn resolution_rank(entry: &CacheEntry) -> (u64, u32, u32) {
+    let area = entry.dimensions().map(|(width, height)| width as u64 * height as u64).unwrap_or_default();
+    (area, entry.cache_type.and_then(|x| x.max_dimension()).unwrap_or_default(), entry.data_size)
+}
+
+impl ThumbcacheSet {
+    /// Writes one copy of every distinct image of the databases into the directory, creating it if needed.
+    ///
+    /// An item cached at several resolutions is written once, from the copy with the most pixels (or the largest cache type
+    /// when the dimensions are unknown). Items holding the exact same bytes are written once as well.
+    /// Files are named after the identifier string of the item, sanitized like `NamingStrategy::Identifier` does, entries without data are left out.
+    pub fn export_unique(&self, dir: impl AsRef<Path>) -> Result<UniqueExport, ThumbsError> {
+        std::fs::create_dir_all(&dir).map_err(ThumbsError::IoError)?;
+        let mut summary = UniqueExport::default();
+        // The best copy of every item, in the order the items were first found
+        let mut best: Vec<Cow<'_, CacheEntry>> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for database in self.databases() {
+            for entry in database.iter().filter(|x| x.data_size != 0) {
+                let entry = if entry.is_data_loaded() {
+                    Cow::Borrowed(entry)
+                }else {
+                    let mut loaded = entry.clone();
+                    if let Err(error) = loaded.load_data(database) {
+                        summary.failed.push((entry.identifier_string.clone(), error));
+                        continue;
+                    }
+                    Cow::Owned(loaded)
+                };
+                match positions.get(entry.identifier_string.as_str()) {
+                    Some(&i) => {
+                        summary.duplicates += 1;
+                        if resolution_rank(&entry) > resolution_rank(&best[i]) {
+                            best[i] = entry;
+                        }
+                    },
+                    None => {
+                        positions.insert(entry.identifier_string.clone(), best.len());
+                        best.push(entry);
+                    }
+                }
+            }
+        }
+        let mut written_data: HashMap<u64, Vec<Vec<u8>>> = HashMap::new();
+        for entry in best {
+            let (data, kind) = entry.viewable_data();
+            let copies = written_data.entry(crc64(&data)).or_default();
+            if copies.iter().any(|x| **x == *data) {
+                summary.duplicates += 1;
+                continue;
+            }
+            let path = dir.as_ref().join(format!("{}.{}", entry.file_stem(), kind.extension()));
+            match write_data(&path, &data) {
+                Ok(()) => summary.written.push(path),
+                Err(error) => summary.failed.push((entry.identifier_string.clone(), error))
+            }
+            copies.push(data.into_owned());
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{build_database, temp_dir};
+    use std::io::Cursor;
+
+    #[test]
+    fn exports_non_empty_entries() {
+        let mut database = Thumbscache::from_reader(Cursor::new(build_database(32, 1, &[(0xAB, b"BMfirst"), (0xCD, b""), (0xEF, b"\xFF\xD8\xFFsecond")]))).unwrap();
+        database.read().unwrap();
+        let dir = temp_dir("export_all");
+        let summary = database.export_all(&dir, NamingStrategy::Identifier).unwrap();
+        assert_eq!(summary.written, [dir.join("00000000000000ab.bmp"), dir.join("00000000000000ef.jpg")]);
+        assert_eq!(summary.skipped, [1]);
+        assert!(summary.failed.is_empty());
+        assert_eq!(std::fs::read(dir.join("00000000000000ef.jpg")).unwrap(), b"\xFF\xD8\xFFsecond");
+
+        let summary = database.export_all(&dir, NamingStrategy::Custom(Box::new(|index, entry| format!("custom_{}_{}", index, entry.data_size)))).unwrap();
+        assert_eq!(summary.written[1], dir.join("custom_2_9"));
+
+        let summary = database.export_all_cancellable(&dir, NamingStrategy::Index, &AtomicBool::new(true)).unwrap();
+        assert!(summary.written.is_empty() && summary.skipped.is_empty());
+    }
+
+    #[test]
+    fn keeps_crafted_identifiers_inside_the_directory() {
+        let mut database = Thumbscache::from_bytes(build_database(32, 1, &[(0xAB, b"BMfirst"), (0xCD, b"BMsecond"), (0xEF, b"BMthird")]));
+        database.read().unwrap();
+        database.entries_mut()[0].identifier_string = String::from("../../escaped");
+        database.entries_mut()[1].identifier_string = String::from("..");
+        database.entries_mut()[2].identifier_string = String::from("C:\\Windows/evil name");
+        let dir = temp_dir("export_crafted");
+        let summary = database.export_all(&dir, NamingStrategy::Identifier).unwrap();
+        assert_eq!(summary.written, [dir.join("....escaped.bmp"), dir.join("00000000000000cd.bmp"), dir.join("CWindowsevilname.bmp")]);
+        assert_eq!(database.entries()[1].file_stem(), "00000000000000cd");
+    }
+
+    #[test]
+    fn stays_within_the_limits() {
+        let mut database = Thumbscache::from_bytes(build_database(32, 1, &[(1, b"BM1"), (2, b"BMsecond"), (3, b"BMthird entry"), (4, b"BMfourth")]));
+        database.read().unwrap();
+        let dir = temp_dir("export_limits");
+        let options = ExportOptions { min_entry_size: Some(4), max_entry_size: Some(10), ..ExportOptions::default() };
+        let summary = database.export_all_with_options(&dir, NamingStrategy::Index, options).unwrap();
+        assert_eq!(summary.written, [dir.join("1.bmp"), dir.join("3.bmp")]);
+        assert_eq!((summary.skipped, summary.bytes_written, summary.limit_reached), (vec![0, 2], 16, false));
+
+        let summary = database.export_all_with_options(&dir, NamingStrategy::Index, ExportOptions { max_files: Some(2), ..ExportOptions::default() }).unwrap();
+        assert_eq!((summary.written.len(), summary.limit_reached), (2, true));
+        // The third entry would bring the total to 24 bytes
+        let summary = database.export_all_with_options(&dir, NamingStrategy::Index, ExportOptions { max_total_bytes: Some(20), ..ExportOptions::default() }).unwrap();
+        assert_eq!((summary.written.len(), summary.bytes_written, summary.limit_reached), (2, 11, true));
+    }
+
+    #[test]
+    fn applies_the_collision_policy() {
+        let mut database = Thumbscache::from_bytes(build_database(32, 1, &[(0xAB, b"BMfirst"), (0xAB, b"BMagain")]));
+        database.read().unwrap();
+        let dir = temp_dir("collisions");
+        let first = dir.join("00000000000000ab.bmp");
+
+        let summary = database.export_all_with_policy(&dir, NamingStrategy::Identifier, CollisionPolicy::Skip).unwrap();
+        assert_eq!(summary.outcomes, [(0, WriteOutcome::Created(first.clone())), (1, WriteOutcome::Skipped(first.clone()))]);
+        assert_eq!(std::fs::read(&first).unwrap(), b"BMfirst");
+
+        let summary = database.export_all_with_policy(&dir, NamingStrategy::Identifier, CollisionPolicy::RenameWithSuffix).unwrap();
+        assert_eq!(summary.written, [dir.join("00000000000000ab_1.bmp"), dir.join("00000000000000ab_2.bmp")]);
+        assert_eq!(std::fs::read(dir.join("00000000000000ab_2.bmp")).unwrap(), b"BMagain");
+
+        let summary = database.export_all_with_policy(&dir, NamingStrategy::Identifier, CollisionPolicy::Error).unwrap();
+        assert!(summary.written.is_empty());
+        assert!(matches!(&summary.failed[1].1, ThumbsError::IoError(x) if x.kind() == std::io::ErrorKind::AlreadyExists));
+
+        let outcome = database.entries()[1].write_to_file_with_policy(Some(&first), CollisionPolicy::Overwrite).unwrap();
+        assert_eq!(outcome, WriteOutcome::Overwritten(first.clone()));
+        assert_eq!(std::fs::read(&first).unwrap(), b"BMagain");
+
+        let exported = database.entries()[0].write_to_file(Some(&dir.join("written"))).unwrap();
+        assert_eq!(exported, ExportedFile { path: dir.join("written"), bytes_written: 7, format: PayloadKind::Image(crate::ImageFormat::Bmp) });
+        assert_eq!(std::fs::read(&exported.path).unwrap(), b"BMfirst");
+    }
+
+    #[test]
+    fn extracts_listed_hashes() {
+        let mut database = Thumbscache::from_bytes(build_database(32, 1, &[(0xAB, b"BMfirst"), (0xCD, b""), (0xAB, b"BMagain"), (0xEF, b"\xFF\xD8\xFFsecond")]));
+        database.read_with_options(crate::ReadOptions::lazy()).unwrap();
+        let dir = temp_dir("extract_hashes");
+        let results = database.extract_hashes(&[0xEF, 0x12, 0xCD, 0xAB], &dir).unwrap();
+        let hits: Vec<bool> = results.iter().map(|(_, x)| x.is_hit()).collect();
+        assert_eq!(hits, [true, false, false, true]);
+        assert!(matches!(results[1].1, HashExtraction::NotFound));
+        assert!(matches!(results[2].1, HashExtraction::Empty));
+        let HashExtraction::Written(paths) = &results[3].1 else { panic!("0xab wasn't extracted") };
+        assert_eq!(paths, &[dir.join("00000000000000ab.bmp"), dir.join("00000000000000ab_1.bmp")]);
+        assert_eq!(std::fs::read(&paths[1]).unwrap(), b"BMagain");
+        assert_eq!(std::fs::read(dir.join("00000000000000ef.jpg")).unwrap(), b"\xFF\xD8\xFFsecond");
+
+        // Headerless pixels are rebuilt into a BMP, an entry that can't be written doesn't hide the files written before it
+        // 16 by 16 pixels for the 16px cache
+        let pixels = vec![0x40; 16 * 16 * 4];
+        let mut database = Thumbscache::from_bytes(build_database(32, 0, &[(1, &pixels), (1, b"BMsecond")]));
+        database.read_with_options(crate::ReadOptions::lazy()).unwrap();
+        std::fs::create_dir_all(dir.join("0000000000000001_1.bmp")).unwrap();
+        let results = database.extract_hashes(&[1], &dir).unwrap();
+        let HashExtraction::Failed { written, errors } = &results[0].1 else { panic!("the directory in the way wasn't reported") };
+        assert_eq!(written, &[dir.join("0000000000000001.bmp")]);
+        assert_eq!(errors.len(), 1);
+        assert!(results[0].1.is_hit());
+        assert_eq!(std::fs::read(&written[0]).unwrap()[..2], *b"BM");
+        assert_eq!(std::fs::read(&written[0]).unwrap().len(), 54 + pixels.len());
+    }
+
+    #[test]
+    fn exports_each_image_once() {
+        let mut small = Thumbscache::from_bytes(build_database(32, 0, &[(1, b"BMsmall"), (2, b"BMsame"), (4, b"")]));
+        small.read().unwrap();
+        let mut large = Thumbscache::from_bytes(build_database(32, 4, &[(1, b"BMlarge"), (3, b"BMsame")]));
+        large.read_with_options(crate::ReadOptions::lazy()).unwrap();
+        let set = ThumbcacheSet::from_databases(None, vec![small, large]);
+        let dir = temp_dir("export_unique");
+        let summary = set.export_unique(&dir).unwrap();
+        // The 256px copy of the first item wins, the third item holds the same bytes as the second one
+        assert_eq!(summary.written, [dir.join("0000000000000001.bmp"), dir.join("0000000000000002.bmp")]);
+        assert_eq!(summary.duplicates, 2);
+        assert!(summary.failed.is_empty());
+        assert_eq!(std::fs::read(&summary.written[0]).unwrap(), b"BMlarge");
+
+        // Crafted identifiers stay inside the directory
+        let mut crafted = Thumbscache::from_bytes(build_database(32, 1, &[(5, b"BMcrafted")]));
+        crafted.read().unwrap();
+        crafted.entries_mut()[0].identifier_string = String::from("../escaped");
+        let dir = temp_dir("export_unique_crafted");
+        let summary = ThumbcacheSet::from_databases(None, vec![crafted]).export_unique(&dir).unwrap();
+        assert_eq!(summary.written, [dir.join("..escaped.bmp")]);
+    }
+}
@@ -0,0 +1,241 @@
+//! C ABI, enabled with the `ffi` feature
+//!
+//! A database is opened into an opaque handle, read, then queried entry by entry. The declarations are in include/thumbscache.h,
+//! regenerate it with `cbindgen --config cbindgen.toml --output include/thumbscache.h` after changing this module.
+//! Functions returning an `int32_t` return THUMBSCACHE_OK or one of the negative error codes.
+
+use core::ffi::{c_char, CStr};
+
+use crate::{CacheEntry, ImageFormat, Thumbscache, ThumbsError};
+
+/// The call succeeded
+pub const THUMBSCACHE_OK: i32 = 0;
+/// A pointer argument was null
+pub const THUMBSCACHE_ERROR_NULL: i32 = -1;
+/// The file couldn't be opened or read
+pub const THUMBSCACHE_ERROR_IO: i32 = -2;
+/// The database is malformed
+pub const THUMBSCACHE_ERROR_PARSE: i32 = -3;
+/// There is no entry at the index
+pub const THUMBSCACHE_ERROR_OUT_OF_RANGE: i32 = -4;
+/// The buffer is too small for the data
+pub const THUMBSCACHE_ERROR_BUFFER_TOO_SMALL: i32 = -5;
+
+/// An opened database, created by thumbscache_open() or thumbscache_open_bytes() and released by thumbscache_free()
+pub struct ThumbscacheHandle {
+    database: Thumbscache
+}
+
+/// The metadata of a cache entry, filled by thumbscache_entry_metadata()
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ThumbscacheEntryMetadata {
+    /// The 64-bit hash identifying the cached item
+    pub entry_hash: u64,
+    /// Offset of the entry in the database
+    pub offset: u64,
+    /// Offset of the data in the database
+    pub data_offset: u64,
+    /// Size of the data in bytes, the buffer size needed by thumbscache_copy_data()
+    pub data_size: u32,
+    /// 0 for unknown data, 1 for BMP, 2 for JPEG and 3 for PNG
+    pub format: u32,
+    /// Width of the thumbnail, 0 when unknown
+    pub width: u32,
+    /// Height of the thumbnail, 0 when unknown
+    pub height: u32,
+    /// CRC-64 of the data, as stored in the entry header
+    pub data_checksum: u64,
+    /// CRC-64 of the entry header, as stored in it
+    pub header_checksum: u64
+}
+
+impl From<&CacheEntry> for ThumbscacheEntryMetadata {
+    fn from(entry: &CacheEntry) -> ThumbscacheEntryMetadata {
+        let (width, height) = entry.dimensions().unwrap_or_default();
+        ThumbscacheEntryMetadata {
+            entry_hash: entry.entry_hash,
+            offset: entry.offset,
+            data_offset: entry.data_offset,
+            data_size: entry.data_size,
+            format: match entry.detect_format() {
+                ImageFormat::Unknown => 0,
+                ImageFormat::Bmp => 1,
+                ImageFormat::Jpeg => 2,
+                ImageFormat::Png => 3
+            },
+            width,
+            height,
+            data_checksum: entry.data_checksum,
+            header_checksum: entry.header_checksum
+        }
+    }
+}
+
+fn error_code(error: &ThumbsError) -> i32 {
+    match error {
+        ThumbsError::InvalidFile | ThumbsError::IoError(_) => THUMBSCACHE_ERROR_IO,
+        ThumbsError::EntryNotFound { .. } | ThumbsError::EntryHashNotFound { .. } => THUMBSCACHE_ERROR_OUT_OF_RANGE,
+        _ => THUMBSCACHE_ERROR_PARSE
+    }
+}
+
+fn into_handle(database: Thumbscache) -> *mut ThumbscacheHandle {
+    Box::into_raw(Box::new(ThumbscacheHandle { database }))
+}
+
+// Finds the entry at the index of the handle
+unsafe fn entry<'a>(handle: *const ThumbscacheHandle, index: usize) -> Result<&'a CacheEntry, i32> {
+    let handle = handle.as_ref().ok_or(THUMBSCACHE_ERROR_NULL)?;
+    handle.database.entries().get(index).ok_or(THUMBSCACHE_ERROR_OUT_OF_RANGE)
+}
+
+/// Opens the database at the path, a null-terminated UTF-8 string. Returns null if it can't be opened.
+///
+/// # Safety
+/// `path` must be null or point to a null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn thumbscache_open(path: *const c_char) -> *mut ThumbscacheHandle {
+    if path.is_null() {
+        return core::ptr::null_mut();
+    }
+    let Ok(path) = CStr::from_ptr(path).to_str() else {
+        return core::ptr::null_mut();
+    };
+    match Thumbscache::from_path(path) {
+        Ok(database) => into_handle(database),
+        Err(_) => core::ptr::null_mut()
+    }
+}
+
+/// Copies the bytes of a whole database into a new handle. Returns null if `bytes` is null.
+///
+/// # Safety
+/// `bytes` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn thumbscache_open_bytes(bytes: *const u8, len: usize) -> *mut ThumbscacheHandle {
+    if bytes.is_null() {
+        return core::ptr::null_mut();
+    }
+    into_handle(Thumbscache::from_bytes(core::slice::from_raw_parts(bytes, len).to_vec()))
+}
+
+/// Reads the cache entries of the database, like Thumbscache::read()
+///
+/// # Safety
+/// `handle` must be null or a handle that wasn't freed.
+#[no_mangle]
+pub unsafe extern "C" fn thumbscache_read(handle: *mut ThumbscacheHandle) -> i32 {
+    let Some(handle) = handle.as_mut() else {
+        return THUMBSCACHE_ERROR_NULL;
+    };
+    match handle.database.read() {
+        Ok(_) => THUMBSCACHE_OK,
+        Err(error) => error_code(&error)
+    }
+}
+
+/// The number of cache entries read, 0 for a null handle
+///
+/// # Safety
+/// `handle` must be null or a handle that wasn't freed.
+#[no_mangle]
+pub unsafe extern "C" fn thumbscache_entry_count(handle: *const ThumbscacheHandle) -> usize {
+    handle.as_ref().map(|x| x.database.len()).unwrap_or(0)
+}
+
+/// Fills `metadata` with the metadata of the entry at the index
+///
+/// # Safety
+/// `handle` must be null or a handle that wasn't freed, `metadata` must be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn thumbscache_entry_metadata(handle: *const ThumbscacheHandle, index: usize, metadata: *mut ThumbscacheEntryMetadata) -> i32 {
+    let entry = match entry(handle, index) {
+        Ok(entry) => entry,
+        Err(code) => return code
+    };
+    let Some(metadata) = metadata.as_mut() else {
+        return THUMBSCACHE_ERROR_NULL;
+    };
+    *metadata = ThumbscacheEntryMetadata::from(entry);
+    THUMBSCACHE_OK
+}
+
+/// Copies the data of the entry at the index into the buffer, which has to hold at least its data_size bytes
+///
+/// # Safety
+/// `handle` must be null or a handle that wasn't freed, `buffer` must be null or point to `buffer_len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn thumbscache_copy_data(handle: *const ThumbscacheHandle, index: usize, buffer: *mut u8, buffer_len: usize) -> i32 {
+    let entry = match entry(handle, index) {
+        Ok(entry) => entry,
+        Err(code) => return code
+    };
+    if buffer.is_null() {
+        return THUMBSCACHE_ERROR_NULL;
+    }
+    if buffer_len < entry.data.len() {
+        return THUMBSCACHE_ERROR_BUFFER_TOO_SMALL;
+    }
+    core::ptr::copy_nonoverlapping(entry.data.as_ptr(), buffer, entry.data.len());
+    THUMBSCACHE_OK
+}
+
+/// Releases the handle, null is ignored
+///
+/// # Safety
+/// `handle` must be null or a handle that wasn't freed yet, it can't be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn thumbscache_free(handle: *mut ThumbscacheHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{build_database, temp_dir};
+    use std::ffi::CString;
+
+    #[test]
+    fn drives_the_c_abi() {
+        let bytes = build_database(32, 1, &[(0xAB, b"BMfirst"), (0xCD, b"\xFF\xD8\xFFsecond")]);
+        let path = temp_dir("ffi").join("thumbcache_32.db");
+        std::fs::write(&path, &bytes).unwrap();
+        let path = CString::new(path.to_str().unwrap()).unwrap();
+        unsafe {
+            let handle = thumbscache_open(path.as_ptr());
+            assert!(!handle.is_null());
+            assert_eq!(thumbscache_entry_count(handle), 0);
+            assert_eq!(thumbscache_read(handle), THUMBSCACHE_OK);
+            assert_eq!(thumbscache_entry_count(handle), 2);
+
+            let mut metadata = ThumbscacheEntryMetadata::default();
+            assert_eq!(thumbscache_entry_metadata(handle, 1, &mut metadata), THUMBSCACHE_OK);
+            assert_eq!((metadata.entry_hash, metadata.data_size, metadata.format), (0xCD, 9, 2));
+            assert_eq!(thumbscache_entry_metadata(handle, 2, &mut metadata), THUMBSCACHE_ERROR_OUT_OF_RANGE);
+
+            let mut buffer = [0u8; 9];
+            assert_eq!(thumbscache_copy_data(handle, 0, buffer.as_mut_ptr(), 4), THUMBSCACHE_ERROR_BUFFER_TOO_SMALL);
+            assert_eq!(thumbscache_copy_data(handle, 0, buffer.as_mut_ptr(), buffer.len()), THUMBSCACHE_OK);
+            assert_eq!(&buffer[..7], b"BMfirst");
+            thumbscache_free(handle);
+
+            let handle = thumbscache_open_bytes(b"garbage".as_ptr(), 7);
+            assert_eq!(thumbscache_read(handle), THUMBSCACHE_ERROR_PARSE);
+            thumbscache_free(handle);
+            assert!(thumbscache_open(CString::new("missing.db").unwrap().as_ptr()).is_null());
+            assert_eq!(thumbscache_read(core::ptr::null_mut()), THUMBSCACHE_ERROR_NULL);
+        }
+    }
+
+    #[test]
+    fn header_declares_every_function() {
+        let header = include_str!("../include/thumbscache.h");
+        for name in ["thumbscache_open(", "thumbscache_open_bytes(", "thumbscache_read(", "thumbscache_entry_count(", "thumbscache_entry_metadata(",
+            "thumbscache_copy_data(", "thumbscache_free(", "THUMBSCACHE_ERROR_BUFFER_TOO_SMALL", "} ThumbscacheEntryMetadata;"] {
+            assert!(header.contains(name), "{} is missing from the header", name);
+        }
+    }
+}
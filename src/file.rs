@@ -0,0 +1,108 @@
+//! Classifying the files of an Explorer cache directory by their name, before parsing them
+
+use std::path::{Path, PathBuf};
+
+use crate::{CacheType, DatabaseKind};
+#[cfg(feature = "fs")]
+use crate::{Thumbscache, ThumbsError};
+
+/// What a file of an Explorer cache directory holds, see `ThumbcacheFile::kind()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CacheFileKind {
+    /// A database of this cache type
+    Database(CacheType),
+    /// The index file, thumbcache_idx.db or iconcache_idx.db
+    Index,
+    /// A database whose cache type isn't known from its name
+    Unknown
+}
+
+/// A thumbnail or icon cache file, classified from its name
+///
+/// ```
+/// use thumbscache::{CacheFileKind, CacheType, ThumbcacheFile};
+/// let file = ThumbcacheFile::new("Explorer/thumbcache_768.db").unwrap();
+/// assert_eq!(file.kind(), CacheFileKind::Database(CacheType::Res768));
+/// assert_eq!(file.cache_type().and_then(|x| x.max_dimension()), Some(768));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThumbcacheFile {
+    path: PathBuf,
+    database_kind: DatabaseKind
+}
+
+impl ThumbcacheFile {
+    /// Classifies the file at the path, without opening it.
+    ///
+    /// Returns None if its name doesn't follow the thumbcache_*.db or iconcache_*.db pattern.
+    pub fn new(path: impl AsRef<Path>) -> Option<ThumbcacheFile> {
+        let path = path.as_ref();
+        let file_name = path.file_name()?.to_string_lossy().to_lowercase();
+        if !file_name.ends_with(".db") {
+            return None;
+        }
+        Some(ThumbcacheFile {
+            database_kind: DatabaseKind::from_path(path)?,
+            path: path.to_path_buf()
+        })
+    }
+
+    /// The path of the file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the file belongs to the thumbnail or the icon cache
+    pub fn database_kind(&self) -> DatabaseKind {
+        self.database_kind
+    }
+
+    /// What the file holds, according to its name
+    pub fn kind(&self) -> CacheFileKind {
+        let file_name = self.path.file_name().map(|x| x.to_string_lossy().to_lowercase()).unwrap_or_default();
+        if file_name == format!("{}idx.db", self.database_kind.file_prefix()) {
+            return CacheFileKind::Index;
+        }
+        match CacheType::from_filename(&file_name) {
+            Some(cache_type) => CacheFileKind::Database(cache_type),
+            None => CacheFileKind::Unknown
+        }
+    }
+
+    /// The cache type according to the name, None for the index file
+    pub fn cache_type(&self) -> Option<CacheType> {
+        match self.kind() {
+            CacheFileKind::Database(cache_type) => Some(cache_type),
+            _ => None
+        }
+    }
+
+    /// Opens the database, additional parsing is neccessary using the .read() function
+    #[cfg(feature = "fs")]
+    pub fn open(&self) -> Result<Thumbscache, ThumbsError> {
+        Thumbscache::from_path(&self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_files_by_name() {
+        let kinds: Vec<Option<CacheFileKind>> = ["thumbcache_16.db", "THUMBCACHE_WIDE_ALTERNATE.DB", "iconcache_custom_stream.db", "thumbcache_idx.db", "thumbcache_4096.db", "thumbcache_16.tmp", "desktop.ini"]
+            .into_iter().map(|x| ThumbcacheFile::new(Path::new("Explorer").join(x)).map(|x| x.kind())).collect();
+        assert_eq!(kinds, [
+            Some(CacheFileKind::Database(CacheType::Res16)),
+            Some(CacheFileKind::Database(CacheType::WideAlternate)),
+            Some(CacheFileKind::Database(CacheType::CustomStream)),
+            Some(CacheFileKind::Index),
+            Some(CacheFileKind::Unknown),
+            None,
+            None
+        ]);
+        assert_eq!(ThumbcacheFile::new("iconcache_256.db").unwrap().database_kind(), DatabaseKind::IconCache);
+        assert_eq!(CacheType::from_filename("C:\\Explorer\\thumbcache_sr.db"), Some(CacheType::SR));
+        assert_eq!(CacheType::Wide.max_dimension(), None);
+    }
+}
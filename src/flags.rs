@@ -0,0 +1,67 @@
+//! The flags field of the Windows 8 and later entry headers
+//!
+//! Windows 8 added 4 bytes between the height of the thumbnail and the data checksum. Microsoft doesn't document them,
+//! so every bit is kept as read, none is named, and entries can be told apart by the bits they set.
+
+use crate::{parse::CacheEntryRef, CacheEntry, VersionFields};
+
+bitflags::bitflags! {
+    /// The flags stored by Windows 8 and later in the header of every entry, see `CacheEntry::flags()`
+    ///
+    /// No bit has a documented meaning, they are compared as read with `EntryFlags::from_bits_retain()`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize))]
+    pub struct EntryFlags: u32 {
+        // Every bit is kept as read
+        const _ = !0;
+    }
+}
+
+fn flags_of(fields: &VersionFields) -> Option<EntryFlags> {
+    match fields {
+        VersionFields::Win8 { flags, .. } => Some(*flags),
+        _ => None
+    }
+}
+
+impl CacheEntry {
+    /// The flags stored in the entry header, None before Windows 8
+    pub fn flags(&self) -> Option<EntryFlags> {
+        flags_of(&self.version_fields)
+    }
+
+    /// Returns true if the entry header stores every flag given
+    pub fn has_flags(&self, flags: EntryFlags) -> bool {
+        self.flags().is_some_and(|x| x.contains(flags))
+    }
+}
+
+impl CacheEntryRef<'_> {
+    /// The flags stored in the entry header, None before Windows 8
+    pub fn flags(&self) -> Option<EntryFlags> {
+        flags_of(&self.version_fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_database, tests::build_database, ReadOptions};
+
+    #[test]
+    fn reads_the_flags() {
+        let mut bytes = build_database(32, 0, &[(1, b"first"), (2, b"second")]);
+        let second = 24 + 56 + 32 + 5;
+        bytes[second + 36..second + 40].copy_from_slice(&0x0000_0101u32.to_le_bytes());
+        let (_, entries) = parse_database(&bytes, ReadOptions::default()).unwrap();
+        assert_eq!(entries[0].flags(), Some(EntryFlags::empty()));
+        assert_eq!(entries[1].flags().map(|x| x.bits()), Some(0x101));
+        assert!(entries[1].has_flags(EntryFlags::from_bits_retain(0x100)) && !entries[0].has_flags(EntryFlags::from_bits_retain(0x100)));
+        assert!(entries.iter().all(|x| x.has_flags(EntryFlags::empty())));
+
+        let bytes = build_database(21, 0, &[(1, b"first")]);
+        let (_, entries) = parse_database(&bytes, ReadOptions::default()).unwrap();
+        assert_eq!(entries[0].flags(), None);
+        assert!(!entries[0].has_flags(EntryFlags::empty()));
+    }
+}
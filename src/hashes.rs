@@ -0,0 +1,54 @@
+//! Content hashes of the thumbnail data, enabled with the `hashes` feature
+
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::CacheEntry;
+
+/// MD5, SHA-1 and SHA-256 of the data of an entry, as lowercase hexadecimal strings
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EntryHashes {
+    pub md5: String,
+    pub sha1: String,
+    pub sha256: String
+}
+
+pub(crate) fn hex_digest<D: Digest>(data: &[u8]) -> String {
+    D::digest(data).iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+impl EntryHashes {
+    // Hashes the data of an entry
+    pub(crate) fn of(data: &[u8]) -> EntryHashes {
+        EntryHashes {
+            md5: hex_digest::<Md5>(data),
+            sha1: hex_digest::<Sha1>(data),
+            sha256: hex_digest::<Sha256>(data)
+        }
+    }
+}
+
+impl CacheEntry {
+    /// Hashes the data of the entry, as it would be written by .write_to_file()
+    pub fn hashes(&self) -> EntryHashes {
+        EntryHashes::of(&self.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tests::build_database, Thumbscache};
+    use std::io::Cursor;
+
+    #[test]
+    fn hashes_the_data() {
+        let mut database = Thumbscache::from_reader(Cursor::new(build_database(32, 1, &[(1, b"abc")]))).unwrap();
+        database.read().unwrap();
+        let hashes = database.entries()[0].hashes();
+        assert_eq!(hashes.md5, "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(hashes.sha1, "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(hashes.sha256, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+}
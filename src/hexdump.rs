@@ -0,0 +1,125 @@
+//! Hex dumps of entry records with the fields labelled, to compare the parsing with other tools byte by byte
+
+use alloc::{format, string::String, vec::Vec};
+use core::fmt::Write;
+
+use crate::{format_entry_hash, spans::Span, CacheEntry, VersionFields};
+
+// Bytes per line of the dump
+const LINE_LEN: usize = 16;
+// Bytes of data dumped before the rest is summarized
+const DATA_DUMP_LIMIT: usize = 256;
+
+// Writes the bytes 16 per line, starting at the offset, the label and value on the first line only
+fn dump_field(dump: &mut String, offset: u64, bytes: &[u8], label: &str, value: &str) {
+    for (index, line) in bytes.chunks(LINE_LEN).enumerate() {
+        let hex: Vec<String> = line.iter().map(|x| format!("{:02x}", x)).collect();
+        let _ = write!(dump, "{:08x}  {:<width$}", offset + (index * LINE_LEN) as u64, hex.join(" "), width = LINE_LEN * 3 - 1);
+        if index == 0 {
+            let _ = write!(dump, "  {}", label);
+            if !value.is_empty() {
+                let _ = write!(dump, "  {}", value);
+            }
+        }
+        dump.push('\n');
+    }
+}
+
+// A line for bytes that aren't part of the entry
+fn note(dump: &mut String, offset: u64, text: &str) {
+    let _ = writeln!(dump, "{:08x}  {}", offset, text);
+}
+
+impl CacheEntry {
+    /// Dumps the record of the entry in hexadecimal, with the name and the value of every field next to its bytes.
+    ///
+    /// Offsets are counted from the start of the database. Header bytes whose meaning isn't known are labelled as unknown,
+    /// the padding isn't kept by the entry so only its length is given, and only the first 256 bytes of the data are dumped.
+    ///
+    /// ```text
+    /// 00000018  43 4d 4d 4d                                      signature  "CMMM"
+    /// 0000001c  5f 00 00 00                                      size  95
+    /// ```
+    pub fn annotated_hexdump(&self) -> String {
+        let spans = self.spans();
+        let mut fields: Vec<(Span, &str, String)> = Vec::from([
+            (spans.signature, "signature", String::from("\"CMMM\"")),
+            (spans.size, "size", format!("{}", self.size)),
+            (spans.entry_hash, "entry_hash", format_entry_hash(self.entry_hash, false)),
+            (spans.identifier_string_size, "identifier_string_size", format!("{}", self.identifier_string_size)),
+            (spans.padding_size, "padding_size", format!("{}", self.padding_size)),
+            (spans.data_size, "data_size", format!("{}", self.data_size)),
+            (spans.data_checksum, "data_checksum", format_entry_hash(self.data_checksum, false)),
+            (spans.header_checksum, "header_checksum", format_entry_hash(self.header_checksum, false))
+        ]);
+        match &self.version_fields {
+            VersionFields::Vista { file_extension } => fields.extend(spans.file_extension.map(|x| (x, "file_extension", format!("{:?}", file_extension)))),
+            VersionFields::Win8 { width, height, flags } => {
+                fields.extend(spans.width.map(|x| (x, "width", format!("{}", width))));
+                fields.extend(spans.height.map(|x| (x, "height", format!("{}", height))));
+                fields.extend(spans.flags.map(|x| (x, "flags", format!("{:#010x}", flags.bits()))));
+            },
+            VersionFields::Win7 => ()
+        }
+        fields.sort_by_key(|x| x.0.offset);
+        let mut dump = String::new();
+        let mut position = self.offset;
+        for (span, label, value) in fields {
+            if span.offset > position {
+                self.dump_header(&mut dump, position, span.offset, "unknown", "");
+            }
+            self.dump_header(&mut dump, span.offset, span.end(), label, &value);
+            position = span.end();
+        }
+        if spans.identifier.offset > position {
+            self.dump_header(&mut dump, position, spans.identifier.offset, "unknown", "");
+        }
+        dump_field(&mut dump, spans.identifier.offset, &self.identifier, "identifier", &format!("{:?}", self.identifier_string));
+        if spans.padding.len != 0 {
+            note(&mut dump, spans.padding.offset, &format!("padding, {} bytes", spans.padding.len));
+        }
+        // Entries read without their data, see ReadOptions::lazy()
+        if self.data.len() != self.data_size as usize {
+            note(&mut dump, spans.data.offset, &format!("data, {} bytes, not loaded", spans.data.len));
+        }else if !self.data.is_empty() {
+            let shown = &self.data[..self.data.len().min(DATA_DUMP_LIMIT)];
+            dump_field(&mut dump, spans.data.offset, shown, "data", &format!("{} bytes, {}", self.data.len(), self.payload_kind().extension()));
+            if shown.len() < self.data.len() {
+                note(&mut dump, spans.data.offset + shown.len() as u64, &format!("{} more bytes of data", self.data.len() - shown.len()));
+            }
+        }
+        if spans.slack.len != 0 {
+            note(&mut dump, spans.slack.offset, &format!("slack, {} bytes", spans.slack.len));
+        }
+        dump
+    }
+
+    // Dumps the header bytes in start..end, given as offsets in the database
+    fn dump_header(&self, dump: &mut String, start: u64, end: u64, label: &str, value: &str) {
+        let bytes = self.header.get((start - self.offset) as usize..(end - self.offset) as usize).unwrap_or_default();
+        dump_field(dump, start, bytes, label, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_database, tests::build_database, ReadOptions};
+
+    #[test]
+    fn labels_every_field() {
+        let bytes = build_database(32, 0, &[(0xAB, b"BMfirst")]);
+        let (_, entries) = parse_database(&bytes, ReadOptions::default()).unwrap();
+        let dump = entries[0].annotated_hexdump();
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines[0], "00000018  43 4d 4d 4d                                      signature  \"CMMM\"");
+        assert!(lines[2].starts_with("00000020  ab 00 00 00 00 00 00 00") && lines[2].ends_with("entry_hash  00000000000000ab"));
+        assert!(lines.iter().any(|x| x.starts_with("0000003c  ") && x.ends_with("flags  0x00000000")));
+        assert!(!lines.iter().any(|x| x.ends_with("unknown")));
+        assert!(lines.iter().any(|x| x.ends_with("width  0")));
+        assert!(lines.iter().any(|x| x.starts_with("00000050  ") && x.contains("identifier  \"00000000000000ab\"")));
+        assert!(lines.last().unwrap().ends_with("data  7 bytes, bmp"));
+
+        let (_, lazy) = parse_database(&bytes, ReadOptions { load_data: false, ..ReadOptions::default() }).unwrap();
+        assert!(lazy[0].annotated_hexdump().ends_with("data, 7 bytes, not loaded\n"));
+    }
+}
@@ -0,0 +1,113 @@
+//! HTML gallery reports, enabled with the `html` feature
+
+#[cfg(feature = "fs")]
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+
+use crate::{CacheEntry, PayloadKind, Thumbscache};
+#[cfg(feature = "fs")]
+use crate::ThumbsError;
+
+// Escapes the characters that have a meaning in HTML text and attributes
+fn escape(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+impl CacheEntry {
+    /// Encodes the image held by the entry as a data URI, "data:image/jpeg;base64,/9j/..." for instance, to embed it without writing a file.
+    ///
+    /// The MIME type is sniffed from the data, headerless pixel data is turned into a BMP like `write_to_file()` does.
+    /// Returns None if the data isn't an image in a known format.
+    pub fn to_data_uri(&self) -> Option<String> {
+        match self.viewable_data() {
+            (data, PayloadKind::Image(format)) => Some(format!("data:{};base64,{}", format.mime_type(), base64::engine::general_purpose::STANDARD.encode(&data))),
+            _ => None
+        }
+    }
+}
+
+impl Thumbscache {
+    /// Writes an HTML page listing every entry with its thumbnail embedded, for triage in a browser.
+    ///
+    /// The page is named after the cache type (report_Res256.html for instance) and written into the directory, which is created if needed.
+    /// Returns the path of the page.
+    #[cfg(feature = "fs")]
+    pub fn export_html_report(&self, dir: impl AsRef<Path>) -> Result<PathBuf, ThumbsError> {
+        std::fs::create_dir_all(&dir).map_err(ThumbsError::IoError)?;
+        let cache_type = self.cache_type.map(|x| format!("{:?}", x)).unwrap_or_else(|| "Unknown".to_string());
+        let path = dir.as_ref().join(format!("report_{}.html", cache_type));
+        crate::write_data(&path, self.to_html_report().as_bytes())?;
+        Ok(path)
+    }
+
+    /// Builds the HTML page of `Thumbscache::export_html_report()` without writing it
+    pub fn to_html_report(&self) -> String {
+        let cache_type = self.cache_type.map(|x| format!("{:?}", x)).unwrap_or_else(|| "Unknown".to_string());
+        let windows_version = self.windows_version.map(|x| x.detail().to_string()).unwrap_or_else(|| "Unknown".to_string());
+        let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        html.push_str(&format!("<title>Thumbnail cache {}</title>\n", escape(&cache_type)));
+        html.push_str("<style>body { font-family: sans-serif; } td { padding: 4px 8px; border-bottom: 1px solid #ccc; } .corrupted { color: #b00; } img { max-width: 256px; max-height: 256px; }</style>\n");
+        html.push_str("</head>\n<body>\n");
+        html.push_str(&format!("<h1>Thumbnail cache {}</h1>\n<p>Windows version : {}, {} entries</p>\n", escape(&cache_type), escape(&windows_version), self.len()));
+        html.push_str("<table>\n<tr><th>Thumbnail</th><th>Identifier</th><th>Entry hash</th><th>Data size</th><th>Cache type</th><th>Origin</th><th>Checksums</th></tr>\n");
+        for entry in self.iter() {
+            // Entries read without their data are shown from the data in the database
+            let loaded = self.loaded_entry(entry).ok();
+            let thumbnail = match loaded.as_ref().and_then(|x| x.to_data_uri()) {
+                Some(uri) => format!("<img src=\"{}\" alt=\"{}\">", uri, escape(&entry.identifier_string)),
+                None => String::new()
+            };
+            let checksums = if entry.verify_data(self.loaded_data(entry)).is_valid() {
+                "<td>valid</td>"
+            }else {
+                "<td class=\"corrupted\">mismatch</td>"
+            };
+            html.push_str(&format!("<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:?}</td>{}</tr>\n",
+                thumbnail,
+                escape(&entry.identifier_string),
+                entry.entry_hash_hex(),
+                entry.data_size,
+                escape(&cache_type),
+                entry.origin,
+                checksums));
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+        html
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_database;
+    use std::io::Cursor;
+
+    #[test]
+    fn writes_gallery_page() {
+        let mut database = Thumbscache::from_reader(Cursor::new(build_database(32, 1, &[(0xAB, b"BMdata"), (0xCD, b"unknown")]))).unwrap();
+        database.read().unwrap();
+        let html = database.to_html_report();
+        #[cfg(feature = "fs")]
+        {
+            let path = database.export_html_report(crate::tests::temp_dir("html_report")).unwrap();
+            assert!(path.ends_with("report_Res32.html"));
+            assert_eq!(std::fs::read_to_string(path).unwrap(), html);
+        }
+        assert!(html.contains("<img src=\"data:image/bmp;base64,Qk1kYXRh\""));
+        assert!(html.contains("<td>00000000000000cd</td>"));
+        assert_eq!(html.matches("<img").count(), 1);
+        assert_eq!(html.matches("<td>valid</td>").count(), 2);
+        assert_eq!(escape("<a href=\"x\">&"), "&lt;a href=&quot;x&quot;&gt;&amp;");
+    }
+
+    #[test]
+    fn encodes_data_uris() {
+        let pixels = vec![0u8; 96 * 96 * 4];
+        let mut database = Thumbscache::from_bytes(build_database(32, 3, &[(1, b"\xFF\xD8\xFF"), (2, &pixels), (3, b"unknown")]));
+        database.read().unwrap();
+        assert_eq!(database.entries()[0].to_data_uri().as_deref(), Some("data:image/jpeg;base64,/9j/"));
+        assert!(database.entries()[1].to_data_uri().unwrap().starts_with("data:image/bmp;base64,Qk"));
+        assert_eq!(database.entries()[2].to_data_uri(), None);
+    }
+}
@@ -0,0 +1,195 @@
+//! Typed decoding of identifier strings
+//!
+//! Windows 7 and later name every entry after its hash, written as 16 hexadecimal UTF-16 characters.
+//! Windows Vista identifiers can hold other, path-like strings, so they are never read as a hash.
+//! Identifiers that aren't valid UTF-16LE are kept as raw bytes instead of being decoded lossily.
+
+use alloc::{format, string::String, vec::Vec};
+use core::fmt;
+
+use crate::{parse::CacheEntryRef, CacheEntry, VersionFields, WindowsVersion};
+
+/// Formats an entry hash the way Explorer names entries, as 16 lowercase hexadecimal digits, optionally preceded by 0x
+pub fn format_entry_hash(entry_hash: u64, prefixed: bool) -> String {
+    if prefixed {
+        format!("{:#018x}", entry_hash)
+    }else {
+        format!("{:016x}", entry_hash)
+    }
+}
+
+/// Parses an entry hash written in hexadecimal, as formatted by `format_entry_hash()` or other tools.
+///
+/// Surrounding whitespace and a 0x prefix are ignored, the digits are case insensitive and leading zeros can be left out.
+/// Returns None if there are more than 16 digits or anything else than hexadecimal digits.
+pub fn parse_entry_hash(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let digits = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")).unwrap_or(text);
+    if digits.is_empty() || digits.len() > 16 || !digits.bytes().all(|x| x.is_ascii_hexdigit()) {
+        return None;
+    }
+    u64::from_str_radix(digits, 16).ok()
+}
+
+/// A decoded identifier string, see `CacheEntry::identifier()`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Identifier {
+    /// The hash of the cached item, written as 16 hexadecimal digits
+    Hash(u64),
+    /// Any other text, trailing null characters removed
+    Utf16(String),
+    /// Bytes that aren't valid UTF-16LE
+    Raw(Vec<u8>)
+}
+
+impl Identifier {
+    /// Decodes the raw bytes of an identifier string with the rules of the Windows version, None applies the rules of Windows 7 and later.
+    ///
+    /// Text is only read as a hash outside Windows Vista, bytes of odd length or with unpaired surrogates are kept raw.
+    pub fn decode(bytes: &[u8], windows_version: Option<WindowsVersion>) -> Identifier {
+        if !bytes.len().is_multiple_of(2) {
+            return Identifier::Raw(bytes.to_vec());
+        }
+        let units = bytes.chunks_exact(2).map(|a| u16::from_le_bytes([a[0], a[1]]));
+        let Ok(text) = char::decode_utf16(units).collect::<Result<String, _>>() else {
+            return Identifier::Raw(bytes.to_vec());
+        };
+        let text = text.trim_end_matches('\0');
+        if windows_version != Some(WindowsVersion::WinVista) && text.len() == 16 && text.bytes().all(|x| x.is_ascii_hexdigit()) {
+            if let Some(hash) = parse_entry_hash(text) {
+                return Identifier::Hash(hash);
+            }
+        }
+        Identifier::Utf16(String::from(text))
+    }
+
+    /// The hash, if the identifier is one
+    pub fn as_hash(&self) -> Option<u64> {
+        match self {
+            Identifier::Hash(hash) => Some(*hash),
+            _ => None
+        }
+    }
+
+    /// The name of the cached item held by the identifier, as written by Windows Vista.
+    ///
+    /// Null characters are removed wherever they are, including a stray null byte making the length odd, and surrounding whitespace is trimmed.
+    /// Returns None for hashes, for bytes that still aren't valid UTF-16LE and for names that are empty or hold control characters.
+    pub fn original_name(&self) -> Option<String> {
+        let text = match self {
+            Identifier::Hash(_) => return None,
+            Identifier::Utf16(text) => text.clone(),
+            Identifier::Raw(bytes) => {
+                let bytes = match bytes.strip_suffix(&[0]) {
+                    Some(rest) if !bytes.len().is_multiple_of(2) => rest,
+                    _ => bytes.as_slice()
+                };
+                if !bytes.len().is_multiple_of(2) {
+                    return None;
+                }
+                char::decode_utf16(bytes.chunks_exact(2).map(|a| u16::from_le_bytes([a[0], a[1]]))).collect::<Result<String, _>>().ok()?
+            }
+        };
+        let name: String = text.chars().filter(|&x| x != '\0').collect();
+        let name = name.trim();
+        if name.is_empty() || name.chars().any(char::is_control) {
+            return None;
+        }
+        Some(String::from(name))
+    }
+}
+
+/// Hashes are written as 16 lowercase hexadecimal digits and raw bytes as hexadecimal pairs
+impl fmt::Display for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Identifier::Hash(hash) => f.write_str(&format_entry_hash(*hash, false)),
+            Identifier::Utf16(text) => f.write_str(text),
+            Identifier::Raw(bytes) => bytes.iter().try_for_each(|x| write!(f, "{:02x}", x))
+        }
+    }
+}
+
+// The entry headers only tell Vista apart from later versions, which is all the decoding rules need
+fn version_of(fields: &VersionFields) -> Option<WindowsVersion> {
+    match fields {
+        VersionFields::Vista { .. } => Some(WindowsVersion::WinVista),
+        _ => None
+    }
+}
+
+impl CacheEntry {
+    /// Decodes the identifier string into a hash, text or raw bytes, see `Identifier::decode()`
+    pub fn identifier(&self) -> Identifier {
+        Identifier::decode(self.identifier_bytes(), version_of(&self.version_fields))
+    }
+
+    /// The cleaned name of the cached item, for Windows Vista identifiers holding one, see `Identifier::original_name()`
+    pub fn original_name(&self) -> Option<String> {
+        self.identifier().original_name()
+    }
+}
+
+impl CacheEntryRef<'_> {
+    /// Decodes the identifier string into a hash, text or raw bytes, see `Identifier::decode()`
+    pub fn identifier(&self) -> Identifier {
+        Identifier::decode(self.identifier_bytes(), version_of(&self.version_fields))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_database, tests::build_database, ReadOptions};
+
+    fn utf16(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(|x| x.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn round_trips_entry_hashes() {
+        assert_eq!(format_entry_hash(0xAB, false), "00000000000000ab");
+        assert_eq!(format_entry_hash(0xAB, true), "0x00000000000000ab");
+        assert_eq!(format_entry_hash(u64::MAX, true), "0xffffffffffffffff");
+        for text in ["00000000000000ab", "0x00000000000000AB", " 0Xab\n", "AB"] {
+            assert_eq!(parse_entry_hash(text), Some(0xAB), "{}", text);
+        }
+        for text in ["", "0x", "+ab", "0x0x1", "00000000000000000ab", "1122 3344", "ghij"] {
+            assert_eq!(parse_entry_hash(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn decodes_with_the_rules_of_each_version() {
+        assert_eq!(Identifier::decode(&utf16("00000000000000aB"), Some(WindowsVersion::Win7)), Identifier::Hash(0xAB));
+        assert_eq!(Identifier::decode(&utf16("00000000000000ab\0"), None), Identifier::Hash(0xAB));
+        assert_eq!(Identifier::decode(&utf16("00000000000000ab"), Some(WindowsVersion::WinVista)), Identifier::Utf16("00000000000000ab".into()));
+        assert_eq!(Identifier::decode(&utf16("C:\\photo.jpg\0\0"), Some(WindowsVersion::Win10)), Identifier::Utf16("C:\\photo.jpg".into()));
+        // An unpaired surrogate and an odd length
+        assert_eq!(Identifier::decode(&[0x00, 0xD8, 0x41, 0x00], None), Identifier::Raw(vec![0x00, 0xD8, 0x41, 0x00]));
+        assert_eq!(Identifier::decode(b"abc", None).to_string(), "616263");
+        assert_eq!(Identifier::Hash(0xAB).to_string(), "00000000000000ab");
+
+        assert_eq!(Identifier::decode(&utf16(" 000000000000abc"), None), Identifier::Utf16(" 000000000000abc".into()));
+        let bytes = build_database(20, 0, &[(0xCD, b"data")]);
+        let (_, entries) = parse_database(&bytes, ReadOptions::default()).unwrap();
+        assert_eq!(entries[0].identifier(), Identifier::Utf16("00000000000000cd".into()));
+        let bytes = build_database(32, 0, &[(0xCD, b"data")]);
+        let (_, entries) = parse_database(&bytes, ReadOptions::default()).unwrap();
+        assert_eq!(entries[0].identifier().as_hash(), Some(0xCD));
+        assert_eq!(entries[0].original_name(), None);
+    }
+
+    #[test]
+    fn cleans_up_original_names() {
+        assert_eq!(Identifier::decode(&utf16("C:\\Users\\z\0\\photo.jpg \0\0"), Some(WindowsVersion::WinVista)).original_name().as_deref(), Some("C:\\Users\\z\\photo.jpg"));
+        // A stray null byte after the UTF-16 characters
+        let mut bytes = utf16("photo.jpg");
+        bytes.push(0);
+        assert_eq!(Identifier::decode(&bytes, Some(WindowsVersion::WinVista)).original_name().as_deref(), Some("photo.jpg"));
+        for identifier in [Identifier::Hash(0xAB), Identifier::Utf16("\0 \0".into()), Identifier::Utf16("a\u{1}b".into()), Identifier::Raw(vec![0x00, 0xD8, 0x41, 0x00])] {
+            assert_eq!(identifier.original_name(), None, "{:?}", identifier);
+        }
+    }
+}
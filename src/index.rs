@@ -0,0 +1,130 @@
+//! Parser for the thumbcache_idx.db index file
+//!
+//! The index file keeps one record per cached item, listing the offset of the item in every thumbcache_*.db database that holds a copy of it.
+
+#[cfg(feature = "fs")]
+use std::{io::Read, path::Path};
+
+use crate::{endian::LittleEndian, parse::slice_at, CacheType, ThumbsError, WindowsVersion};
+
+// Offsets set to this value mean the item isn't stored in that database
+const NO_ENTRY: u32 = 0xFFFF_FFFF;
+const HEADER_SIZE: usize = 24;
+
+/// Index entry
+///
+/// Represents a single record of the index file.
+#[derive(Debug, Clone)]
+pub struct IndexEntry {
+    pub entry_hash: u64,
+    pub flags: u32,
+    /// Last modification time as a FILETIME (only applicable for Windows Vista)
+    pub last_modified: Option<u64>,
+    /// The offset of the cache entry in each database that holds a copy of it
+    pub offsets: Vec<(CacheType, u32)>
+}
+
+impl IndexEntry {
+    /// Returns the offset of the cache entry inside the database of the given cache type
+    pub fn offset_for(&self, cache_type: CacheType) -> Option<u32> {
+        self.offsets.iter().find(|(x, _)| *x == cache_type).map(|(_, offset)| *offset)
+    }
+}
+
+/// Thumbcache index
+///
+/// Represents a parsed thumbcache_idx.db file.
+#[derive(Debug, Clone)]
+pub struct ThumbcacheIndex {
+    pub windows_version: WindowsVersion,
+    pub used_entries: u32,
+    pub total_entries: u32,
+    pub entries: Vec<IndexEntry>
+}
+
+impl ThumbcacheIndex {
+    /// Opens and parses the index file
+    #[cfg(feature = "fs")]
+    pub fn open(file: impl AsRef<Path>) -> Result<ThumbcacheIndex, ThumbsError> {
+        let mut bytes: Vec<u8> = Vec::new();
+        if let Ok(mut opened_file) = std::fs::OpenOptions::new().read(true).open(file) {
+            opened_file.read_to_end(&mut bytes).map_err(ThumbsError::IoError)?;
+            ThumbcacheIndex::parse(&bytes)
+        }else {
+            Err(ThumbsError::InvalidFile)
+        }
+    }
+
+    /// Parses the contents of an index file
+    pub fn parse(bytes: &[u8]) -> Result<ThumbcacheIndex, ThumbsError> {
+        let header = slice_at(bytes, 0, HEADER_SIZE as u64)?;
+        if let Ok(check_string) = std::str::from_utf8(&header[0..4]) {
+            if check_string != "IMMM" {
+                return Err(ThumbsError::UnexpectedIndexString(check_string.to_string()));
+            }
+        }else {
+            return Err(ThumbsError::InvalidCheckString);
+        }
+        let format_version: u32 = bytes.u32_le(4);
+        let windows_version = WindowsVersion::from_format_version(format_version).ok_or(ThumbsError::InvalidFile)?;
+        let cache_types = windows_version.cache_types();
+        // Windows 8 and above added an extra field both to the header and to the entries
+        let (used_entries, total_entries, entry_header_size) = match windows_version {
+            WindowsVersion::WinVista => (bytes.u32_le(12), bytes.u32_le(16), 20),
+            WindowsVersion::Win7 => (bytes.u32_le(12), bytes.u32_le(16), 12),
+            _ => (bytes.u32_le(16), bytes.u32_le(20), 16)
+        };
+        let entry_size = entry_header_size + 4 * cache_types.len();
+        let mut entries = Vec::new();
+        for record in bytes[HEADER_SIZE..].chunks_exact(entry_size) {
+            let entry_hash: u64 = record.u64_le(0);
+            // Unused slots of the hash table are zeroed out
+            if entry_hash == 0 {
+                continue;
+            }
+            let (flags, last_modified) = match windows_version {
+                WindowsVersion::WinVista => (record.u32_le(16), Some(record.u64_le(8))),
+                _ => (record.u32_le(8), None)
+            };
+            let offsets = record[entry_header_size..].chunks_exact(4)
+                .zip(cache_types)
+                .map(|(offset, cache_type)| (*cache_type, offset.u32_le(0)))
+                .filter(|(_, offset)| *offset != NO_ENTRY)
+                .collect();
+            entries.push(IndexEntry {
+                entry_hash,
+                flags,
+                last_modified,
+                offsets
+            });
+        }
+        Ok(ThumbcacheIndex {
+            windows_version,
+            used_entries,
+            total_entries,
+            entries
+        })
+    }
+
+    /// Returns the index entry of the given entry hash
+    pub fn get(&self, entry_hash: u64) -> Option<&IndexEntry> {
+        self.entries.iter().find(|x| x.entry_hash == entry_hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_index;
+
+    #[test]
+    fn parses_offsets_of_win10_index() {
+        let bytes = build_index(32, &[(0xAABB, &[(0, 24), (1, 24)])]);
+        let index = ThumbcacheIndex::parse(&bytes).unwrap();
+        assert_eq!(index.entries.len(), 1);
+        let entry = index.get(0xAABB).unwrap();
+        assert_eq!(entry.offset_for(CacheType::Res16), Some(24));
+        assert_eq!(entry.offset_for(CacheType::Res32), Some(24));
+        assert_eq!(entry.offset_for(CacheType::Res48), None);
+    }
+}
@@ -0,0 +1,66 @@
+//! Iterating over the cache entries of a database
+
+use std::slice::{Iter, IterMut};
+
+use crate::{CacheEntry, Thumbscache};
+
+impl Thumbscache {
+    /// Returns an iterator over the parsed cache entries
+    pub fn iter(&self) -> Iter<'_, CacheEntry> {
+        self.entries().iter()
+    }
+
+    /// Returns an iterator allowing to modify the parsed cache entries
+    pub fn iter_mut(&mut self) -> IterMut<'_, CacheEntry> {
+        self.entries_mut().iter_mut()
+    }
+}
+
+impl<'a> IntoIterator for &'a Thumbscache {
+    type Item = &'a CacheEntry;
+    type IntoIter = Iter<'a, CacheEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Thumbscache {
+    type Item = &'a mut CacheEntry;
+    type IntoIter = IterMut<'a, CacheEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl IntoIterator for Thumbscache {
+    type Item = CacheEntry;
+    type IntoIter = std::vec::IntoIter<CacheEntry>;
+
+    /// Consumes the database, returning its parsed cache entries
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_entries().into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_database;
+
+    #[test]
+    fn iterates_over_entries() {
+        let mut database = Thumbscache::from_bytes(build_database(32, 1, &[(1, b"first"), (2, b""), (3, b"third")]));
+        database.read().unwrap();
+        assert_eq!(database.iter().len(), 3);
+        let hashes: Vec<u64> = (&database).into_iter().filter(|x| x.data_size > 0).map(|x| x.entry_hash()).collect();
+        assert_eq!(hashes, [1, 3]);
+        for entry in &mut database {
+            entry.data.clear();
+        }
+        assert!(database.iter().all(|x| x.data.is_empty()));
+        let entries: Vec<CacheEntry> = database.into_iter().collect();
+        assert_eq!(entries.len(), 3);
+    }
+}
@@ -0,0 +1,111 @@
+//! Fetching the data of entries read without it, see `ReadOptions::lazy()`
+
+use alloc::borrow::Cow;
+
+use crate::{CacheEntry, ReadOptions, Thumbscache, ThumbsError};
+
+impl ReadOptions {
+    /// Options reading every entry without copying its data
+    pub fn lazy() -> ReadOptions {
+        ReadOptions {
+            load_data: false,
+            ..ReadOptions::default()
+        }
+    }
+}
+
+impl CacheEntry {
+    /// Returns true if the data of the entry is loaded, entries without data are always loaded
+    pub fn is_data_loaded(&self) -> bool {
+        self.data.len() == self.data_size as usize
+    }
+
+    /// Copies the data of the entry from the database it was read from
+    pub fn load_data(&mut self, database: &Thumbscache) -> Result<(), ThumbsError> {
+        if self.is_data_loaded() {
+            return Ok(());
+        }
+        let data = database.entry_data(self).ok_or(ThumbsError::TruncatedEntry {
+            offset: self.offset,
+            needed: (self.data_offset + self.data_size as u64).saturating_sub(database.bytes().len() as u64)
+        })?;
+        self.data = data.to_vec();
+        Ok(())
+    }
+}
+
+impl Thumbscache {
+    /// Loads the data of the entry at the given position, returning the entry.
+    ///
+    /// Returns ThumbsError::EntryNotFound if there is no entry at this position.
+    pub fn load_entry_data(&mut self, index: usize) -> Result<&CacheEntry, ThumbsError> {
+        let Some(mut entry) = self.entries().get(index).cloned() else {
+            return Err(ThumbsError::EntryNotFound { index });
+        };
+        entry.load_data(self)?;
+        self.entry_list()[index] = entry;
+        Ok(&self.entries()[index])
+    }
+
+    // The data of the entry, taken from the database when the entry was read without it.
+    // Entries holding part of their data, such as carved ones cut short, keep what they hold.
+    pub(crate) fn loaded_data<'a>(&'a self, entry: &'a CacheEntry) -> &'a [u8] {
+        if !entry.data.is_empty() || entry.is_data_loaded() {
+            &entry.data
+        }else {
+            self.entry_data(entry).unwrap_or(&entry.data)
+        }
+    }
+
+    // The entry along with its data, copied from the database when the entry was read without it
+    #[cfg_attr(not(any(feature = "fs", feature = "sqlite", feature = "html")), allow(dead_code))]
+    pub(crate) fn loaded_entry<'a>(&self, entry: &'a CacheEntry) -> Result<Cow<'a, CacheEntry>, ThumbsError> {
+        if !entry.data.is_empty() || entry.is_data_loaded() {
+            return Ok(Cow::Borrowed(entry));
+        }
+        let mut loaded = entry.clone();
+        loaded.load_data(self)?;
+        Ok(Cow::Owned(loaded))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_database;
+    use std::io::Cursor;
+
+    #[test]
+    fn loads_data_on_demand() {
+        let mut database = Thumbscache::from_reader(Cursor::new(build_database(32, 1, &[(1, b"first"), (2, b"second")]))).unwrap();
+        database.read_with_options(ReadOptions::lazy()).unwrap();
+        assert!(!database.entries()[0].is_data_loaded());
+        let mut entry = database.entries()[0].clone();
+        entry.load_data(&database).unwrap();
+        assert_eq!(entry.data, b"first");
+        assert_eq!(database.load_entry_data(1).unwrap().data, b"second");
+        assert!(database.entries()[1].is_data_loaded());
+        assert!(matches!(database.load_entry_data(2), Err(ThumbsError::EntryNotFound { index: 2 })));
+    }
+
+    #[test]
+    fn lazily_read_entries_are_verified_and_reported_from_the_database() {
+        let mut database = Thumbscache::from_reader(Cursor::new(build_database(32, 1, &[(1, b"BMfirst"), (2, b""), (3, b"third")]))).unwrap();
+        database.read_with_options(ReadOptions::lazy()).unwrap();
+        assert!(database.verify_all().is_clean());
+        assert!(database.check_all().is_ok());
+        assert!(database.to_csv_report().contains(",bmp,"));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn lazily_read_entries_are_exported_from_the_database() {
+        let mut database = Thumbscache::from_reader(Cursor::new(build_database(32, 1, &[(1, b"BMfirst"), (2, b""), (3, b"third")]))).unwrap();
+        database.read_with_options(ReadOptions::lazy()).unwrap();
+        let dir = crate::tests::temp_dir("lazy_export");
+        let summary = database.export_all(&dir, crate::NamingStrategy::Index).unwrap();
+        assert_eq!(summary.written.len(), 2);
+        assert_eq!(summary.skipped, [1]);
+        assert_eq!(std::fs::read(dir.join("0.bmp")).unwrap(), b"BMfirst");
+    }
+}
@@ -2,21 +2,190 @@
 //! 
 //! <https://en.wikipedia.org/wiki/Windows_thumbnail_cache>
 //! This library provides an easy-to-use function to read the contents of the thumbnail cache files and view the cache entries of it
-//! Supports Windows Vista and above
-//! TODO :
-//! - Data and header verification
+//! Supports Windows Vista and above, the Thumbs.db files of Windows XP can be read using the thumbsdb module
+//! Large databases can be enumerated without loading them into memory using the ThumbscacheReader
+//! Without the default `std` feature only the parsing core is built, working on `no_std` targets with an allocator (see parse_database)
+//! Parsing never panics, whatever the input: malformed or hostile files end in an error or in fewer entries.
+//! This is checked by fuzzing and by the tests, which run every public parsing function over damaged databases
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
-use std::{fs::{File, OpenOptions}, io::{Cursor, Read, Write}};
+extern crate alloc;
+
+use alloc::{string::String, vec::Vec};
+#[cfg(feature = "std")]
+use std::{io::{Cursor, Read, Seek, SeekFrom}, path::Path, sync::atomic::{AtomicBool, Ordering}, time::SystemTime};
 
 use thiserror::Error;
 
+#[macro_use]
+mod trace;
+
+mod bitmap;
+mod borrowed;
+#[cfg(feature = "std")]
+mod builder;
+#[cfg(feature = "std")]
+mod carve;
+mod checksum;
+#[cfg(feature = "std")]
+mod consistency;
+#[cfg(feature = "compressed-input")]
+mod compressed;
+#[cfg(feature = "image")]
+mod decode;
+#[cfg(feature = "decompress")]
+mod decompress;
+#[cfg(feature = "std")]
+mod diff;
+mod dimensions;
+mod endian;
+#[cfg(feature = "std")]
+mod evidence;
+mod exif;
+#[cfg(feature = "fs")]
+mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+mod file;
+mod flags;
+#[cfg(feature = "hashes")]
+mod hashes;
+mod hexdump;
+#[cfg(feature = "html")]
+mod html;
+mod identifier;
+#[cfg(feature = "std")]
+mod index;
+#[cfg(feature = "std")]
+mod iter;
+#[cfg(feature = "std")]
+mod lazy;
+#[cfg(feature = "fs")]
+mod locate;
+#[cfg(feature = "std")]
+mod lossy;
+#[cfg(all(feature = "fs", feature = "hashes"))]
+mod manifest;
+#[cfg(feature = "std")]
+mod modify;
+#[cfg(feature = "std")]
+mod ordering;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod parse;
+#[cfg(feature = "phash")]
+mod phash;
+#[cfg(feature = "std")]
+mod query;
+#[cfg(feature = "fs")]
+mod redact;
+#[cfg(feature = "fs")]
+mod refresh;
+#[cfg(feature = "std")]
+mod registry;
+#[cfg(feature = "std")]
+mod report;
+mod role;
+#[cfg(feature = "serde")]
+mod schema;
+#[cfg(feature = "std")]
+mod search;
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "std")]
+mod set;
+#[cfg(feature = "std")]
+mod shared;
+mod spans;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(feature = "std")]
+mod stats;
+#[cfg(feature = "std")]
+mod stream;
+#[cfg(feature = "std")]
+pub mod thumbsdb;
+#[cfg(feature = "std")]
+mod timeline;
+mod version;
+#[cfg(feature = "watch")]
+mod watch;
+mod writer;
+
+pub use borrowed::ThumbscacheRef;
+#[cfg(feature = "std")]
+pub use builder::{HeaderPolicy, ReadReport, Strictness, ThumbscacheBuilder};
+pub use checksum::{ChecksumStatus, CorruptedEntry, VerificationReport};
+#[cfg(feature = "std")]
+pub use consistency::ConsistencyReport;
+#[cfg(feature = "compressed-input")]
+pub use compressed::thumbscaches_in_zip;
+#[cfg(feature = "decompress")]
+pub use decompress::Compression;
+#[cfg(feature = "std")]
+pub use diff::CacheDiff;
+#[cfg(feature = "std")]
+pub use evidence::EvidenceSource;
+pub use exif::{ExifData, ExifDirectory, ExifField, ExifValue};
+#[cfg(feature = "fs")]
+pub use export::{CollisionPolicy, ExportedFile, ExportOptions, ExportSummary, HashExtraction, NameFn, NamingStrategy, UniqueExport, WriteOutcome};
+#[cfg(feature = "std")]
+pub use file::{CacheFileKind, ThumbcacheFile};
+pub use flags::EntryFlags;
+#[cfg(feature = "hashes")]
+pub use hashes::EntryHashes;
+pub use identifier::{format_entry_hash, parse_entry_hash, Identifier};
+#[cfg(feature = "std")]
+pub use index::{IndexEntry, ThumbcacheIndex};
+#[cfg(feature = "fs")]
+pub use locate::{locate_thumbcache_databases, locate_thumbcache_dirs, scan_system, UserCaches};
+#[cfg(feature = "std")]
+pub use lossy::ParseWarning;
+#[cfg(all(feature = "fs", feature = "hashes"))]
+pub use manifest::ManifestFormat;
+#[cfg(feature = "mmap")]
+pub use mmap::open_thumbscache_mmap;
+#[cfg(feature = "std")]
+pub use ordering::{EntryOrdering, OrderingReport};
+#[cfg(feature = "rayon")]
+pub use parallel::{open_thumbscache_dir, ParallelVerificationReport};
+pub use parse::{parse_database, parse_database_ref, parse_entry_at, parse_header, scan_database_headers, CacheEntryRef, CacheHeader, EntrySummary, HeaderValidation, OffsetStatus};
+#[cfg(feature = "phash")]
+pub use phash::PerceptualHash;
+#[cfg(feature = "fs")]
+pub use redact::Redaction;
+#[cfg(feature = "std")]
+pub use registry::{CacheSetting, RegistrySettings};
+pub use role::DatabaseRole;
+#[cfg(feature = "serde")]
+pub use schema::{DatabaseReportV1, EntryReportV1, RegistryValueV1, ReportEncoding, ReportV1, REPORT_SCHEMA_VERSION};
+#[cfg(feature = "std")]
+pub use search::{ResolvedEntry, SearchRow};
+#[cfg(feature = "std")]
+pub use set::ThumbcacheSet;
+#[cfg(feature = "std")]
+pub use shared::ParsedCache;
+pub use spans::{EntrySpans, HeaderSpans, Span};
+#[cfg(feature = "std")]
+pub use stats::CacheStats;
+#[cfg(feature = "std")]
+pub use stream::{Entries, EntryHeader, ThumbscacheReader};
+pub use version::VersionDetail;
+#[cfg(feature = "watch")]
+pub use watch::{CacheWatcher, WatchEvent};
+pub use writer::ThumbscacheWriter;
+
 /// The Windows version associated with the thumbnail cache file
 /// 
 /// Thumbnail cache files can have different structures depending on its Windows version. This enum can provide the Windows version used for the file.
 /// 
 /// Note : Windows 10 also includes Windows 11.
-#[derive(Clone, Copy, Debug)]
+/// Every Windows 10 build, 1703 and later included, writes the same format version and the 56 byte entry header of Windows 8,
+/// so there is no separate variant for them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum WindowsVersion {
     WinVista,
     Win7,
@@ -25,7 +194,8 @@ pub enum WindowsVersion {
     Win10
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum CacheType {
     Res16,
     Res32,
@@ -45,6 +215,146 @@ pub enum CacheType {
     CustomStream
 }
 
+impl CacheType {
+    /// Determines the cache type from the name of a database file such as "thumbcache_768.db", the directory is ignored.
+    ///
+    /// Returns None for the index file and names that don't follow the thumbcache_*.db or iconcache_*.db pattern.
+    pub fn from_filename(file_name: &str) -> Option<CacheType> {
+        let file_name = file_name.rsplit(['/', '\\']).next()?.to_ascii_lowercase();
+        let name = [DatabaseKind::Thumbnail, DatabaseKind::IconCache].iter().find_map(|x| file_name.strip_prefix(x.file_prefix()))?.strip_suffix(".db")?;
+        Some(match name {
+            "16" => CacheType::Res16,
+            "32" => CacheType::Res32,
+            "48" => CacheType::Res48,
+            "96" => CacheType::Res96,
+            "256" => CacheType::Res256,
+            "768" => CacheType::Res768,
+            "1024" => CacheType::Res1024,
+            "1280" => CacheType::Res1280,
+            "1600" => CacheType::Res1600,
+            "1920" => CacheType::Res1920,
+            "2560" => CacheType::Res2560,
+            "sr" => CacheType::SR,
+            "wide" => CacheType::Wide,
+            "exif" => CacheType::EXIF,
+            "wide_alternate" => CacheType::WideAlternate,
+            "custom_stream" => CacheType::CustomStream,
+            _ => return None
+        })
+    }
+
+    /// The name of the cache type in database file names, "768" for thumbcache_768.db for instance
+    pub fn name(&self) -> &'static str {
+        match self {
+            CacheType::Res16 => "16",
+            CacheType::Res32 => "32",
+            CacheType::Res48 => "48",
+            CacheType::Res96 => "96",
+            CacheType::Res256 => "256",
+            CacheType::Res768 => "768",
+            CacheType::Res1024 => "1024",
+            CacheType::Res1280 => "1280",
+            CacheType::Res1600 => "1600",
+            CacheType::Res1920 => "1920",
+            CacheType::Res2560 => "2560",
+            CacheType::SR => "sr",
+            CacheType::Wide => "wide",
+            CacheType::EXIF => "exif",
+            CacheType::WideAlternate => "wide_alternate",
+            CacheType::CustomStream => "custom_stream"
+        }
+    }
+
+    /// The largest side of the thumbnails stored by this cache type, in pixels, from 16 to 2560.
+    ///
+    /// Windows scales images down until they fit, smaller images keep their size.
+    /// Returns None for the cache types that aren't tied to a single resolution.
+    pub fn max_dimension(&self) -> Option<u32> {
+        match self {
+            CacheType::Res16 => Some(16),
+            CacheType::Res32 => Some(32),
+            CacheType::Res48 => Some(48),
+            CacheType::Res96 => Some(96),
+            CacheType::Res256 => Some(256),
+            CacheType::Res768 => Some(768),
+            CacheType::Res1024 => Some(1024),
+            CacheType::Res1280 => Some(1280),
+            CacheType::Res1600 => Some(1600),
+            CacheType::Res1920 => Some(1920),
+            CacheType::Res2560 => Some(2560),
+            _ => None
+        }
+    }
+
+    /// Returns true for the cache types holding thumbnails of a single resolution, from Res16 to Res2560
+    pub fn is_resolution(&self) -> bool {
+        self.max_dimension().is_some()
+    }
+}
+
+// The format versions written into database headers, along with the layout they use.
+// Every Windows 10 and 11 revision seen so far, including the ones adding the 768, 1280, 1920 and 2560 caches, writes 32.
+// A newer revision using its own number but an existing layout only needs a line here.
+const FORMAT_VERSIONS: [(u32, WindowsVersion); 5] = [
+    (20, WindowsVersion::WinVista),
+    (21, WindowsVersion::Win7),
+    (30, WindowsVersion::Win8),
+    (31, WindowsVersion::Win81),
+    (32, WindowsVersion::Win10)
+];
+
+impl WindowsVersion {
+    /// Maps the format version found in the database header to a Windows version
+    pub fn from_format_version(format_version: u32) -> Option<WindowsVersion> {
+        FORMAT_VERSIONS.iter().find(|(x, _)| *x == format_version).map(|(_, version)| *version)
+    }
+
+    /// The format version written into the database header by this Windows version
+    pub fn format_version(&self) -> u32 {
+        FORMAT_VERSIONS.iter().find(|(_, x)| x == self).map(|(format_version, _)| *format_version).unwrap_or_default()
+    }
+
+    /// The cache types used by this Windows version, in the order Windows numbers them.
+    ///
+    /// The cache type field of a database header and the offset columns of the index file both follow this order.
+    pub fn cache_types(&self) -> &'static [CacheType] {
+        match self {
+            WindowsVersion::WinVista | WindowsVersion::Win7 => &[CacheType::Res32, CacheType::Res96, CacheType::Res256, CacheType::Res1024, CacheType::SR],
+            WindowsVersion::Win8 => &[CacheType::Res16, CacheType::Res32, CacheType::Res48, CacheType::Res96, CacheType::Res256, CacheType::Res1024, CacheType::SR, CacheType::Wide, CacheType::EXIF],
+            WindowsVersion::Win81 => &[CacheType::Res16, CacheType::Res32, CacheType::Res48, CacheType::Res96, CacheType::Res256, CacheType::Res1024, CacheType::Res1600, CacheType::SR, CacheType::Wide, CacheType::EXIF, CacheType::WideAlternate],
+            WindowsVersion::Win10 => &[CacheType::Res16, CacheType::Res32, CacheType::Res48, CacheType::Res96, CacheType::Res256, CacheType::Res768, CacheType::Res1280, CacheType::Res1920, CacheType::Res2560, CacheType::SR, CacheType::Wide, CacheType::EXIF, CacheType::WideAlternate, CacheType::CustomStream]
+        }
+    }
+}
+
+/// The kind of database, Windows Explorer keeps both thumbnail and icon caches using the same layout
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum DatabaseKind {
+    /// thumbcache_*.db files
+    #[default]
+    Thumbnail,
+    /// iconcache_*.db files
+    IconCache
+}
+
+impl DatabaseKind {
+    /// The prefix of the file names used for this kind of database
+    pub fn file_prefix(&self) -> &'static str {
+        match self {
+            DatabaseKind::Thumbnail => "thumbcache_",
+            DatabaseKind::IconCache => "iconcache_"
+        }
+    }
+
+    /// Determines the kind of database from its file name
+    #[cfg(feature = "std")]
+    pub fn from_path(file: impl AsRef<Path>) -> Option<DatabaseKind> {
+        let file_name = file.as_ref().file_name()?.to_string_lossy().to_lowercase();
+        [DatabaseKind::Thumbnail, DatabaseKind::IconCache].into_iter().find(|kind| file_name.starts_with(kind.file_prefix()))
+    }
+}
+
 /// These errors can appear if you're trying to read a file that isn't a thumbnail cache database or if you're trying to read an invalid file
 #[derive(Error, Debug)]
 pub enum ThumbsError {
@@ -54,19 +364,66 @@ pub enum ThumbsError {
     UnexpectedString(String),
     #[error("Invalid string. Are you sure you opened the right file?")]
     InvalidCheckString,
+    #[error("Expected IMMM, got {0}. Are you sure you opened the index file?")]
+    UnexpectedIndexString(String),
+    #[error("An error occurred while serializing the report : {0}")]
+    SerializationError(String),
+    #[error("An error occurred while decoding or encoding the image : {0}")]
+    ImageError(String),
+    #[error("An error occurred while writing the SQLite database : {0}")]
+    DatabaseError(String),
+    #[error("Unknown format version {version}, the layout of a known Windows version can be forced with assume_version()")]
+    UnknownVersion { version: u32 },
+    #[error("The offsets of the database header point outside of the entries : {0:?}")]
+    InvalidHeader(HeaderValidation),
+    #[error("{0:?} databases are not used by {1:?}")]
+    UnsupportedCacheType(CacheType, WindowsVersion),
+    #[error("The entry at offset {offset} is cut short by the end of the file, {needed} more bytes are needed")]
+    TruncatedEntry { offset: u64, needed: u64 },
+    #[error("Expected an entry starting with CMMM at offset {offset}")]
+    BadEntrySignature { offset: u64 },
+    #[error("The entry at offset {offset} claims {data_size} bytes of data, more than the limit of {max} bytes")]
+    DataTooLarge { offset: u64, data_size: u32, max: u32 },
+    #[error("The sizes in the header of the entry at offset {offset} don't add up")]
+    MalformedEntry { offset: u64 },
+    #[error("Reading at offset {offset} goes past the end of the file, {needed} more bytes are needed")]
+    Truncated { offset: u64, needed: u64 },
+    #[error("The checksum of entry {entry} doesn't match, expected {expected:016x} but computed {actual:016x}")]
+    ChecksumMismatch { entry: usize, expected: u64, actual: u64 },
+    #[error("There is no entry at position {index}")]
+    EntryNotFound { index: usize },
+    #[error("No entry has the entry hash {entry_hash:016x}")]
+    EntryHashNotFound { entry_hash: u64 },
     #[error("An error occurred while trying to write a cache entry into a file or while trying to fill up a buffer while parsing.")]
+    #[cfg(feature = "std")]
     IoError(std::io::Error)
 }
 
-// Converts a slice into a slice with fixed length because some functions like to bitch about it.
-// Thanks @malbarbo from Stackoverflow.
-fn clone_into_array<A, T>(slice: &[T]) -> A
-    where A: Sized + Default + AsMut<[T]>,
-          T: Clone
-{
-    let mut a = Default::default();
-    <A as AsMut<[T]>>::as_mut(&mut a).clone_from_slice(slice);
-    a
+// The bytes of a database, either read into memory or mapped from the file
+#[cfg(feature = "std")]
+#[derive(Clone)]
+enum Backing {
+    Owned(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mapped(std::sync::Arc<memmap2::Mmap>)
+}
+
+#[cfg(feature = "std")]
+impl AsRef<[u8]> for Backing {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            Backing::Owned(bytes) => bytes,
+            #[cfg(feature = "mmap")]
+            Backing::Mapped(map) => map
+        }
+    }
+}
+
+// The data of a cache entry within the bytes of its database, None if it lies outside of them
+#[cfg(feature = "std")]
+pub(crate) fn data_in<'a>(bytes: &'a [u8], entry: &CacheEntry) -> Option<&'a [u8]> {
+    let start = usize::try_from(entry.data_offset).ok()?;
+    bytes.get(start..start.checked_add(usize::try_from(entry.data_size).ok()?)?)
 }
 
 /// Thumbscache
@@ -81,17 +438,35 @@ fn clone_into_array<A, T>(slice: &[T]) -> A
 /// ```
 /// 
 /// The windows version and cache type stays None unless database gets parsed using the .read() function.
+#[cfg(feature = "std")]
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Thumbscache {
-    stream: Cursor<Vec<u8>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    stream: Cursor<Backing>,
     pub windows_version: Option<WindowsVersion>,
+    #[deprecated(since = "1.1.0", note = "use entries(), entries_mut() or into_entries() instead")]
+    #[cfg_attr(feature = "serde", serde(rename = "entries"))]
     pub cache_entires: Vec<CacheEntry>,
-    pub cache_type: Option<CacheType>
+    pub cache_type: Option<CacheType>,
+    /// Whether the database holds thumbnails or icons, detected from the file name when opened from a path
+    pub kind: DatabaseKind,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    modified: Option<SystemTime>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    created: Option<SystemTime>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    assumed_version: Option<WindowsVersion>,
+    // The file the database was opened from, read again by refresh()
+    #[cfg(feature = "fs")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    path: Option<std::path::PathBuf>
 }
 
-impl std::fmt::Debug for Thumbscache {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Thumbscache").field("Windows version", &self.windows_version).field("Number of cache entries", &self.cache_entires.len()).field("Cache type", &self.cache_type).finish()
+#[cfg(feature = "std")]
+impl core::fmt::Debug for Thumbscache {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Thumbscache").field("Windows version", &self.windows_version).field("Number of cache entries", &self.len()).field("Cache type", &self.cache_type).field("Kind", &self.kind).finish()
     }
 }
 
@@ -99,287 +474,843 @@ impl std::fmt::Debug for Thumbscache {
 /// Additional parsing is neccessary using the .read() function.
 /// 
 /// Returns an error if you specify an invalid file path
+#[cfg(feature = "fs")]
 pub fn open_thumbscache(file: String) -> Result<Thumbscache, ThumbsError> {
-    let mut bytes: Vec<u8> = Vec::new();
-    if let Ok(mut opened_file) = std::fs::OpenOptions::new().read(true).open(file) {
-        opened_file.read_to_end(&mut bytes).map_err(|x| {ThumbsError::IoError(x)})?;
-        return Ok(Thumbscache {
-            stream: Cursor::new(bytes),
-            windows_version: None,
-            cache_entires: Vec::new(),
-            cache_type: None
-        });
+    Thumbscache::from_path(file)
+}
+
+/// The image format of the data stored in a cache entry
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ImageFormat {
+    Bmp,
+    Jpeg,
+    Png,
+    Unknown
+}
+
+impl ImageFormat {
+    /// Detects the image format of the bytes by looking at their magic number
+    pub fn detect(data: &[u8]) -> ImageFormat {
+        if data.starts_with(b"BM") {
+            ImageFormat::Bmp
+        }else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            ImageFormat::Jpeg
+        }else if data.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+            ImageFormat::Png
+        }else {
+            ImageFormat::Unknown
+        }
+    }
+
+    /// The file extension used for this format, without the leading dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Bmp => "bmp",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Png => "png",
+            ImageFormat::Unknown => "bin"
+        }
+    }
+
+    /// The MIME type of this format, application/octet-stream when it is unknown
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Bmp => "image/bmp",
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Png => "image/png",
+            ImageFormat::Unknown => "application/octet-stream"
+        }
+    }
+}
+
+/// What the data of a cache entry holds, see `CacheEntry::payload_kind()`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub enum PayloadKind {
+    /// A thumbnail in a known image format
+    Image(ImageFormat),
+    /// EXIF metadata, stored by the EXIF cache (see `CacheEntry::exif()`)
+    Exif,
+    /// A custom stream of the Windows 10 CustomStream cache, whose format depends on the application that wrote it
+    Stream,
+    /// Data of an unknown format
+    Unknown
+}
+
+impl PayloadKind {
+    /// Classifies data stored in a database of the cache type, by looking at its magic number
+    pub fn detect(data: &[u8], cache_type: Option<CacheType>) -> PayloadKind {
+        match ImageFormat::detect(data) {
+            ImageFormat::Unknown if data.starts_with(b"Exif\0\0") || (cache_type == Some(CacheType::EXIF) && ExifData::parse(data).is_some()) => PayloadKind::Exif,
+            ImageFormat::Unknown if cache_type == Some(CacheType::CustomStream) => PayloadKind::Stream,
+            ImageFormat::Unknown => PayloadKind::Unknown,
+            format => PayloadKind::Image(format)
+        }
+    }
+
+    /// The file extension used for this kind of data, without the leading dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PayloadKind::Image(format) => format.extension(),
+            PayloadKind::Exif => "exif",
+            PayloadKind::Stream => "stream",
+            PayloadKind::Unknown => "bin"
+        }
+    }
+}
+
+// Writes the bytes into a file, replacing its contents
+#[cfg(feature = "fs")]
+fn write_data(file_path: impl AsRef<Path>, data: &[u8]) -> Result<(), ThumbsError> {
+    use std::io::Write;
+    let mut file: std::fs::File;
+    if let Ok(opened_file) = std::fs::OpenOptions::new().create(true).write(true).truncate(true).open(file_path) {
+        file = opened_file;
+    }else {
+        return Err(ThumbsError::IoError(std::io::ErrorKind::InvalidInput.into()));
+    }
+    if file.write_all(data).is_ok() {
+        Ok(())
     }else {
-        return Err(ThumbsError::InvalidFile);
+        Err(ThumbsError::IoError(std::io::ErrorKind::InvalidData.into()))
+    }
+}
+
+/// Entry header fields that only exist in some Windows versions
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub enum VersionFields {
+    /// Windows Vista stores the file extension of the cached item, as up to 4 UTF-16 characters
+    Vista {
+        file_extension: String
+    },
+    Win7,
+    /// Windows 8 and later store the dimensions of the thumbnail, they are 0 when unknown, and flags
+    #[non_exhaustive]
+    Win8 {
+        width: u32,
+        height: u32,
+        flags: EntryFlags
     }
-    
+}
+
+/// Where a cache entry was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub enum EntryOrigin {
+    /// Part of the chain of entries Windows still references
+    #[default]
+    Live,
+    /// Found by scanning for entry signatures, see `Thumbscache::carve()` and `Thumbscache::read_lossy()`
+    Carved,
+    /// Left past the live entries, see `Thumbscache::recover_unreferenced()`
+    Unallocated
+}
+
+/// Options for `Thumbscache::read_with_options()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadOptions {
+    /// Leaves out the entries without data, which Windows keeps as placeholders
+    pub skip_empty: bool,
+    /// Stops after this many entries were added
+    pub max_entries: Option<u32>,
+    /// Copies the data of every entry. When disabled the data is left empty,
+    /// it can be fetched later on with `CacheEntry::load_data()` or `Thumbscache::entry_data()`
+    pub load_data: bool,
+    /// Entries claiming more data than this are rejected with `ThumbsError::DataTooLarge`, so a corrupted size can't cause a huge allocation.
+    ///
+    /// Defaults to 64 MiB, well above the largest thumbnail Windows stores.
+    pub max_data_size: u32,
+    /// Skips damaged entries instead of failing, parsing resumes at the next entry signature followed by a valid header.
+    ///
+    /// The entries found after a damaged one are marked as `EntryOrigin::Carved`. Disabled by default, see `Thumbscache::read_lossy()` to also get the problems found.
+    pub resync: bool
+}
 
+impl Default for ReadOptions {
+    fn default() -> Self {
+        ReadOptions {
+            skip_empty: false,
+            max_entries: None,
+            load_data: true,
+            max_data_size: 64 * 1024 * 1024,
+            resync: false
+        }
+    }
 }
 
 /// Cache entry
 /// 
 /// This struct represents a file in the thumbscache database. 
-/// It includes the file extension of the file (only applicable for Windows Vista), the size of the data, the identifier string for it and the data itself, in .bmp format (unless stated otherwise in the file_extension field)
+/// It includes the fields specific to the Windows version (see VersionFields), the size of the data, the identifier string for it and the data itself, usually in .bmp, .jpg or .png format (see detect_format())
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct CacheEntry {
+    offset: u64,
     size: u32,
-    pub file_extension: Option<String>,
+    entry_hash: u64,
+    pub version_fields: VersionFields,
     identifier_string_size: u32,
     padding_size: u32,
     pub data_size: u32,
     data_checksum: u64,
     header_checksum: u64,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    header: Vec<u8>,
+    data_offset: u64,
     pub identifier_string: String,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    identifier: Vec<u8>,
+    /// Where the entry was found
+    pub origin: EntryOrigin,
+    /// The cache type of the database the entry was read from
+    pub cache_type: Option<CacheType>,
+    /// Left out when serialized, see `Thumbscache::to_json_report()` to include it
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub data: Vec<u8>
 }
 
 impl CacheEntry {
-    /// Writes the contents of the cache entry into a file.
-    /// The file path defaults to the current directory unless stated otherwise.
-    pub fn write_to_file(&self, file_path: Option<String>) -> Result<(), ThumbsError> {
-        let mut file: File;
-        if let Some(file_path) = file_path {
-            if let Ok(opened_file) = OpenOptions::new().create(true).write(true).open(file_path) {
-                file = opened_file;
-            }else {
-                return Err(ThumbsError::IoError(std::io::ErrorKind::InvalidInput.into())); 
-            }
-        }else {
-            if let Ok(opened_file) = OpenOptions::new().create(true).write(true).open(format!("./{}.bmp",self.identifier_string)) {
-                file = opened_file;
-            }else {
-                return Err(ThumbsError::IoError(std::io::ErrorKind::InvalidInput.into()));   
-            }
-        }
-        if let Ok(_) = file.write_all(&self.data) {
-            Ok(())
-        }else {
-            Err(ThumbsError::IoError(std::io::ErrorKind::InvalidData.into()))
+    /// Detects the image format of the data by looking at its magic number
+    pub fn detect_format(&self) -> ImageFormat {
+        ImageFormat::detect(&self.data)
+    }
+
+    /// Classifies the data using the cache type of its database, telling thumbnails apart from EXIF metadata and custom streams
+    pub fn payload_kind(&self) -> PayloadKind {
+        PayloadKind::detect(&self.data, self.cache_type)
+    }
+
+    /// The 64-bit hash identifying the cached item, as stored in the entry header.
+    ///
+    /// This is the value the index file and the ThumbnailCacheId column of Windows.edb refer to.
+    pub fn entry_hash(&self) -> u64 {
+        self.entry_hash
+    }
+
+    /// The file extension of the cached item, only stored by Windows Vista
+    pub fn file_extension(&self) -> Option<&str> {
+        match &self.version_fields {
+            VersionFields::Vista { file_extension } => Some(file_extension),
+            _ => None
         }
     }
+
+    /// The entry hash as 16 lowercase hexadecimal digits, the way it appears in identifier strings
+    pub fn entry_hash_hex(&self) -> String {
+        format_entry_hash(self.entry_hash, false)
+    }
+
+    /// The byte offset the entry was found at, from the start of the database
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The raw bytes of the identifier string, before UTF-16 decoding
+    pub fn identifier_bytes(&self) -> &[u8] {
+        &self.identifier
+    }
+
+    /// The raw bytes of the entry header, starting with the CMMM signature and ending with the header checksum
+    pub fn raw_header(&self) -> &[u8] {
+        &self.header
+    }
+
+    /// The byte offset of the data, from the start of the database
+    pub fn data_offset(&self) -> u64 {
+        self.data_offset
+    }
+
+    /// The bytes of the database the whole entry spans, from its signature to the start of the next entry.
+    ///
+    /// See `Thumbscache::raw_record()` to borrow them.
+    pub fn record_range(&self) -> core::ops::Range<u64> {
+        self.offset..self.offset + self.size as u64
+    }
 } 
 
+#[cfg(feature = "std")]
 impl Thumbscache {
+    fn from_backing(backing: Backing) -> Thumbscache {
+        Thumbscache {
+            stream: Cursor::new(backing),
+            windows_version: None,
+            #[allow(deprecated)]
+            cache_entires: Vec::new(),
+            cache_type: None,
+            kind: DatabaseKind::Thumbnail,
+            modified: None,
+            created: None,
+            assumed_version: None,
+            #[cfg(feature = "fs")]
+            path: None
+        }
+    }
+
+    /// Opens the thumbscache database found at the path and reads it to a struct.
+    /// Additional parsing is neccessary using the .read() function.
+    ///
+    /// Returns an error if you specify an invalid file path
+    #[cfg(feature = "fs")]
+    pub fn from_path(file: impl AsRef<Path>) -> Result<Thumbscache, ThumbsError> {
+        event!(debug, path = %file.as_ref().display(), "opening database");
+        if let Ok(opened_file) = std::fs::OpenOptions::new().read(true).open(&file) {
+            let metadata = opened_file.metadata().ok();
+            let mut database = Thumbscache::from_reader(opened_file)?;
+            database.kind = DatabaseKind::from_path(&file).unwrap_or_default();
+            database.modified = metadata.as_ref().and_then(|x| x.modified().ok());
+            database.created = metadata.as_ref().and_then(|x| x.created().ok());
+            database.path = Some(file.as_ref().to_path_buf());
+            Ok(database)
+        }else {
+            Err(ThumbsError::InvalidFile)
+        }
+    }
+
+    /// Takes the bytes of a whole database, without copying them.
+    /// Additional parsing is neccessary using the .read() function.
+    pub fn from_bytes(bytes: Vec<u8>) -> Thumbscache {
+        Thumbscache::from_backing(Backing::Owned(bytes))
+    }
+
+    /// Reads the whole database from any seekable byte source, starting at its beginning.
+    /// Additional parsing is neccessary using the .read() function.
+    pub fn from_reader(mut reader: impl Read + Seek) -> Result<Thumbscache, ThumbsError> {
+        let mut bytes: Vec<u8> = Vec::new();
+        reader.seek(SeekFrom::Start(0)).map_err(ThumbsError::IoError)?;
+        reader.read_to_end(&mut bytes).map_err(ThumbsError::IoError)?;
+        Ok(Thumbscache::from_backing(Backing::Owned(bytes)))
+    }
+
+    /// Returns the parsed cache entries, in file order
+    #[allow(deprecated)]
+    pub fn entries(&self) -> &[CacheEntry] {
+        &self.cache_entires
+    }
+
+    /// Returns the parsed cache entries, allowing to modify them
+    #[allow(deprecated)]
+    pub fn entries_mut(&mut self) -> &mut [CacheEntry] {
+        &mut self.cache_entires
+    }
+
+    /// Consumes the database, returning its parsed cache entries
+    #[allow(deprecated)]
+    pub fn into_entries(self) -> Vec<CacheEntry> {
+        self.cache_entires
+    }
+
+    /// The number of parsed cache entries
+    pub fn len(&self) -> usize {
+        self.entries().len()
+    }
+
+    /// Returns true if no cache entries were parsed
+    pub fn is_empty(&self) -> bool {
+        self.entries().is_empty()
+    }
+
+    // The list of entries, for the parsers adding or removing entries
+    #[allow(deprecated)]
+    pub(crate) fn entry_list(&mut self) -> &mut Vec<CacheEntry> {
+        &mut self.cache_entires
+    }
+
+    /// Returns the raw bytes of the database
+    pub fn bytes(&self) -> &[u8] {
+        self.stream.get_ref().as_ref()
+    }
+
+    /// Returns the raw bytes of the database header, fewer than 24 when the database is shorter than that
+    pub fn raw_header(&self) -> &[u8] {
+        &self.bytes()[..self.bytes().len().min(24)]
+    }
+
+    /// Returns the bytes between the database header and the first entry, empty unless the first entry offset leaves a gap.
+    ///
+    /// Windows doesn't document them, they are kept for researchers comparing databases across Windows builds.
+    pub fn reserved_bytes(&self) -> &[u8] {
+        let first_entry = self.header().map(|x| x.first_entry_offset()).unwrap_or(24).min(self.bytes().len() as u64) as usize;
+        self.bytes().get(24..first_entry).unwrap_or_default()
+    }
+
+    /// Returns the data of a cache entry as a slice borrowed from the database, without copying it
+    ///
+    /// Returns None if the entry doesn't belong to this database.
+    pub fn entry_data(&self, entry: &CacheEntry) -> Option<&[u8]> {
+        data_in(self.bytes(), entry)
+    }
+
+    /// Returns the raw bytes of a whole entry record, header, identifier, padding, data and slack included, borrowed from the database
+    ///
+    /// Returns None if the entry doesn't belong to this database or is cut short by its end.
+    pub fn raw_record(&self, entry: &CacheEntry) -> Option<&[u8]> {
+        let range = entry.record_range();
+        self.bytes().get(range.start as usize..range.end as usize)
+    }
+
+    /// Returns a streaming reader over the database, useful to enumerate the entry headers without copying the data
+    pub fn reader(&self) -> Result<ThumbscacheReader<Cursor<&[u8]>>, ThumbsError> {
+        ThumbscacheReader::new(Cursor::new(self.bytes()))
+    }
+
+    /// Parses the header of the database, without reading any entries.
+    ///
+    /// The Windows version set with .assume_version() replaces the one of the header.
+    pub fn header(&self) -> Result<CacheHeader, ThumbsError> {
+        let mut header = parse_header(self.bytes())?;
+        if let Some(version) = self.assumed_version {
+            header.assume_version(version);
+        }
+        Ok(header)
+    }
+
+    /// Checks where the offsets of the database header point to, see `CacheHeader::validate()`
+    pub fn validate_header(&self) -> Result<HeaderValidation, ThumbsError> {
+        Ok(self.header()?.validate(self.bytes().len() as u64))
+    }
+
+    /// Parses the entries with the layout of this Windows version whatever the format version of the header says.
+    ///
+    /// Meant for pre-release Windows builds whose format version isn't known yet, which otherwise fail with ThumbsError::UnknownVersion.
+    pub fn assume_version(&mut self, version: WindowsVersion) {
+        self.assumed_version = Some(version);
+    }
+
     /// Determines the Windows version and the cache type
-    /// Reads all the cache entries and stores them into a list
+    /// Reads all the cache entries and stores them into a list, replacing the entries read before
+    ///
+    /// See ThumbscacheBuilder to configure strictness, checksum verification and carving.
     pub fn read(&mut self) -> Result<u32, ThumbsError> {
-        let mut read_bytes: [u8; 32] = [0; 32];
-        self.stream.read_exact(&mut read_bytes).map_err(|x| {ThumbsError::IoError(x)})?;
-        if let Ok(check_string) = std::str::from_utf8(&read_bytes[0..4]) {
-            if check_string != "CMMM" {
-                return Err(ThumbsError::UnexpectedString(check_string.to_string()));
+        self.read_with_progress(|_, _, _| true)
+    }
+
+    /// Reads all the cache entries like .read() does, reporting the progress after every entry.
+    ///
+    /// The callback receives the number of bytes processed, the size of the database and the number of entries read so far.
+    /// Returning false from it stops parsing, the entries read until then are kept.
+    pub fn read_with_progress(&mut self, progress: impl FnMut(u64, u64, u32) -> bool) -> Result<u32, ThumbsError> {
+        self.read_entries(ReadOptions::default(), progress)
+    }
+
+    /// Reads all the cache entries like .read() does, stopping after the current entry once the flag is set, possibly from another thread.
+    ///
+    /// Meant for user interfaces aborting the parse of a large database, the entries read until then are kept.
+    pub fn read_cancellable(&mut self, cancel: &AtomicBool) -> Result<u32, ThumbsError> {
+        self.read_entries(ReadOptions::default(), |_, _, _| !cancel.load(Ordering::Relaxed))
+    }
+
+    /// Reads the cache entries like .read() does, following the options
+    pub fn read_with_options(&mut self, options: ReadOptions) -> Result<u32, ThumbsError> {
+        self.read_entries(options, |_, _, _| true)
+    }
+
+    fn read_entries(&mut self, options: ReadOptions, progress: impl FnMut(u64, u64, u32) -> bool) -> Result<u32, ThumbsError> {
+        span!(debug_span, "read", kind = ?self.kind);
+        let header = self.header()?;
+        self.windows_version = header.windows_version;
+        self.cache_type = header.cache_type;
+        let bytes = self.stream.get_ref().as_ref();
+        #[allow(deprecated)]
+        {
+            // Reading again starts over, the entries of the previous read would be listed twice otherwise
+            self.cache_entires.clear();
+            parse::parse_entries(bytes, &header, header.first_entry_offset(), options, &mut self.cache_entires, progress)
+        }
+    }
+
+    /// Parses the entries without storing them, borrowing their identifier and data from the database instead of copying them.
+    ///
+    /// Meant for large databases that are only scanned once, the parsed entries of the struct are left untouched.
+    pub fn entry_refs(&self, options: ReadOptions) -> Result<Vec<CacheEntryRef<'_>>, ThumbsError> {
+        Ok(parse_database_ref(self.bytes(), options)?.1)
+    }
+
+    /// Parses the single entry starting at the offset without reading the others, see `parse_entry_at()`.
+    ///
+    /// Meant for viewers that only need one thumbnail, the parsed entries of the struct are left untouched.
+    pub fn entry_at_offset(&self, offset: u64) -> Result<CacheEntry, ThumbsError> {
+        Ok(parse_entry_at(self.bytes(), &self.header()?, offset)?.to_entry())
+    }
+
+    /// Walks the entry headers without copying identifiers or data, returning a summary of every entry.
+    ///
+    /// Meant for the triage of large databases, the parsed entries of the struct are left untouched.
+    pub fn scan_headers(&self) -> Result<Vec<EntrySummary>, ThumbsError> {
+        let header = self.header()?;
+        let mut entries = Vec::new();
+        parse::walk_entries(self.bytes(), &header, ReadOptions::default(), |entry, _| {
+            entries.push(entry.summary());
+            true
+        })?;
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a database with one entry per (hash, data) pair, versions and cache types are given as the numbers stored in the header
+    pub(crate) fn build_database(format_version: u32, cache_type: u32, entries: &[(u64, &[u8])]) -> Vec<u8> {
+        let version = WindowsVersion::from_format_version(format_version).unwrap();
+        let mut writer = ThumbscacheWriter::new(version, version.cache_types()[cache_type as usize]).unwrap();
+        for (hash, data) in entries {
+            writer.append(*hash, data);
+        }
+        writer.into_bytes()
+    }
+
+    /// Builds a Windows 8+ style index file, offsets are given as (cache type number, offset) pairs
+    #[cfg(feature = "std")]
+    pub(crate) fn build_index(format_version: u32, entries: &[(u64, &[(usize, u32)])]) -> Vec<u8> {
+        let cache_types = WindowsVersion::from_format_version(format_version).unwrap().cache_types().len();
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"IMMM");
+        bytes.extend_from_slice(&format_version.to_le_bytes());
+        bytes.extend_from_slice(&[0u8; 8]);
+        bytes.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        for (hash, offsets) in entries {
+            bytes.extend_from_slice(&hash.to_le_bytes());
+            bytes.extend_from_slice(&[0u8; 8]);
+            for i in 0..cache_types {
+                let offset = offsets.iter().find(|(x, _)| *x == i).map(|(_, offset)| *offset).unwrap_or(u32::MAX);
+                bytes.extend_from_slice(&offset.to_le_bytes());
             }
-        }else {
-            return Err(ThumbsError::InvalidCheckString);
         }
-        let format_version: u32 = u32::from_ne_bytes(clone_into_array(&read_bytes[4..8]));
-        
-        let cache_type: u32 = u32::from_ne_bytes(clone_into_array(&read_bytes[8..12]));
-        match format_version {
-            20 => {
-                self.windows_version = Some(WindowsVersion::WinVista);
-                match cache_type {
-                    0 => self.cache_type = Some(CacheType::Res32),
-                    1 => self.cache_type = Some(CacheType::Res96),
-                    2 => self.cache_type = Some(CacheType::Res256),
-                    3 => self.cache_type = Some(CacheType::Res1024),
-                    4 => self.cache_type = Some(CacheType::SR),
-                    _ => {}
-                }
-            },
-            21 => {
-                self.windows_version = Some(WindowsVersion::Win7);
-                match cache_type {
-                    0 => self.cache_type = Some(CacheType::Res32),
-                    1 => self.cache_type = Some(CacheType::Res96),
-                    2 => self.cache_type = Some(CacheType::Res256),
-                    3 => self.cache_type = Some(CacheType::Res1024),
-                    4 => self.cache_type = Some(CacheType::SR),
-                    _ => {}
-                }
-            },
-            30 => {
-                self.windows_version = Some(WindowsVersion::Win8);
-                match cache_type {
-                    0 => self.cache_type = Some(CacheType::Res16),
-                    1 => self.cache_type = Some(CacheType::Res32),
-                    2 => self.cache_type = Some(CacheType::Res48),
-                    3 => self.cache_type = Some(CacheType::Res96),
-                    4 => self.cache_type = Some(CacheType::Res256),
-                    5 => self.cache_type = Some(CacheType::Res1024),
-                    6 => self.cache_type = Some(CacheType::SR),
-                    7 => self.cache_type = Some(CacheType::Wide),
-                    8 => self.cache_type = Some(CacheType::EXIF),
-                    _ => {}
-                }
-            },
-            31 => {
-                self.windows_version = Some(WindowsVersion::Win81);
-                match cache_type {
-                    0 => self.cache_type = Some(CacheType::Res16),
-                    1 => self.cache_type = Some(CacheType::Res32),
-                    2 => self.cache_type = Some(CacheType::Res48),
-                    3 => self.cache_type = Some(CacheType::Res96),
-                    4 => self.cache_type = Some(CacheType::Res256),
-                    5 => self.cache_type = Some(CacheType::Res1024),
-                    6 => self.cache_type = Some(CacheType::Res1600),
-                    7 => self.cache_type = Some(CacheType::SR),
-                    8 => self.cache_type = Some(CacheType::Wide),
-                    9 => self.cache_type = Some(CacheType::EXIF),
-                    10 => self.cache_type = Some(CacheType::WideAlternate),
-                    _ => {}
-                }
-            },
-            32 => {
-                self.windows_version = Some(WindowsVersion::Win10);
-                match cache_type {
-                    0 => self.cache_type = Some(CacheType::Res16),
-                    1 => self.cache_type = Some(CacheType::Res32),
-                    2 => self.cache_type = Some(CacheType::Res48),
-                    3 => self.cache_type = Some(CacheType::Res96),
-                    4 => self.cache_type = Some(CacheType::Res256),
-                    5 => self.cache_type = Some(CacheType::Res768),
-                    6 => self.cache_type = Some(CacheType::Res1280),
-                    7 => self.cache_type = Some(CacheType::Res1920),
-                    8 => self.cache_type = Some(CacheType::Res2560),
-                    9 => self.cache_type = Some(CacheType::SR),
-                    10 => self.cache_type = Some(CacheType::Wide),
-                    11 => self.cache_type = Some(CacheType::EXIF),
-                    12 => self.cache_type = Some(CacheType::WideAlternate),
-                    13 => self.cache_type = Some(CacheType::CustomStream),
-                    _ => {}
-                }
-            },
-            _ => {}
+        bytes
+    }
+
+    /// Creates an empty scratch directory for tests that need files on disk
+    #[cfg(feature = "fs")]
+    pub(crate) fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("thumbscache_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // Runs the bytes through every public function reading untrusted input, the results don't matter as long as nothing panics
+    #[cfg(feature = "std")]
+    pub(crate) fn exercise(bytes: &[u8]) {
+        for options in [ReadOptions::default(), ReadOptions::lazy(), ReadOptions { skip_empty: true, max_entries: Some(2), ..ReadOptions::default() }] {
+            let _ = parse_database(bytes, options);
+            for entry in parse_database_ref(bytes, options).map(|x| x.1).unwrap_or_default() {
+                let _ = (entry.identifier_string(), entry.detect_format(), entry.summary(), entry.identifier());
+            }
+            let _ = scan_database_headers(bytes, options);
         }
-        let first_entry: u32 = u32::from_ne_bytes(clone_into_array(&read_bytes[12..16]));
-        let _first_available_entry: u32 = u32::from_ne_bytes(clone_into_array(&read_bytes[16..20]));
-        self.stream.set_position((24 + first_entry).into());
-        let mut temp_bytes: [u8; 56];
-        let mut padding_size: u32;
-        let mut added_entries = 0;
-        while self.stream.position() < self.stream.get_ref().len() as u64 {
-            temp_bytes = [0;56];
-            let _ = self.stream.read_exact(&mut temp_bytes);
-            if let Ok(check_string) = std::str::from_utf8(&temp_bytes[0..4]) {
-                if check_string != "CMMM" {
-                    break;
-                }
-                match self.windows_version {
-                    Some(version) => {
-                        match version {
-                            WindowsVersion::WinVista => {
-                                let size: u32 = u32::from_ne_bytes(clone_into_array(&temp_bytes[4..8]));
-                                let file_extension_vec_u16: Vec<u16> = temp_bytes[16..24].chunks_exact(2).into_iter().map(|a| u16::from_ne_bytes([a[0], a[1]])).collect();
-                                let file_extension: String = String::from_utf16_lossy(&file_extension_vec_u16);
-                                let identifier_string_size: u32 = u32::from_ne_bytes(clone_into_array(&temp_bytes[24..28]));
-                                padding_size = u32::from_ne_bytes(clone_into_array(&temp_bytes[28..32]));
-                                let data_size: u32 = u32::from_ne_bytes(clone_into_array(&temp_bytes[32..36]));
-                                let data_checksum: u64 = u64::from_ne_bytes(clone_into_array(&temp_bytes[40..48]));
-                                let header_checksum: u64 = u64::from_ne_bytes(clone_into_array(&temp_bytes[48..56]));
-                                let mut identifier_string_vec: Vec<u8> = Vec::with_capacity(identifier_string_size as usize);
-                                identifier_string_vec.extend_from_slice(&self.stream.get_ref()[self.stream.position() as usize..self.stream.position() as usize+identifier_string_size as usize]);
-                                let identifier_string_vec_u16: Vec<u16> = identifier_string_vec.chunks_exact(2).into_iter().map(|a| u16::from_ne_bytes([a[0], a[1]])).collect();
-                                let identifier_string: String = String::from_utf16_lossy(identifier_string_vec_u16.as_slice());
-                                self.stream.set_position(self.stream.position() + padding_size as u64);
-                                let mut data = vec![0u8; data_size.try_into().unwrap()];
-                                self.stream.read_exact(&mut data).map_err(|x| {ThumbsError::IoError(x)})?;
-                                // If we didn't read enough data then we skip to the next cache entry
-                                self.stream.set_position(self.stream.position() + (size-(56+data_size+identifier_string_size+padding_size)) as u64);
-                                let cache_entry = CacheEntry {
-                                    size,
-                                    file_extension: Some(file_extension),
-                                    identifier_string_size,
-                                    padding_size,
-                                    data_size,
-                                    data_checksum,
-                                    header_checksum,
-                                    identifier_string,
-                                    data
-                                };
-                                self.cache_entires.push(cache_entry);      
-                                added_entries = added_entries + 1;
-                            },
-                            WindowsVersion::Win7 => {
-                                let size: u32 = u32::from_ne_bytes(clone_into_array(&temp_bytes[4..8]));
-                                let identifier_string_size: u32 = u32::from_ne_bytes(clone_into_array(&temp_bytes[16..20]));
-                                padding_size = u32::from_ne_bytes(clone_into_array(&temp_bytes[20..24]));
-                                let data_size: u32 = u32::from_ne_bytes(clone_into_array(&temp_bytes[24..28]));
-                                let data_checksum: u64 = u64::from_ne_bytes(clone_into_array(&temp_bytes[32..40]));
-                                let header_checksum: u64 = u64::from_ne_bytes(clone_into_array(&temp_bytes[40..48]));
-                                let mut identifier_string_vec: Vec<u8> = vec![0u8; identifier_string_size.try_into().unwrap()];
-                                let _ = self.stream.read_exact(&mut identifier_string_vec);
-                                let identifier_string_vec_u16: Vec<u16> = identifier_string_vec.chunks_exact(2).into_iter().map(|a| u16::from_ne_bytes([a[0], a[1]])).collect();
-                                let identifier_string: String = String::from_utf16_lossy(identifier_string_vec_u16.as_slice());
-                                self.stream.set_position(self.stream.position() + padding_size as u64);
-                                let mut data = vec![0u8; data_size.try_into().unwrap()];
-                                self.stream.read_exact(&mut data).map_err(|x| {ThumbsError::IoError(x)})?;
-                                // If we didn't read enough data then we skip to the next cache entry
-                                self.stream.set_position(self.stream.position() + (size-(56+data_size+identifier_string_size+padding_size)) as u64);
-                                let cache_entry = CacheEntry {
-                                    size,
-                                    file_extension: None,
-                                    identifier_string_size,
-                                    padding_size,
-                                    data_size,
-                                    data_checksum,
-                                    header_checksum,
-                                    identifier_string,
-                                    data
-                                };
-                                
-                                self.cache_entires.push(cache_entry);      
-                                added_entries = added_entries + 1;
-                            },
-                            _ => {
-                                let size: u32 = u32::from_ne_bytes(clone_into_array(&temp_bytes[4..8]));
-                                let identifier_string_size: u32 = u32::from_ne_bytes(clone_into_array(&temp_bytes[16..20]));
-                                padding_size = u32::from_ne_bytes(clone_into_array(&temp_bytes[20..24]));
-                                let data_size: u32 = u32::from_ne_bytes(clone_into_array(&temp_bytes[24..28]));
-                                let data_checksum: u64 = u64::from_ne_bytes(clone_into_array(&temp_bytes[40..48]));
-                                let header_checksum: u64 = u64::from_ne_bytes(clone_into_array(&temp_bytes[48..56]));
-                                let mut identifier_string_vec: Vec<u8> = vec![0u8; identifier_string_size.try_into().unwrap()];
-                                let _ = self.stream.read_exact(&mut identifier_string_vec);
-                                let identifier_string_vec_u16: Vec<u16> = identifier_string_vec.chunks_exact(2).into_iter().map(|a| u16::from_ne_bytes([a[0], a[1]])).collect();
-                                let identifier_string: String = String::from_utf16_lossy(identifier_string_vec_u16.as_slice());
-                                self.stream.set_position(self.stream.position() + padding_size as u64);
-                                let mut data = vec![0u8; data_size.try_into().unwrap()];
-                                self.stream.read_exact(&mut data).map_err(|x| {ThumbsError::IoError(x)})?;
-                                // If we didn't read enough data then we skip to the next cache entry
-                                self.stream.set_position(self.stream.position() + (size-(56+data_size+identifier_string_size+padding_size)) as u64);
-                                let cache_entry = CacheEntry {
-                                    size,
-                                    file_extension: None,
-                                    identifier_string_size,
-                                    padding_size,
-                                    data_size,
-                                    data_checksum,
-                                    header_checksum,
-                                    identifier_string,
-                                    data
-                                };
-                                
-                                self.cache_entires.push(cache_entry);      
-                                added_entries = added_entries + 1;
-                            }
-                        }
-                    },
-                    None => {
-                        
+        if let Ok(header) = parse_header(bytes) {
+            let _ = header.validate(bytes.len() as u64);
+            for offset in [0, 24, header.first_entry_offset(), header.first_available_entry as u64, bytes.len() as u64, u64::MAX] {
+                let _ = parse_entry_at(bytes, &header, offset);
+            }
+        }
+        let _ = ThumbscacheRef::new(bytes).read();
+        let _ = ThumbcacheIndex::parse(bytes);
+        let _ = ExifData::parse(bytes);
+        if let Ok(mut reader) = ThumbscacheReader::new(std::io::Cursor::new(bytes)) {
+            let headers: Vec<EntryHeader> = reader.entries().take(64).filter_map(|x| x.ok()).collect();
+            for header in headers {
+                let _ = reader.read_entry(&header);
+            }
+        }
+        let mut database = Thumbscache::from_bytes(bytes.to_vec());
+        let _ = database.read();
+        let _ = (database.validate_header(), database.consistency_check(), database.scan_headers(), database.verify_all(), database.stats(), database.to_csv_report());
+        let _ = (database.dimension_mismatches(), database.ordering_report(), database.entry_at_offset(24));
+        for entry in database.iter() {
+            let _ = (entry.identifier(), entry.dimensions(), entry.raw_pixel_dimensions(), entry.reconstructed_bmp(), entry.exif(), entry.fits_cache_type());
+            let _ = (database.raw_record(entry), database.entry_data(entry));
+        }
+        let _ = database.carve();
+        let _ = database.recover_unreferenced();
+        let mut lossy = Thumbscache::from_bytes(bytes.to_vec());
+        let _ = lossy.read_lossy();
+        let _ = Thumbscache::from_bytes(bytes.to_vec()).carve_headerless();
+        let _ = ThumbscacheBuilder::new().strictness(Strictness::Lossy).carve(true).header_policy(HeaderPolicy::SkipAndCarve).load_data(false).read(&mut Thumbscache::from_bytes(bytes.to_vec()));
+    }
+
+    // A xorshift generator, deterministic so that a failure can be reproduced
+    #[cfg(feature = "std")]
+    pub(crate) fn mutations(seed: &[u8], count: usize) -> impl Iterator<Item = Vec<u8>> + '_ {
+        let mut state = 0x9E37_79B9_7F4A_7C15u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        (0..count).map(move |_| {
+            let mut bytes = seed.to_vec();
+            for _ in 0..1 + next() % 4 {
+                let offset = (next() % bytes.len().max(1) as u64) as usize;
+                match next() % 4 {
+                    // A field set to an extreme value, sizes and offsets are where parsers go wrong
+                    0 => {
+                        let value = [0, 1, u32::MAX, u32::MAX - 3, bytes.len() as u32, i32::MAX as u32][(next() % 6) as usize];
+                        let end = (offset + 4).min(bytes.len());
+                        bytes[offset..end].copy_from_slice(&value.to_le_bytes()[..end - offset]);
                     },
+                    1 => bytes.truncate(offset),
+                    2 => bytes.extend_from_within(offset..),
+                    _ => if let Some(byte) = bytes.get_mut(offset) {
+                        *byte ^= next() as u8;
+                    }
                 }
-                
+            }
+            bytes
+        })
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn never_panics_on_damaged_databases() {
+        let pixels = vec![7u8; 16 * 16 * 4];
+        for (format_version, cache_type) in [(20, 0), (21, 1), (30, 2), (31, 0), (32, 0), (32, 11)] {
+            let seed = build_database(format_version, cache_type, &[(1, b"BM\x46\0\0\0first"), (2, b""), (3, b"\xFF\xD8\xFF\xE1\0\x10Exif\0\0II*\0"), (4, &pixels)]);
+            for bytes in mutations(&seed, 1500) {
+                exercise(&bytes);
             }
         }
-        Ok(added_entries)
+        for bytes in mutations(&build_index(32, &[(1, &[(0, 24)]), (2, &[(3, 96)])]), 500) {
+            exercise(&bytes);
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[cfg(feature = "std")]
+    #[test]
+    fn never_panics_on_the_fuzz_seeds() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/seeds/parse_database");
+        let seeds: Vec<Vec<u8>> = std::fs::read_dir(dir).unwrap().map(|x| std::fs::read(x.unwrap().path()).unwrap()).collect();
+        assert!(!seeds.is_empty());
+        for seed in &seeds {
+            exercise(seed);
+            for bytes in mutations(seed, 200) {
+                exercise(&bytes);
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn reads_every_cache_type_of_every_format_version() {
+        for (format_version, version) in FORMAT_VERSIONS {
+            assert_eq!(version.format_version(), format_version);
+            for (number, cache_type) in version.cache_types().iter().enumerate() {
+                let mut a = Thumbscache::from_bytes(build_database(format_version, number as u32, &[(1, b"first"), (2, b"second")]));
+                assert_eq!(a.read().unwrap(), 2, "{:?} {:?}", version, cache_type);
+                assert_eq!((a.windows_version, a.cache_type), (Some(version), Some(*cache_type)));
+                assert_eq!(a.entries()[1].data, b"second");
+                assert_eq!(CacheType::from_filename(&format!("iconcache_{}.db", cache_type.name())), Some(*cache_type));
+                if let Some(resolution) = cache_type.max_dimension() {
+                    assert_eq!(CacheType::from_filename(&format!("thumbcache_{}.db", resolution)), Some(*cache_type));
+                }
+            }
+        }
+        assert_eq!(WindowsVersion::from_format_version(33), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn exposes_raw_entry_bytes() {
+        let bytes = build_database(21, 1, &[(1, b"first"), (2, b"second")]);
+        let mut a = Thumbscache::from_bytes(bytes.clone());
+        a.read().unwrap();
+        let entry = &a.entries()[1];
+        assert_eq!(entry.raw_header().len(), 48);
+        assert_eq!(&entry.raw_header()[..4], b"CMMM");
+        let record = a.raw_record(entry).unwrap();
+        assert_eq!(record.len(), entry.size as usize);
+        assert_eq!(&record[..48], entry.raw_header());
+        assert_eq!(&bytes[entry.data_offset() as usize..][..6], b"second");
+        assert_eq!(entry.record_range().end, bytes.len() as u64);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn unknown_versions_fail_unless_a_layout_is_assumed() {
+        let mut bytes = build_database(32, 5, &[(1, b"first"), (2, b"second")]);
+        // A pre-release build writing a newer format version
+        bytes[4..8].copy_from_slice(&40u32.to_le_bytes());
+        let mut a = Thumbscache::from_bytes(bytes.clone());
+        assert!(matches!(a.read(), Err(ThumbsError::UnknownVersion { version: 40 })));
+        assert!(matches!(a.read_lossy(), Err(ThumbsError::UnknownVersion { version: 40 })));
+        assert!(matches!(parse_database(&bytes, ReadOptions::default()), Err(ThumbsError::UnknownVersion { version: 40 })));
+        a.assume_version(WindowsVersion::Win10);
+        assert_eq!(a.read().unwrap(), 2);
+        assert_eq!((a.windows_version, a.cache_type), (Some(WindowsVersion::Win10), Some(CacheType::Res768)));
+        assert_eq!(a.entries()[1].data, b"second");
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn reads_win10_entries() {
+        let dir = temp_dir("read_win10");
+        let path = dir.join("thumbcache_256.db");
+        std::fs::write(&path, build_database(32, 4, &[(0x1122334455667788, b"BMdata")])).unwrap();
+        let mut a = open_thumbscache(path.to_string_lossy().into_owned()).unwrap();
+        assert_eq!(a.read().unwrap(), 1);
+        assert_eq!(a.windows_version, Some(WindowsVersion::Win10));
+        assert_eq!(a.cache_type, Some(CacheType::Res256));
+        assert_eq!(a.entries()[0].identifier_string, "1122334455667788");
+        assert_eq!(a.entries()[0].entry_hash(), 0x1122334455667788);
+        assert_eq!(a.entries()[0].entry_hash_hex(), a.entries()[0].identifier_string);
+        assert_eq!(a.entries()[0].identifier_bytes().len(), 32);
+        assert_eq!(a.entries()[0].data, b"BMdata");
+        assert_eq!(a.entries()[0].version_fields, VersionFields::Win8 { width: 0, height: 0, flags: EntryFlags::empty() });
+        assert_eq!(a.entries()[0].file_extension(), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn reads_from_in_memory_buffer() {
+        let mut a = Thumbscache::from_reader(Cursor::new(build_database(31, 6, &[(7, b"data")]))).unwrap();
+        assert_eq!(a.read().unwrap(), 1);
+        assert_eq!(a.windows_version, Some(WindowsVersion::Win81));
+        assert_eq!(a.cache_type, Some(CacheType::Res1600));
+        // The deprecated field stays in sync with the accessors until it's removed
+        #[allow(deprecated)]
+        let deprecated_len = a.cache_entires.len();
+        assert_eq!(deprecated_len, a.len());
+        assert_eq!(a.entries()[0].data, b"data");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn reports_where_parsing_failed() {
+        let mut bytes = build_database(32, 1, &[(1, b"first"), (2, b"second")]);
+        let second_entry = 24 + 56 + 32 + 5;
+        bytes[second_entry..second_entry + 4].copy_from_slice(b"XXXX");
+        let mut a = Thumbscache::from_reader(Cursor::new(bytes)).unwrap();
+        assert!(matches!(a.read(), Err(ThumbsError::BadEntrySignature { offset }) if offset == second_entry as u64));
+
+        // The header points past the end of the file, so the entry is known to be incomplete
+        let mut bytes = build_database(32, 1, &[(1, b"first")]);
+        bytes.truncate(bytes.len() - 3);
+        let len = bytes.len() as u32;
+        bytes[16..20].copy_from_slice(&len.to_le_bytes());
+        let mut a = Thumbscache::from_reader(Cursor::new(bytes)).unwrap();
+        assert!(matches!(a.read(), Err(ThumbsError::TruncatedEntry { offset: 24, needed: 3 })));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn reports_progress_and_stops_early() {
+        let bytes = build_database(32, 1, &[(1, b"first"), (2, b"second"), (3, b"third")]);
+        let len = bytes.len() as u64;
+        let mut a = Thumbscache::from_reader(Cursor::new(bytes)).unwrap();
+        let mut calls = Vec::new();
+        assert_eq!(a.read_with_progress(|processed, total, entries| {
+            calls.push((processed, total, entries));
+            entries < 2
+        }).unwrap(), 2);
+        assert_eq!(calls, [(24 + 56 + 32 + 5, len, 1), (24 + 2 * (56 + 32) + 11, len, 2)]);
+        assert_eq!(a.len(), 2);
+
+        // A cancelled read keeps the entry it was parsing
+        let mut b = Thumbscache::from_bytes(a.bytes().to_vec());
+        assert_eq!(b.read_cancellable(&AtomicBool::new(true)).unwrap(), 1);
+        assert_eq!(b.len(), 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn reads_metadata_only() {
+        let bytes = build_database(32, 1, &[(1, b"first"), (2, b""), (3, b"third"), (4, b"fourth")]);
+        let mut a = Thumbscache::from_reader(Cursor::new(bytes)).unwrap();
+        assert_eq!(a.read_with_options(ReadOptions { skip_empty: true, max_entries: Some(2), load_data: false, ..ReadOptions::default() }).unwrap(), 2);
+        assert_eq!(a.entries()[1].entry_hash(), 3);
+        assert!(a.entries()[1].data.is_empty());
+        assert_eq!(a.entry_data(&a.entries()[1]), Some(&b"third"[..]));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn reading_again_replaces_the_entries() {
+        let mut a = Thumbscache::from_bytes(build_database(32, 1, &[(1, b"first"), (2, b"second")]));
+        assert_eq!(a.read().unwrap(), 2);
+        assert_eq!(a.read().unwrap(), 2);
+        assert_eq!(a.len(), 2);
+        assert_eq!(a.read_with_options(ReadOptions::lazy()).unwrap(), 2);
+        assert_eq!(a.iter().map(|x| x.entry_hash()).collect::<Vec<_>>(), [1, 2]);
+        assert!(!a.entries()[0].is_data_loaded());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn exposes_header_fields() {
+        let bytes = build_database(32, 1, &[(1, b"first"), (2, b"second")]);
+        let a = Thumbscache::from_reader(Cursor::new(bytes.clone())).unwrap();
+        let header = a.header().unwrap();
+        assert_eq!(&header.magic, b"CMMM");
+        assert_eq!(header.format_version, 32);
+        assert_eq!(header.windows_version, Some(WindowsVersion::Win10));
+        assert_eq!(header.cache_type_number, 1);
+        assert_eq!(header.cache_type, Some(CacheType::Res32));
+        assert_eq!(header.first_entry, 0);
+        assert_eq!(header.first_available_entry as usize, bytes.len());
+        assert_eq!(header.entry_count, Some(2));
+        let empty = Thumbscache::from_reader(Cursor::new(build_database(32, 1, &[]))).unwrap();
+        assert_eq!(empty.header().unwrap().entry_count, Some(0));
+        assert_eq!(&header.raw, &bytes[..24]);
+        assert_eq!(a.raw_header(), &bytes[..24]);
+        assert!(a.reserved_bytes().is_empty());
+
+        // Undocumented bytes between the header and the first entry
+        let mut gap = bytes[..24].to_vec();
+        gap.extend_from_slice(b"reserved");
+        gap.extend_from_slice(&bytes[24..]);
+        gap[12..16].copy_from_slice(&8u32.to_le_bytes());
+        gap[16..20].copy_from_slice(&(bytes.len() as u32 + 8).to_le_bytes());
+        let mut b = Thumbscache::from_bytes(gap);
+        assert_eq!(b.reserved_bytes(), b"reserved");
+        assert_eq!(b.read().unwrap(), 2);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn detects_icon_caches_from_file_name() {
+        let dir = temp_dir("iconcache");
+        let path = dir.join("iconcache_32.db");
+        std::fs::write(&path, build_database(32, 1, &[(5, b"icon")])).unwrap();
+        let mut a = Thumbscache::from_path(&path).unwrap();
+        assert_eq!(a.kind, DatabaseKind::IconCache);
+        assert_eq!(a.read().unwrap(), 1);
+        assert_eq!(a.entries()[0].data, b"icon");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn detects_image_formats() {
+        let mut a = Thumbscache::from_bytes(build_database(32, 3, &[(1, b"BM\x00\x00"), (2, &[0xFF, 0xD8, 0xFF, 0xE0]), (3, b"\x89PNG\r\n\x1a\n"), (4, b"")]));
+        a.read().unwrap();
+        let formats: Vec<ImageFormat> = a.iter().map(|x| x.detect_format()).collect();
+        assert_eq!(formats, [ImageFormat::Bmp, ImageFormat::Jpeg, ImageFormat::Png, ImageFormat::Unknown]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn classifies_payloads_by_cache_type() {
+        let entries: &[(u64, &[u8])] = &[(1, b"\x89PNG\r\n\x1a\n"), (2, b"II*\0\x08\0\0\0\0\0\0\0\0\0"), (3, b"stream")];
+        let mut exif = Thumbscache::from_bytes(build_database(32, 11, entries));
+        exif.read().unwrap();
+        assert_eq!(exif.entries()[0].cache_type, Some(CacheType::EXIF));
+        let kinds: Vec<PayloadKind> = exif.iter().map(|x| x.payload_kind()).collect();
+        assert_eq!(kinds, [PayloadKind::Image(ImageFormat::Png), PayloadKind::Exif, PayloadKind::Unknown]);
+        let mut custom = Thumbscache::from_bytes(build_database(32, 13, entries));
+        custom.read().unwrap();
+        let kinds: Vec<&str> = custom.iter().map(|x| x.payload_kind().extension()).collect();
+        assert_eq!(kinds, ["png", "stream", "stream"]);
+    }
 
+    #[cfg(feature = "fs")]
     #[test]
     fn it_works() {
         let mut a = open_thumbscache(String::from("C:\\Users\\z\\AppData\\Local\\Microsoft\\Windows\\Explorer\\thumbcache_16.db")).unwrap();
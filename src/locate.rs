@@ -0,0 +1,166 @@
+//! Discovery of the Explorer cache directories of a Windows installation
+
+use std::path::{Path, PathBuf};
+
+use crate::{DatabaseKind, ThumbcacheSet, ThumbsError};
+
+// Where Explorer keeps its caches, relative to a user profile
+const EXPLORER_DIR: [&str; 5] = ["AppData", "Local", "Microsoft", "Windows", "Explorer"];
+
+// Collects the existing cache directories, the current user first, without duplicates
+fn cache_dirs(local_app_data: Option<PathBuf>, profiles_dir: Option<PathBuf>) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(local_app_data) = local_app_data {
+        dirs.push(local_app_data.join("Microsoft").join("Windows").join("Explorer"));
+    }
+    if let Some(Ok(profiles)) = profiles_dir.map(std::fs::read_dir) {
+        let mut profile_dirs: Vec<PathBuf> = profiles.filter_map(|x| x.ok()).map(|x| EXPLORER_DIR.iter().fold(x.path(), |path, x| path.join(x))).collect();
+        profile_dirs.sort();
+        dirs.extend(profile_dirs);
+    }
+    let mut found: Vec<PathBuf> = Vec::new();
+    for dir in dirs {
+        if dir.is_dir() && !found.iter().any(|x| same_dir(x, &dir)) {
+            found.push(dir);
+        }
+    }
+    found
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b
+    }
+}
+
+/// Returns the Explorer cache directories holding the thumbnail databases.
+///
+/// The directory of the current user is resolved from %LOCALAPPDATA%. With all_users set, the other profiles next to %USERPROFILE%
+/// are enumerated as well, the profiles that can't be read with the current privileges are left out.
+/// Only existing directories are returned, so the result is empty outside of Windows.
+pub fn locate_thumbcache_dirs(all_users: bool) -> Vec<PathBuf> {
+    let local_app_data = std::env::var_os("LOCALAPPDATA").map(PathBuf::from);
+    let profiles_dir = if all_users {
+        std::env::var_os("USERPROFILE").and_then(|x| Path::new(&x).parent().map(Path::to_path_buf))
+    }else {
+        None
+    };
+    cache_dirs(local_app_data, profiles_dir)
+}
+
+/// Returns the paths of the thumbnail databases (thumbcache_*.db, including the index file) found in the directories located by `locate_thumbcache_dirs()`
+pub fn locate_thumbcache_databases(all_users: bool) -> Vec<PathBuf> {
+    let mut databases = Vec::new();
+    for dir in locate_thumbcache_dirs(all_users) {
+        let Ok(dir_entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut paths: Vec<PathBuf> = dir_entries.filter_map(|x| x.ok()).map(|x| x.path())
+            .filter(|x| DatabaseKind::from_path(x) == Some(DatabaseKind::Thumbnail) && x.extension().is_some_and(|x| x.eq_ignore_ascii_case("db")))
+            .collect();
+        paths.sort();
+        databases.extend(paths);
+    }
+    databases
+}
+
+/// The caches of a single user account, see `scan_system()`
+#[derive(Debug)]
+pub struct UserCaches {
+    /// Name of the profile directory, usually the name of the account
+    pub user: String,
+    /// The Explorer cache directory of the profile
+    pub dir: PathBuf,
+    /// The thumbnail databases of the directory along with their index
+    pub thumbnails: Result<ThumbcacheSet, ThumbsError>,
+    /// The icon cache databases of the directory
+    pub icons: Result<ThumbcacheSet, ThumbsError>
+}
+
+// Finds the child of the directory with this name, ignoring case since images are often mounted on case-sensitive file systems
+fn child_ignore_case(dir: &Path, name: &str) -> Option<PathBuf> {
+    let exact = dir.join(name);
+    if exact.exists() {
+        return Some(exact);
+    }
+    std::fs::read_dir(dir).ok()?.filter_map(|x| x.ok()).find(|x| x.file_name().to_string_lossy().eq_ignore_ascii_case(name)).map(|x| x.path())
+}
+
+/// Scans a mounted system drive or image mount point for the Explorer caches of every user account.
+///
+/// Every profile under Users with an AppData\Local\Microsoft\Windows\Explorer directory is parsed, sorted by user name.
+/// A database failing to parse is reported in the caches of its user instead of stopping the scan.
+/// Returns ThumbsError::InvalidFile if the root has no Users directory.
+pub fn scan_system(root: impl AsRef<Path>) -> Result<Vec<UserCaches>, ThumbsError> {
+    let users = child_ignore_case(root.as_ref(), "Users").ok_or(ThumbsError::InvalidFile)?;
+    let mut profiles: Vec<PathBuf> = std::fs::read_dir(&users).map_err(ThumbsError::IoError)?
+        .filter_map(|x| x.ok()).map(|x| x.path()).filter(|x| x.is_dir()).collect();
+    profiles.sort();
+    let mut found = Vec::new();
+    for profile in profiles {
+        let Some(dir) = EXPLORER_DIR.iter().try_fold(profile.clone(), |path, x| child_ignore_case(&path, x)).filter(|x| x.is_dir()) else {
+            continue;
+        };
+        span!(info_span, "user", dir = %dir.display());
+        found.push(UserCaches {
+            user: profile.file_name().map(|x| x.to_string_lossy().into_owned()).unwrap_or_default(),
+            thumbnails: ThumbcacheSet::open(&dir),
+            icons: ThumbcacheSet::open_kind(&dir, DatabaseKind::IconCache),
+            dir
+        });
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::temp_dir;
+
+    #[test]
+    fn finds_current_and_other_profiles() {
+        let users = temp_dir("locate_users");
+        let current = users.join("alice");
+        let other = users.join("bob");
+        let without_cache = users.join("carol");
+        for profile in [&current, &other] {
+            std::fs::create_dir_all(EXPLORER_DIR.iter().fold(profile.clone(), |path, x| path.join(x))).unwrap();
+        }
+        std::fs::create_dir_all(&without_cache).unwrap();
+        let local_app_data = current.join("AppData").join("Local");
+        let expected_current = local_app_data.join("Microsoft").join("Windows").join("Explorer");
+
+        assert_eq!(cache_dirs(Some(local_app_data.clone()), None).len(), 1);
+        let dirs = cache_dirs(Some(local_app_data), Some(users));
+        assert_eq!(dirs.len(), 2);
+        assert_eq!(dirs[0], expected_current);
+        assert!(dirs[1].starts_with(&other));
+    }
+
+    #[test]
+    fn scans_every_profile_of_a_mounted_drive() {
+        use crate::tests::build_database;
+        let root = temp_dir("scan_system");
+        let alice = EXPLORER_DIR.iter().fold(root.join("Users").join("alice"), |path, x| path.join(x));
+        // Mounted images don't always keep the case Windows uses
+        let bob = ["appdata", "LOCAL", "Microsoft", "windows", "Explorer"].iter().fold(root.join("Users").join("bob"), |path, x| path.join(x));
+        for dir in [&alice, &bob] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        std::fs::create_dir_all(root.join("Users").join("Public")).unwrap();
+        std::fs::write(alice.join("thumbcache_32.db"), build_database(32, 1, &[(1, b"alice")])).unwrap();
+        std::fs::write(alice.join("iconcache_32.db"), build_database(32, 1, &[(2, b"icon")])).unwrap();
+        std::fs::write(bob.join("thumbcache_32.db"), b"CMMM damaged").unwrap();
+
+        let users = scan_system(&root).unwrap();
+        let names: Vec<&str> = users.iter().map(|x| x.user.as_str()).collect();
+        assert_eq!(names, ["alice", "bob"]);
+        assert_eq!(users[0].dir, alice);
+        assert_eq!(users[0].thumbnails.as_ref().unwrap().lookup(1).len(), 1);
+        assert_eq!(users[0].icons.as_ref().unwrap().databases().len(), 1);
+        assert!(users[1].thumbnails.is_err());
+        assert!(users[1].icons.as_ref().unwrap().databases().is_empty());
+        assert!(matches!(scan_system(root.join("Users")), Err(ThumbsError::InvalidFile)));
+    }
+}
@@ -0,0 +1,168 @@
+//! Parsing damaged databases without giving up on the first bad entry
+
+use crate::{carve::carve_entry_at, parse::{entry_header_at, find_next_entry}, EntryOrigin, OffsetStatus, ReadOptions, Thumbscache, ThumbsError, WindowsVersion};
+
+/// A problem found while parsing an entry, see `Thumbscache::read_lossy()`
+#[derive(Debug)]
+pub struct ParseWarning {
+    /// Offset of the entry in the database
+    pub offset: u64,
+    pub error: ThumbsError
+}
+
+impl Thumbscache {
+    /// Reads all the cache entries like .read() does, but keeps going when an entry is damaged.
+    ///
+    /// Problems are collected as warnings instead of errors. After a bad entry, parsing resumes at the next CMMM signature,
+    /// entries cut short by the end of the file are kept with the data that is left.
+    /// Only fails if the database header can't be parsed.
+    pub fn read_lossy(&mut self) -> Result<Vec<ParseWarning>, ThumbsError> {
+        self.read_lossy_with_limit(ReadOptions::default().max_data_size)
+    }
+
+    /// Reads the cache entries like .read_lossy() does, the data of entries claiming more than max_data_size bytes is truncated to that size and reported with a warning
+    pub fn read_lossy_with_limit(&mut self, max_data_size: u32) -> Result<Vec<ParseWarning>, ThumbsError> {
+        self.read_lossy_with_options(ReadOptions { max_data_size, ..ReadOptions::default() })
+    }
+
+    /// Reads the cache entries like .read_lossy() does, following the options.
+    ///
+    /// Damaged entries are always skipped, so `ReadOptions::resync` has no effect. Entries read without their data are still
+    /// verified against the data in the database.
+    pub fn read_lossy_with_options(&mut self, options: ReadOptions) -> Result<Vec<ParseWarning>, ThumbsError> {
+        let max_data_size = options.max_data_size;
+        let header = self.header()?;
+        self.windows_version = header.windows_version;
+        self.cache_type = header.cache_type;
+        let version = header.version()?;
+        let header_len = match version {
+            WindowsVersion::Win7 => 48,
+            _ => 56
+        };
+        span!(debug_span, "read_lossy", max_data_size);
+        let bytes = self.bytes();
+        let end = header.entries_end(bytes.len() as u64).0 as usize;
+        let mut warnings = Vec::new();
+        let validation = header.validate(bytes.len() as u64);
+        if !validation.is_valid() {
+            // Reported at the offset of the header field that can't be trusted
+            let field_offset = if validation.first_entry != OffsetStatus::Valid { 12 } else { 16 };
+            warnings.push(ParseWarning { offset: field_offset, error: ThumbsError::InvalidHeader(validation) });
+        }
+        let mut entries = Vec::new();
+        let mut offset = header.first_entry_offset() as usize;
+        // Once the chain is broken, the entries are only found by their signature
+        let mut origin = EntryOrigin::Live;
+        while offset < end && options.max_entries.is_none_or(|x| entries.len() < x as usize) {
+            let signature_found = bytes.get(offset..offset + 4) == Some(b"CMMM");
+            // Without loading the data, none of it is copied
+            let copied_data_size = if options.load_data { max_data_size } else { 0 };
+            match carve_entry_at(bytes, offset, version, copied_data_size).filter(|_| signature_found) {
+                Some(entry) if options.skip_empty && entry.data_size == 0 => {
+                    offset = offset.saturating_add(entry.size as usize);
+                },
+                Some(mut entry) => {
+                    entry.origin = origin;
+                    entry.cache_type = header.cache_type;
+                    let index = entries.len();
+                    let data = bytes.get(entry.data_offset as usize..).unwrap_or_default();
+                    let data = &data[..data.len().min(entry.data_size as usize)];
+                    if entry.data_size > max_data_size {
+                        warnings.push(ParseWarning {
+                            offset: offset as u64,
+                            error: ThumbsError::DataTooLarge { offset: offset as u64, data_size: entry.data_size, max: max_data_size }
+                        });
+                    }else if data.len() < entry.data_size as usize {
+                        warnings.push(ParseWarning {
+                            offset: offset as u64,
+                            error: ThumbsError::TruncatedEntry { offset: offset as u64, needed: (entry.data_size as usize - data.len()) as u64 }
+                        });
+                    }else if let Some(error) = entry.checksum_mismatch(index, data) {
+                        warnings.push(ParseWarning { offset: offset as u64, error });
+                    }
+                    offset = offset.saturating_add(entry.size as usize);
+                    entries.push(entry);
+                },
+                None => {
+                    let mut resume = offset + 1;
+                    let error = if !signature_found {
+                        ThumbsError::BadEntrySignature { offset: offset as u64 }
+                    }else if bytes.len() < offset + header_len {
+                        ThumbsError::TruncatedEntry { offset: offset as u64, needed: (offset + header_len - bytes.len()) as u64 }
+                    }else {
+                        // The sizes in the header don't add up, the size is the field that can't be trusted.
+                        // The next entry can't start before the end of this one, clamped to the live entries
+                        if let Some((fields, _)) = entry_header_at(bytes, offset, version) {
+                            resume = resume.max((offset as u64).saturating_add(fields.content_len()).min(end as u64) as usize);
+                        }
+                        ThumbsError::MalformedEntry { offset: offset as u64 }
+                    };
+                    warnings.push(ParseWarning { offset: offset as u64, error });
+                    origin = EntryOrigin::Carved;
+                    match find_next_entry(bytes, resume as u64, end as u64, version) {
+                        Some(next) => {
+                            event!(debug, offset, next, "resynchronized on the next entry signature");
+                            offset = next as usize
+                        },
+                        None => {
+                            event!(debug, offset, "no entry signature left to resynchronize on");
+                            break
+                        }
+                    }
+                }
+            }
+        }
+        #[cfg(feature = "tracing")]
+        for warning in &warnings {
+            tracing::warn!(offset = warning.offset, error = %warning.error, "damaged entry");
+        }
+        // Reading again starts over, like .read() does
+        *self.entry_list() = entries;
+        Ok(warnings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_database;
+    use std::io::Cursor;
+
+    #[test]
+    fn keeps_going_after_damaged_entries() {
+        let mut bytes = build_database(32, 1, &[(1, b"first"), (2, b"second"), (3, b"third"), (4, b"fourth")]);
+        let second_entry = 24 + 56 + 32 + 5;
+        let third_entry = second_entry + 56 + 32 + 6;
+        // Break the signature of the second entry and the data of the third
+        bytes[second_entry..second_entry + 4].copy_from_slice(b"XXXX");
+        bytes[third_entry + 56 + 32] ^= 0xFF;
+        let mut database = Thumbscache::from_reader(Cursor::new(bytes)).unwrap();
+        let warnings = database.read_lossy().unwrap();
+        let hashes: Vec<u64> = database.iter().map(|x| x.entry_hash()).collect();
+        assert_eq!(hashes, [1, 3, 4]);
+        let origins: Vec<EntryOrigin> = database.iter().map(|x| x.origin).collect();
+        assert_eq!(origins, [EntryOrigin::Live, EntryOrigin::Carved, EntryOrigin::Carved]);
+        assert_eq!(warnings.len(), 2);
+        assert!(matches!(warnings[0].error, ThumbsError::BadEntrySignature { offset } if offset == second_entry as u64));
+        assert!(matches!(warnings[1].error, ThumbsError::ChecksumMismatch { entry: 1, .. }));
+
+        // A size smaller than the entry, the next entry is found right after the data
+        let mut bytes = build_database(32, 1, &[(1, b"CMMM inside the data"), (2, b"second")]);
+        bytes[24 + 4..24 + 8].copy_from_slice(&8u32.to_le_bytes());
+        let mut database = Thumbscache::from_bytes(bytes);
+        let warnings = database.read_lossy().unwrap();
+        assert_eq!(database.len(), 1);
+        assert_eq!(database.entries()[0].entry_hash(), 2);
+        assert_eq!(warnings.len(), 1);
+        assert!(matches!(warnings[0].error, ThumbsError::MalformedEntry { offset: 24 }));
+
+        let mut database = Thumbscache::from_bytes(build_database(32, 1, &[(1, b"first"), (2, b"second")]));
+        let warnings = database.read_lossy_with_limit(5).unwrap();
+        assert_eq!(database.entries()[1].data, b"secon");
+        assert_eq!(warnings.len(), 1);
+        assert!(matches!(warnings[0].error, ThumbsError::DataTooLarge { data_size: 6, max: 5, .. }));
+        // Reading again replaces the entries
+        database.read_lossy().unwrap();
+        assert_eq!(database.len(), 2);
+    }
+}
@@ -0,0 +1,104 @@
+//! Integrity manifests of exported files, enabled with the `fs` and `hashes` features
+//!
+//! The CSV manifest is always available, the JSON manifest requires the `serde` feature.
+
+use std::{path::Path, sync::atomic::AtomicBool};
+
+use sha2::Sha256;
+
+use crate::{hashes::hex_digest, write_data, CollisionPolicy, ExportOptions, ExportSummary, NamingStrategy, Thumbscache, ThumbsError};
+
+/// The format of the manifest written by `Thumbscache::export_all_with_manifest()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    /// manifest.csv, one line per file preceded by a header line
+    Csv,
+    /// manifest.json, an array with one object per file
+    #[cfg(feature = "serde")]
+    Json
+}
+
+impl ManifestFormat {
+    /// The name of the manifest file, written next to the exported files
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            ManifestFormat::Csv => "manifest.csv",
+            #[cfg(feature = "serde")]
+            ManifestFormat::Json => "manifest.json"
+        }
+    }
+}
+
+// A line of the manifest
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+struct ManifestEntry {
+    file_name: String,
+    offset: u64,
+    entry_hash: String,
+    sha256: String
+}
+
+fn to_csv(entries: &[ManifestEntry]) -> String {
+    let mut csv = String::from("file_name,offset,entry_hash,sha256\n");
+    for entry in entries {
+        let file_name = if entry.file_name.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", entry.file_name.replace('"', "\"\""))
+        }else {
+            entry.file_name.clone()
+        };
+        csv.push_str(&format!("{},{},{},{}\n", file_name, entry.offset, entry.entry_hash, entry.sha256));
+    }
+    csv
+}
+
+impl Thumbscache {
+    /// Exports the entries like `export_all()` does, then writes a manifest listing every file written along with
+    /// the offset of its entry, the entry hash and the SHA-256 of the bytes written.
+    ///
+    /// The manifest is named after its format (see `ManifestFormat::file_name()`) and isn't part of the summary.
+    pub fn export_all_with_manifest(&self, dir: impl AsRef<Path>, naming: NamingStrategy, format: ManifestFormat) -> Result<ExportSummary, ThumbsError> {
+        let dir = dir.as_ref();
+        let mut entries = Vec::new();
+        let summary = self.export_entries(dir, &naming, CollisionPolicy::Overwrite, ExportOptions::default(), &AtomicBool::new(false), |entry, path, data| entries.push(ManifestEntry {
+            file_name: path.strip_prefix(dir).unwrap_or(path).to_string_lossy().into_owned(),
+            offset: entry.offset(),
+            entry_hash: entry.entry_hash_hex(),
+            sha256: hex_digest::<Sha256>(data)
+        }))?;
+        let manifest = match format {
+            ManifestFormat::Csv => to_csv(&entries),
+            #[cfg(feature = "serde")]
+            ManifestFormat::Json => serde_json::to_string_pretty(&entries).map_err(|x| ThumbsError::SerializationError(x.to_string()))?
+        };
+        write_data(dir.join(format.file_name()), manifest.as_bytes())?;
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{build_database, temp_dir};
+
+    #[test]
+    fn lists_the_written_files() {
+        let mut database = Thumbscache::from_bytes(build_database(32, 1, &[(0xAB, b"BMfirst"), (0xCD, b""), (0xEF, b"\xFF\xD8\xFFsecond")]));
+        database.read().unwrap();
+        let dir = temp_dir("manifest");
+        let summary = database.export_all_with_manifest(&dir, NamingStrategy::Index, ManifestFormat::Csv).unwrap();
+        assert_eq!(summary.written.len(), 2);
+        let manifest = std::fs::read_to_string(dir.join("manifest.csv")).unwrap();
+        let lines: Vec<&str> = manifest.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], format!("0.bmp,{},00000000000000ab,{}", database.entries()[0].offset(), hex_digest::<Sha256>(b"BMfirst")));
+        assert!(lines[2].starts_with("2.jpg,"));
+
+        #[cfg(feature = "serde")]
+        {
+            database.export_all_with_manifest(&dir, NamingStrategy::Identifier, ManifestFormat::Json).unwrap();
+            let manifest: serde_json::Value = serde_json::from_slice(&std::fs::read(dir.join("manifest.json")).unwrap()).unwrap();
+            assert_eq!(manifest[1]["file_name"], "00000000000000ef.jpg");
+            assert_eq!(manifest[1]["sha256"], hex_digest::<Sha256>(b"\xFF\xD8\xFFsecond"));
+        }
+    }
+}
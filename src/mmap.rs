@@ -0,0 +1,44 @@
+//! Memory-mapped databases, enabled with the `mmap` feature
+
+use std::{path::Path, sync::Arc};
+
+use crate::{Backing, DatabaseKind, Thumbscache, ThumbsError};
+
+/// Opens the thumbscache database by mapping it into memory instead of reading it.
+/// Additional parsing is neccessary using the .read() function.
+///
+/// The file must not be modified while it is mapped, Windows Explorer should not be running on the analyzed profile.
+///
+/// Returns an error if you specify an invalid file path
+pub fn open_thumbscache_mmap(file: impl AsRef<Path>) -> Result<Thumbscache, ThumbsError> {
+    if let Ok(opened_file) = std::fs::OpenOptions::new().read(true).open(&file) {
+        // Safety : the mapping is read-only, mutating the file underneath is documented as unsupported
+        let map = unsafe { memmap2::Mmap::map(&opened_file) }.map_err(ThumbsError::IoError)?;
+        let mut database = Thumbscache::from_backing(Backing::Mapped(Arc::new(map)));
+        database.kind = DatabaseKind::from_path(&file).unwrap_or_default();
+        let metadata = opened_file.metadata().ok();
+        database.modified = metadata.as_ref().and_then(|x| x.modified().ok());
+        database.created = metadata.as_ref().and_then(|x| x.created().ok());
+        database.path = Some(file.as_ref().to_path_buf());
+        Ok(database)
+    }else {
+        Err(ThumbsError::InvalidFile)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{build_database, temp_dir};
+
+    #[test]
+    fn borrows_data_from_the_mapping() {
+        let dir = temp_dir("mmap");
+        let path = dir.join("thumbcache_48.db");
+        std::fs::write(&path, build_database(32, 2, &[(1, b"mapped")])).unwrap();
+        let mut database = open_thumbscache_mmap(&path).unwrap();
+        database.read().unwrap();
+        assert_eq!(database.cache_type, Some(crate::CacheType::Res48));
+        assert_eq!(database.entry_data(&database.entries()[0]), Some(&b"mapped"[..]));
+    }
+}
@@ -0,0 +1,98 @@
+//! Modifying the entries of a parsed database
+//!
+//! The database is rewritten from its parsed entries, so sizes, offsets and checksums stay consistent.
+//! Unknown header fields and any bytes outside of the parsed entries are not kept, the thumbnail dimensions of Windows 8 and above are read from the data again.
+
+use std::io::Cursor;
+
+use crate::{Backing, Thumbscache, ThumbscacheWriter, ThumbsError};
+
+impl Thumbscache {
+    /// Replaces the data of every entry identified by the hash.
+    /// The database has to be parsed using the .read() function first.
+    ///
+    /// Returns false if no entry has this hash.
+    pub fn replace_entry(&mut self, entry_hash: u64, data: &[u8]) -> Result<bool, ThumbsError> {
+        self.rewrite(entry_hash, Some(data))
+    }
+
+    /// Removes every entry identified by the hash.
+    /// The database has to be parsed using the .read() function first.
+    ///
+    /// Returns false if no entry has this hash.
+    pub fn remove_entry(&mut self, entry_hash: u64) -> Result<bool, ThumbsError> {
+        self.rewrite(entry_hash, None)
+    }
+
+    /// Writes the bytes of the database into a file, replacing its contents
+    #[cfg(feature = "fs")]
+    pub fn write_to_file(&self, file_path: impl AsRef<std::path::Path>) -> Result<(), ThumbsError> {
+        crate::write_data(file_path, self.bytes())
+    }
+
+    fn rewrite(&mut self, entry_hash: u64, replacement: Option<&[u8]>) -> Result<bool, ThumbsError> {
+        if !self.iter().any(|x| x.entry_hash == entry_hash) {
+            return Ok(false);
+        }
+        let (Some(windows_version), Some(cache_type)) = (self.windows_version, self.cache_type) else {
+            return Err(ThumbsError::InvalidFile);
+        };
+        let mut writer = ThumbscacheWriter::new(windows_version, cache_type)?;
+        for entry in self.iter() {
+            if entry.entry_hash != entry_hash {
+                if entry.is_data_loaded() {
+                    writer.append_entry(entry);
+                }else {
+                    // Entries read lazily don't hold their data, it is copied from the database instead
+                    let data = self.entry_data(entry).ok_or(ThumbsError::TruncatedEntry {
+                        offset: entry.offset,
+                        needed: (entry.data_offset + entry.data_size as u64).saturating_sub(self.bytes().len() as u64)
+                    })?;
+                    writer.append_raw(entry.entry_hash, &entry.identifier, data);
+                }
+            }else if let Some(data) = replacement {
+                writer.append_raw(entry.entry_hash, &entry.identifier, data);
+            }
+        }
+        self.stream = Cursor::new(Backing::Owned(writer.into_bytes()));
+        self.entry_list().clear();
+        self.read()?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tests::build_database, ReadOptions, Thumbscache};
+    use std::io::Cursor;
+
+    #[test]
+    fn replaces_and_removes_entries() {
+        let mut database = Thumbscache::from_reader(Cursor::new(build_database(32, 1, &[(1, b"first"), (2, b"second"), (3, b"third")]))).unwrap();
+        database.read().unwrap();
+        assert!(database.replace_entry(2, b"a much longer replacement").unwrap());
+        assert!(database.remove_entry(1).unwrap());
+        assert!(!database.remove_entry(42).unwrap());
+
+        // The rewritten bytes parse on their own
+        let mut reparsed = Thumbscache::from_reader(Cursor::new(database.bytes().to_vec())).unwrap();
+        assert_eq!(reparsed.read().unwrap(), 2);
+        assert_eq!(reparsed.entries()[0].data, b"a much longer replacement");
+        assert_eq!(reparsed.entries()[0].entry_hash(), 2);
+        assert_eq!(reparsed.entries()[1].data, b"third");
+        assert!(reparsed.verify_all().is_clean());
+    }
+
+    #[test]
+    fn keeps_the_data_of_lazily_read_entries() {
+        let mut database = Thumbscache::from_reader(Cursor::new(build_database(32, 1, &[(1, b"first"), (2, b"second"), (3, b"third")]))).unwrap();
+        database.read_with_options(ReadOptions::lazy()).unwrap();
+        assert!(database.replace_entry(2, b"replacement").unwrap());
+        assert!(database.remove_entry(3).unwrap());
+
+        let mut reparsed = Thumbscache::from_reader(Cursor::new(database.bytes().to_vec())).unwrap();
+        assert_eq!(reparsed.read().unwrap(), 2);
+        assert_eq!(reparsed.entries()[0].data, b"first");
+        assert_eq!(reparsed.entries()[1].data, b"replacement");
+    }
+}
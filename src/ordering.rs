@@ -0,0 +1,117 @@
+//! Relative order of the entries and the window in which each of them was cached
+//!
+//! Windows appends entries to a database one after the other, so the order of the live entries in the file is the order
+//! in which their thumbnails were cached. Every entry stored in the file was written after the file was created and
+//! before it was last modified. Carved and recovered entries aren't part of the chain, only their window is known.
+
+use std::time::SystemTime;
+
+use crate::{EntryOrigin, Thumbscache};
+
+/// When an entry was cached relatively to the others, see `Thumbscache::ordering_report()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EntryOrdering {
+    /// Position of the entry in the parsed entries
+    pub index: usize,
+    pub entry_hash: u64,
+    pub offset: u64,
+    /// Position of the entry in the chain of live entries, 0 being the first one cached. None for carved and recovered entries
+    pub sequence: Option<u32>,
+    /// The entry wasn't cached before this time, the creation time of the database file
+    pub not_before: Option<SystemTime>,
+    /// The entry wasn't cached after this time, the modification time of the database file
+    pub not_after: Option<SystemTime>
+}
+
+/// The order in which the entries of a database were cached, returned by `Thumbscache::ordering_report()`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct OrderingReport {
+    /// The creation time of the database file
+    pub created: Option<SystemTime>,
+    /// The modification time of the database file
+    pub modified: Option<SystemTime>,
+    /// Every parsed entry, in the order of the parsed entries
+    pub entries: Vec<EntryOrdering>
+}
+
+impl OrderingReport {
+    /// The live entries, from the first one cached to the last one
+    pub fn sequence(&self) -> Vec<&EntryOrdering> {
+        let mut sequence: Vec<&EntryOrdering> = self.entries.iter().filter(|x| x.sequence.is_some()).collect();
+        sequence.sort_by_key(|x| x.sequence);
+        sequence
+    }
+
+    /// The last live entry cached, the closest to the modification time of the database file
+    pub fn latest(&self) -> Option<&EntryOrdering> {
+        self.entries.iter().filter(|x| x.sequence.is_some()).max_by_key(|x| x.sequence)
+    }
+
+    /// Returns whether the entry at the first index was cached before the entry at the second index,
+    /// None if either of them isn't a live entry
+    pub fn cached_before(&self, first: usize, second: usize) -> Option<bool> {
+        Some(self.entries.get(first)?.sequence? < self.entries.get(second)?.sequence?)
+    }
+}
+
+impl Thumbscache {
+    /// Orders the live entries by their position in the file and bounds when every entry was cached with the timestamps of the database file.
+    ///
+    /// Copying a database gives the copy a new creation time while keeping the modification time,
+    /// a creation time later than the modification time is therefore ignored.
+    /// The timestamps are only known when the database was opened from a path.
+    pub fn ordering_report(&self) -> OrderingReport {
+        let modified = self.modified;
+        let created = self.created.filter(|created| modified.is_none_or(|modified| *created <= modified));
+        let mut live: Vec<(u64, usize)> = self.iter().enumerate().filter(|(_, x)| x.origin == EntryOrigin::Live).map(|(index, x)| (x.offset(), index)).collect();
+        live.sort();
+        let mut sequences = vec![None; self.len()];
+        for (sequence, (_, index)) in live.into_iter().enumerate() {
+            sequences[index] = Some(sequence as u32);
+        }
+        OrderingReport {
+            created,
+            modified,
+            entries: self.iter().zip(sequences).enumerate().map(|(index, (entry, sequence))| EntryOrdering {
+                index,
+                entry_hash: entry.entry_hash(),
+                offset: entry.offset(),
+                sequence,
+                not_before: created,
+                not_after: modified
+            }).collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::tests::build_database;
+
+    #[test]
+    fn orders_the_live_entries() {
+        let mut database = Thumbscache::from_bytes(build_database(32, 1, &[(1, b"first"), (2, b"second"), (3, b"third")]));
+        database.read().unwrap();
+        database.entries_mut()[1].origin = EntryOrigin::Carved;
+        let report = database.ordering_report();
+        assert_eq!(report.entries.iter().map(|x| x.sequence).collect::<Vec<_>>(), [Some(0), None, Some(1)]);
+        assert_eq!(report.sequence().iter().map(|x| x.entry_hash).collect::<Vec<_>>(), [1, 3]);
+        assert_eq!(report.latest().unwrap().entry_hash, 3);
+        assert_eq!((report.cached_before(0, 2), report.cached_before(2, 0), report.cached_before(1, 0)), (Some(true), Some(false), None));
+        assert_eq!(report.entries[0].not_after, None);
+
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        database.modified = Some(modified);
+        database.created = Some(modified - Duration::from_secs(60));
+        let report = database.ordering_report();
+        assert_eq!((report.entries[1].not_before, report.entries[1].not_after), (database.created, Some(modified)));
+
+        // The database was copied after it was last written
+        database.created = Some(modified + Duration::from_secs(60));
+        assert_eq!(database.ordering_report().entries[0].not_before, None);
+    }
+}
@@ -0,0 +1,114 @@
+//! Parsing every database of a directory and verifying checksums concurrently, enabled with the `rayon` feature
+
+use std::{collections::HashMap, path::Path};
+
+use rayon::prelude::*;
+
+use crate::{CacheType, ChecksumStatus, DatabaseKind, Thumbscache, ThumbsError};
+
+/// Opens and parses every thumbcache_*.db database of the directory in parallel.
+///
+/// The index file and databases with an unknown cache type are left out of the map.
+/// Returns the first error if one of the databases fails to open or parse.
+pub fn open_thumbscache_dir(dir: impl AsRef<Path>) -> Result<HashMap<CacheType, Thumbscache>, ThumbsError> {
+    let prefix = DatabaseKind::Thumbnail.file_prefix();
+    let index_name = format!("{}idx.db", prefix);
+    let mut paths = Vec::new();
+    for dir_entry in std::fs::read_dir(dir).map_err(ThumbsError::IoError)? {
+        let path = dir_entry.map_err(ThumbsError::IoError)?.path();
+        let file_name = path.file_name().map(|x| x.to_string_lossy().to_lowercase()).unwrap_or_default();
+        if file_name.starts_with(prefix) && file_name.ends_with(".db") && file_name != index_name {
+            paths.push(path);
+        }
+    }
+    let databases = paths.par_iter().map(|path| {
+        span!(info_span, "database", path = %path.display());
+        let mut database = Thumbscache::from_path(path)?;
+        database.read()?;
+        Ok(database)
+    }).collect::<Result<Vec<Thumbscache>, ThumbsError>>()?;
+    Ok(databases.into_iter().filter_map(|x| Some((x.cache_type?, x))).collect())
+}
+
+/// The outcome of `Thumbscache::par_verify_all()`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParallelVerificationReport {
+    /// The status of every parsed entry, in the order of the entries
+    pub statuses: Vec<ChecksumStatus>,
+    /// Number of entries whose checksums both match
+    pub valid: usize,
+    /// Number of entries whose data checksum doesn't match
+    pub data_mismatches: usize,
+    /// Number of entries whose header checksum doesn't match
+    pub header_mismatches: usize
+}
+
+impl ParallelVerificationReport {
+    /// Returns true if every entry is valid
+    pub fn is_clean(&self) -> bool {
+        self.valid == self.statuses.len()
+    }
+
+    /// The positions of the entries whose checksums don't match
+    pub fn corrupted(&self) -> impl Iterator<Item = usize> + '_ {
+        self.statuses.iter().enumerate().filter(|(_, x)| !x.is_valid()).map(|(index, _)| index)
+    }
+}
+
+impl Thumbscache {
+    /// Verifies every parsed cache entry like `verify_all()` does, spreading the entries across the threads of the rayon pool.
+    ///
+    /// Entries read without their data are verified against the data in the database, so a lazy read can be verified without loading it.
+    pub fn par_verify_all(&self) -> ParallelVerificationReport {
+        let statuses: Vec<ChecksumStatus> = self.entries().par_iter().map(|entry| entry.verify_data(self.entry_data(entry).unwrap_or(&entry.data))).collect();
+        ParallelVerificationReport {
+            valid: statuses.iter().filter(|x| x.is_valid()).count(),
+            data_mismatches: statuses.iter().filter(|x| !x.data_valid).count(),
+            header_mismatches: statuses.iter().filter(|x| !x.header_valid).count(),
+            statuses
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{build_database, build_index, temp_dir};
+
+    #[test]
+    fn parses_every_database_of_the_directory() {
+        let dir = temp_dir("open_dir");
+        std::fs::write(dir.join("thumbcache_32.db"), build_database(32, 1, &[(1, b"small")])).unwrap();
+        std::fs::write(dir.join("thumbcache_256.db"), build_database(32, 4, &[(1, b"large"), (2, b"other")])).unwrap();
+        std::fs::write(dir.join("thumbcache_idx.db"), build_index(32, &[])).unwrap();
+        std::fs::write(dir.join("iconcache_32.db"), build_database(32, 1, &[])).unwrap();
+        let databases = open_thumbscache_dir(&dir).unwrap();
+        assert_eq!(databases.len(), 2);
+        assert_eq!(databases[&CacheType::Res32].entries()[0].data, b"small");
+        assert_eq!(databases[&CacheType::Res256].len(), 2);
+    }
+
+    #[test]
+    fn verifies_entries_in_parallel() {
+        let mut bytes = build_database(32, 1, &[(1, b"first"), (2, b""), (3, b"third"), (4, b"fourth")]);
+        // Corrupt the data of the first entry and the header of the third
+        bytes[24 + 56 + 32] ^= 0xFF;
+        let third = database_offsets(&bytes)[2];
+        bytes[third + 8] ^= 0xFF;
+        let mut database = Thumbscache::from_bytes(bytes);
+        database.read_with_options(crate::ReadOptions::lazy()).unwrap();
+        let report = database.par_verify_all();
+        assert_eq!((report.valid, report.data_mismatches, report.header_mismatches), (2, 1, 1));
+        assert_eq!(report.corrupted().collect::<Vec<_>>(), [0, 2]);
+        assert!(!report.is_clean());
+
+        // The same statuses as the entries read with their data
+        let mut loaded = Thumbscache::from_bytes(database.bytes().to_vec());
+        loaded.read().unwrap();
+        assert_eq!(report.statuses, loaded.iter().map(|x| x.verify()).collect::<Vec<_>>());
+    }
+
+    fn database_offsets(bytes: &[u8]) -> Vec<usize> {
+        crate::parse_database(bytes, crate::ReadOptions::default()).unwrap().1.iter().map(|x| x.offset() as usize).collect()
+    }
+}
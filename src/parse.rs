@@ -0,0 +1,720 @@
+//! The parsing core, working on the bytes of a database held in memory
+//!
+//! Only `core` and `alloc` are used here, so the parser is available without the `std` feature.
+//! Opening files and reading from `std::io` sources is left to `Thumbscache` and `ThumbscacheReader`.
+
+use alloc::{string::{String, ToString}, vec::Vec};
+
+use crate::{endian::{utf16_le, LittleEndian}, CacheEntry, CacheType, EntryFlags, EntryOrigin, ImageFormat, ReadOptions, ThumbsError, VersionFields, WindowsVersion};
+
+// The header at the start of a database is always this long, whatever the Windows version
+pub(crate) const HEADER_SIZE: usize = 24;
+
+/// The header at the start of a database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CacheHeader {
+    /// The signature, always "CMMM"
+    pub magic: [u8; 4],
+    /// The raw format version
+    pub format_version: u32,
+    /// The Windows version matching the format version, if it's known
+    pub windows_version: Option<WindowsVersion>,
+    /// The raw cache type number, its meaning depends on the Windows version
+    pub cache_type_number: u32,
+    /// The cache type matching the cache type number
+    pub cache_type: Option<CacheType>,
+    /// Offset of the first entry, relative to the end of the 24 byte header
+    pub first_entry: u32,
+    /// Offset of the first available entry, where the next entry would be written
+    pub first_available_entry: u32,
+    /// Number of entries, only present when the layout of the header is known
+    pub entry_count: Option<u32>,
+    /// The header as stored, for the fields whose meaning isn't known on every Windows build
+    pub raw: [u8; HEADER_SIZE]
+}
+
+/// Where an offset of the database header points to, see `CacheHeader::validate()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum OffsetStatus {
+    /// Within the entries of the database
+    Valid,
+    /// Inside the 24 byte header
+    InsideHeader,
+    /// Before the first entry
+    BeforeFirstEntry,
+    /// Past the end of the database
+    PastEnd
+}
+
+/// The outcome of checking the offsets of the database header against the size of the database
+///
+/// Windows always keeps them within the file, anything else points to a damaged or tampered header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct HeaderValidation {
+    /// Where the first entry offset points to
+    pub first_entry: OffsetStatus,
+    /// Where the first available entry offset points to
+    pub first_available_entry: OffsetStatus
+}
+
+impl HeaderValidation {
+    /// Returns true if both offsets point within the entries
+    pub fn is_valid(&self) -> bool {
+        self.first_entry == OffsetStatus::Valid && self.first_available_entry == OffsetStatus::Valid
+    }
+}
+
+impl CacheHeader {
+    /// The offset of the first entry from the start of the database
+    pub fn first_entry_offset(&self) -> u64 {
+        HEADER_SIZE as u64 + self.first_entry as u64
+    }
+
+    /// Checks where the offsets of the header point to, in a database of len bytes
+    pub fn validate(&self, len: u64) -> HeaderValidation {
+        let first_entry = self.first_entry_offset();
+        let first_available_entry = self.first_available_entry as u64;
+        HeaderValidation {
+            first_entry: if first_entry > len { OffsetStatus::PastEnd } else { OffsetStatus::Valid },
+            first_available_entry: if first_available_entry < HEADER_SIZE as u64 {
+                OffsetStatus::InsideHeader
+            }else if first_available_entry < first_entry {
+                OffsetStatus::BeforeFirstEntry
+            }else if first_available_entry > len {
+                OffsetStatus::PastEnd
+            }else {
+                OffsetStatus::Valid
+            }
+        }
+    }
+
+    /// The Windows version whose layout the entries use, failing with ThumbsError::UnknownVersion if the format version isn't known
+    pub fn version(&self) -> Result<WindowsVersion, ThumbsError> {
+        self.windows_version.ok_or(ThumbsError::UnknownVersion { version: self.format_version })
+    }
+
+    /// Parses the database with the layout of this Windows version whatever its format version says, the cache type is looked up again
+    pub fn assume_version(&mut self, version: WindowsVersion) {
+        self.windows_version = Some(version);
+        self.cache_type = version.cache_types().get(self.cache_type_number as usize).copied();
+    }
+
+    // Where the chain of live entries ends, and whether that end comes from the header.
+    // Falls back to the end of the file when the first available entry points outside of the entries.
+    pub(crate) fn entries_end(&self, len: u64) -> (u64, bool) {
+        let first_available_entry = self.first_available_entry as u64;
+        if (self.first_entry_offset()..=len).contains(&first_available_entry) {
+            (first_available_entry, true)
+        }else {
+            (len, false)
+        }
+    }
+}
+
+// Borrows the bytes in offset..offset + len, failing instead of panicking when they go past the end.
+// Every length read from a file goes through here before it is used to slice.
+pub(crate) fn slice_at(bytes: &[u8], offset: u64, len: u64) -> Result<&[u8], ThumbsError> {
+    let end = offset.saturating_add(len);
+    if end > bytes.len() as u64 {
+        return Err(ThumbsError::Truncated { offset, needed: end - bytes.len() as u64 });
+    }
+    Ok(&bytes[offset as usize..end as usize])
+}
+
+/// Parses the header at the start of a database, only the first 24 bytes are needed
+pub fn parse_header(bytes: &[u8]) -> Result<CacheHeader, ThumbsError> {
+    let bytes = slice_at(bytes, 0, HEADER_SIZE as u64)?;
+    if let Ok(check_string) = core::str::from_utf8(&bytes[0..4]) {
+        if check_string != "CMMM" {
+            event!(warn, signature = check_string, "not a thumbnail cache database");
+            return Err(ThumbsError::UnexpectedString(check_string.to_string()));
+        }
+    }else {
+        return Err(ThumbsError::InvalidCheckString);
+    }
+    let format_version: u32 = bytes.u32_le(4);
+    let cache_type_number: u32 = bytes.u32_le(8);
+    let windows_version = WindowsVersion::from_format_version(format_version);
+    let cache_type = windows_version.and_then(|version| version.cache_types().get(cache_type_number as usize).copied());
+    let first_entry: u32 = bytes.u32_le(12);
+    let first_available_entry: u32 = bytes.u32_le(16);
+    let entry_count = windows_version.map(|_| bytes.u32_le(20));
+    event!(debug, format_version, cache_type_number, first_entry, first_available_entry, "parsed the database header");
+    Ok(CacheHeader {
+        magic: [bytes[0], bytes[1], bytes[2], bytes[3]],
+        format_version,
+        windows_version,
+        cache_type_number,
+        cache_type,
+        first_entry,
+        first_available_entry,
+        entry_count,
+        raw: bytes.try_into().map_err(|_| ThumbsError::Truncated { offset: 0, needed: HEADER_SIZE as u64 })?
+    })
+}
+
+/// Parses a whole database, returning its header and the entries following the options
+///
+/// ```
+/// use thumbscache::{parse_database, ReadOptions};
+/// # let bytes = {
+/// #     let mut writer = thumbscache::ThumbscacheWriter::new(thumbscache::WindowsVersion::Win10, thumbscache::CacheType::Res256).unwrap();
+/// #     writer.append(1, b"BM...");
+/// #     writer.into_bytes()
+/// # };
+/// let (header, entries) = parse_database(&bytes, ReadOptions::default()).unwrap();
+/// assert_eq!(header.entry_count, Some(entries.len() as u32));
+/// ```
+pub fn parse_database(bytes: &[u8], options: ReadOptions) -> Result<(CacheHeader, Vec<CacheEntry>), ThumbsError> {
+    let header = parse_header(bytes)?;
+    let mut entries = Vec::new();
+    parse_entries(bytes, &header, header.first_entry_offset(), options, &mut entries, |_, _, _| true)?;
+    Ok((header, entries))
+}
+
+/// Parses a whole database like parse_database(), the entries borrow their identifier and data from the bytes instead of copying them
+///
+/// ```
+/// use thumbscache::{parse_database_ref, ReadOptions};
+/// # let bytes = {
+/// #     let mut writer = thumbscache::ThumbscacheWriter::new(thumbscache::WindowsVersion::Win10, thumbscache::CacheType::Res256).unwrap();
+/// #     writer.append(1, b"BM...");
+/// #     writer.into_bytes()
+/// # };
+/// let (_, entries) = parse_database_ref(&bytes, ReadOptions::default()).unwrap();
+/// assert_eq!(entries[0].data, b"BM...");
+/// ```
+pub fn parse_database_ref(bytes: &[u8], options: ReadOptions) -> Result<(CacheHeader, Vec<CacheEntryRef<'_>>), ThumbsError> {
+    let header = parse_header(bytes)?;
+    let mut entries = Vec::new();
+    walk_entries(bytes, &header, options, |entry, _| {
+        entries.push(entry);
+        true
+    })?;
+    Ok((header, entries))
+}
+
+/// Parses the single entry starting at the offset, as found by an earlier scan or an external index, borrowing its identifier and data from the bytes
+///
+/// Fails with ThumbsError::BadEntrySignature if no entry starts there.
+pub fn parse_entry_at<'a>(bytes: &'a [u8], header: &CacheHeader, offset: u64) -> Result<CacheEntryRef<'a>, ThumbsError> {
+    entry_at(bytes, offset, true, header, header.version()?, ReadOptions::default())?.ok_or(ThumbsError::BadEntrySignature { offset })
+}
+
+/// Walks the entry headers of a whole database without copying identifiers or data, returning its header and a summary of every entry
+///
+/// Much faster than parse_database() for triage, as only the fixed-size fields of every entry header are kept.
+pub fn scan_database_headers(bytes: &[u8], options: ReadOptions) -> Result<(CacheHeader, Vec<EntrySummary>), ThumbsError> {
+    let header = parse_header(bytes)?;
+    let mut entries = Vec::new();
+    walk_entries(bytes, &header, options, |entry, _| {
+        entries.push(entry.summary());
+        true
+    })?;
+    Ok((header, entries))
+}
+
+/// The fixed-size fields of an entry header, see `scan_database_headers()` and `Thumbscache::scan_headers()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EntrySummary {
+    /// The byte offset the entry was found at, from the start of the database
+    pub offset: u64,
+    /// The 64-bit hash identifying the cached item
+    pub entry_hash: u64,
+    /// Size of the whole entry record
+    pub size: u32,
+    pub identifier_string_size: u32,
+    pub padding_size: u32,
+    pub data_size: u32,
+    /// Offset of the data, from the start of the database
+    pub data_offset: u64,
+    /// CRC-64 of the data, as stored in the entry header
+    pub data_checksum: u64,
+    /// CRC-64 of the entry header, as stored in it
+    pub header_checksum: u64
+}
+
+/// Cache entry borrowing from the bytes of its database
+///
+/// Returned by `parse_database_ref()` and `Thumbscache::entry_refs()`, it holds the same fields as CacheEntry without copying the identifier and data.
+/// The data is always available, the `load_data` option doesn't apply to borrowed entries.
+#[derive(Debug, Clone)]
+pub struct CacheEntryRef<'a> {
+    offset: u64,
+    size: u32,
+    entry_hash: u64,
+    pub version_fields: VersionFields,
+    identifier_string_size: u32,
+    padding_size: u32,
+    pub data_size: u32,
+    data_checksum: u64,
+    header_checksum: u64,
+    header: &'a [u8],
+    data_offset: u64,
+    identifier: &'a [u8],
+    /// Where the entry was found
+    pub origin: EntryOrigin,
+    /// The cache type of the database the entry was read from
+    pub cache_type: Option<CacheType>,
+    pub data: &'a [u8]
+}
+
+impl<'a> CacheEntryRef<'a> {
+    /// The 64-bit hash identifying the cached item, as stored in the entry header
+    pub fn entry_hash(&self) -> u64 {
+        self.entry_hash
+    }
+
+    /// The byte offset the entry was found at, from the start of the database
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The raw bytes of the identifier string, before UTF-16 decoding
+    pub fn identifier_bytes(&self) -> &'a [u8] {
+        self.identifier
+    }
+
+    /// The raw bytes of the entry header, borrowed from the database
+    pub fn raw_header(&self) -> &'a [u8] {
+        self.header
+    }
+
+    /// Decodes the identifier string
+    pub fn identifier_string(&self) -> String {
+        decode_identifier(self.identifier)
+    }
+
+    /// Detects the image format of the data by looking at its magic number
+    pub fn detect_format(&self) -> ImageFormat {
+        ImageFormat::detect(self.data)
+    }
+
+    /// The fixed-size fields of the entry header
+    pub fn summary(&self) -> EntrySummary {
+        EntrySummary {
+            offset: self.offset,
+            entry_hash: self.entry_hash,
+            size: self.size,
+            identifier_string_size: self.identifier_string_size,
+            padding_size: self.padding_size,
+            data_size: self.data_size,
+            data_offset: self.data_offset,
+            data_checksum: self.data_checksum,
+            header_checksum: self.header_checksum
+        }
+    }
+
+    /// Copies the entry into an owned CacheEntry
+    pub fn to_entry(&self) -> CacheEntry {
+        self.clone().into_entry(true)
+    }
+
+    // Copies the borrowed fields, the data is left empty unless asked for
+    fn into_entry(self, load_data: bool) -> CacheEntry {
+        CacheEntry {
+            offset: self.offset,
+            size: self.size,
+            entry_hash: self.entry_hash,
+            version_fields: self.version_fields,
+            identifier_string_size: self.identifier_string_size,
+            padding_size: self.padding_size,
+            data_size: self.data_size,
+            data_checksum: self.data_checksum,
+            header_checksum: self.header_checksum,
+            header: self.header.to_vec(),
+            data_offset: self.data_offset,
+            identifier_string: decode_identifier(self.identifier),
+            identifier: self.identifier.to_vec(),
+            origin: self.origin,
+            cache_type: self.cache_type,
+            data: if load_data { self.data.to_vec() } else { Vec::new() }
+        }
+    }
+}
+
+// Walks the chain of entries described by the header, adding them to the list as they are parsed so they are kept when an error occurs.
+// The progress callback receives the number of bytes processed, the size of the database and the number of entries added, returning false stops parsing.
+// Parsing starts at the offset, the first entry of the header unless earlier entries were already parsed.
+pub(crate) fn parse_entries(bytes: &[u8], header: &CacheHeader, start: u64, options: ReadOptions, entries: &mut Vec<CacheEntry>, mut progress: impl FnMut(u64, u64, u32) -> bool) -> Result<u32, ThumbsError> {
+    let len = bytes.len() as u64;
+    let mut added_entries = 0;
+    walk_entries_from(bytes, header, start, options, |entry, position| {
+        entries.push(entry.into_entry(options.load_data));
+        added_entries += 1;
+        progress(position.min(len), len, added_entries)
+    })
+}
+
+// Walks the chain of entries described by the header, handing every entry to the callback along with the offset of the next one.
+// Returning false from the callback stops parsing, the number of entries handed out is returned.
+// With the resync option a damaged entry is skipped, parsing resumes at the next valid entry and the entries found from there are marked as carved.
+pub(crate) fn walk_entries<'a>(bytes: &'a [u8], header: &CacheHeader, options: ReadOptions, visit: impl FnMut(CacheEntryRef<'a>, u64) -> bool) -> Result<u32, ThumbsError> {
+    walk_entries_from(bytes, header, header.first_entry_offset(), options, visit)
+}
+
+// Walks the chain like walk_entries() does, starting at the offset of an entry
+fn walk_entries_from<'a>(bytes: &'a [u8], header: &CacheHeader, start: u64, options: ReadOptions, mut visit: impl FnMut(CacheEntryRef<'a>, u64) -> bool) -> Result<u32, ThumbsError> {
+    let len = bytes.len() as u64;
+    let mut position: u64 = start;
+    if position > len {
+        event!(warn, first_entry = position, len, "first entry past the end of the database");
+        return Err(ThumbsError::InvalidHeader(header.validate(len)));
+    }
+    // Past the end of the live entries a different signature is just unused space, before it the chain is broken
+    let (end, strict) = header.entries_end(len);
+    let mut added_entries = 0;
+    let mut origin = EntryOrigin::Live;
+    let version = header.version().inspect_err(|_| {
+        event!(warn, format_version = header.format_version, "unknown format version");
+    })?;
+    span!(debug_span, "parse_entries", len, end);
+    while position < end && options.max_entries.is_none_or(|x| added_entries < x) {
+        let entry_offset = position;
+        let entry = match entry_at(bytes, entry_offset, strict, header, version, options) {
+            Ok(Some(entry)) => entry,
+            Ok(None) | Err(_) if options.resync => match find_next_entry(bytes, entry_offset + 1, end, version) {
+                Some(next) => {
+                    event!(debug, offset = entry_offset, next, "resynchronized on the next entry signature");
+                    position = next;
+                    origin = EntryOrigin::Carved;
+                    continue;
+                },
+                None => break
+            },
+            // A different signature where the end of the entries is unknown
+            Ok(None) => break,
+            Err(error) => return Err(error)
+        };
+        event!(trace, offset = entry_offset, hash = entry.entry_hash, data_size = entry.data_size, "entry");
+        // If we didn't read enough data then we skip to the next cache entry
+        position = entry_offset + entry.size as u64;
+        if options.skip_empty && entry.data_size == 0 {
+            continue;
+        }
+        added_entries += 1;
+        if !visit(CacheEntryRef { origin, ..entry }, position) {
+            break;
+        }
+    }
+    Ok(added_entries)
+}
+
+// Parses the entry starting at the offset, None if there is a different signature where the end of the entries is unknown (strict is false)
+fn entry_at<'a>(bytes: &'a [u8], entry_offset: u64, strict: bool, header: &CacheHeader, version: WindowsVersion, options: ReadOptions) -> Result<Option<CacheEntryRef<'a>>, ThumbsError> {
+    let len = bytes.len() as u64;
+    let mut temp_bytes: [u8; 56] = [0; 56];
+    // The Windows 7 header is shorter, so fewer than 56 bytes may be left
+    let available = slice_at(bytes, entry_offset, len.saturating_sub(entry_offset).min(56))?;
+    temp_bytes[..available.len()].copy_from_slice(available);
+    if &temp_bytes[0..4] != b"CMMM" {
+        if strict {
+            event!(warn, offset = entry_offset, "bad entry signature");
+            return Err(ThumbsError::BadEntrySignature { offset: entry_offset });
+        }
+        return Ok(None);
+    }
+    let fields = parse_entry_header(version, &temp_bytes);
+    if fields.data_size > options.max_data_size {
+        event!(warn, offset = entry_offset, data_size = fields.data_size, "entry data over the size limit");
+        return Err(ThumbsError::DataTooLarge { offset: entry_offset, data_size: fields.data_size, max: options.max_data_size });
+    }
+    let entry_end = entry_offset + fields.content_len();
+    if entry_end > len {
+        event!(warn, offset = entry_offset, entry_end, "entry cut short by the end of the database");
+        return Err(ThumbsError::TruncatedEntry { offset: entry_offset, needed: entry_end - len });
+    }
+    let identifier_start = entry_offset + fields.header_len as u64;
+    let identifier = slice_at(bytes, identifier_start, fields.identifier_string_size as u64)?;
+    let data_offset = identifier_start + fields.identifier_string_size as u64 + fields.padding_size as u64;
+    let data = slice_at(bytes, data_offset, fields.data_size as u64)?;
+    fields.next_entry_offset(entry_offset).inspect_err(|_| {
+        event!(warn, offset = entry_offset, size = fields.size, "entry smaller than its content");
+    })?;
+    Ok(Some(CacheEntryRef {
+        offset: entry_offset,
+        size: fields.size,
+        entry_hash: fields.entry_hash,
+        version_fields: fields.version_fields,
+        identifier_string_size: fields.identifier_string_size,
+        padding_size: fields.padding_size,
+        data_size: fields.data_size,
+        data_checksum: fields.data_checksum,
+        header_checksum: fields.header_checksum,
+        header: slice_at(bytes, entry_offset, fields.header_len as u64)?,
+        data_offset,
+        identifier,
+        origin: EntryOrigin::Live,
+        cache_type: header.cache_type,
+        data
+    }))
+}
+
+// Entry header fields, their position depends on the Windows version
+pub(crate) struct EntryHeaderFields {
+    pub(crate) size: u32,
+    pub(crate) entry_hash: u64,
+    pub(crate) version_fields: VersionFields,
+    pub(crate) identifier_string_size: u32,
+    pub(crate) padding_size: u32,
+    pub(crate) data_size: u32,
+    pub(crate) data_checksum: u64,
+    pub(crate) header_checksum: u64,
+    pub(crate) header_len: usize
+}
+
+impl EntryHeaderFields {
+    // The length of the header, identifier, padding and data together
+    pub(crate) fn content_len(&self) -> u64 {
+        self.header_len as u64 + self.identifier_string_size as u64 + self.padding_size as u64 + self.data_size as u64
+    }
+
+    // Where the entry starting at the offset is followed by the next one.
+    // A size smaller than the content would move backwards or wrap around, so the entry is rejected instead.
+    pub(crate) fn next_entry_offset(&self, offset: u64) -> Result<u64, ThumbsError> {
+        if (self.size as u64) < self.content_len() {
+            return Err(ThumbsError::MalformedEntry { offset });
+        }
+        Ok(offset + self.size as u64)
+    }
+}
+
+// Parses the header of the entry starting at the offset, along with the bytes it was parsed from
+pub(crate) fn entry_header_at(bytes: &[u8], offset: usize, version: WindowsVersion) -> Option<(EntryHeaderFields, [u8; 56])> {
+    // The Windows 7 header is shorter, so fewer than 56 bytes may be left
+    let available = bytes.get(offset..)?;
+    let mut temp_bytes: [u8; 56] = [0; 56];
+    temp_bytes[..available.len().min(56)].copy_from_slice(&available[..available.len().min(56)]);
+    if &temp_bytes[0..4] != b"CMMM" {
+        return None;
+    }
+    let fields = parse_entry_header(version, &temp_bytes);
+    if available.len() < fields.header_len {
+        return None;
+    }
+    Some((fields, temp_bytes))
+}
+
+// Finds the next entry in from..end, a signature followed by a header whose sizes add up and whose content fits in the bytes.
+// Signatures that merely show up inside the data of an entry are passed over.
+pub(crate) fn find_next_entry(bytes: &[u8], from: u64, end: u64, version: WindowsVersion) -> Option<u64> {
+    let window = bytes.get(from as usize..(end as usize).min(bytes.len()))?;
+    window.windows(4).enumerate().filter(|(_, x)| *x == b"CMMM").map(|(x, _)| from + x as u64).find(|offset| {
+        entry_header_at(bytes, *offset as usize, version).is_some_and(|(fields, _)| {
+            fields.next_entry_offset(*offset).is_ok() && offset.saturating_add(fields.content_len()) <= bytes.len() as u64
+        })
+    })
+}
+
+// Parses the 56 bytes read at the start of an entry
+pub(crate) fn parse_entry_header(version: WindowsVersion, temp_bytes: &[u8; 56]) -> EntryHeaderFields {
+    let size: u32 = temp_bytes.u32_le(4);
+    let entry_hash: u64 = temp_bytes.u64_le(8);
+    match version {
+        WindowsVersion::WinVista => {
+            EntryHeaderFields {
+                size,
+                entry_hash,
+                version_fields: VersionFields::Vista {
+                    file_extension: utf16_le(&temp_bytes[16..24])
+                },
+                identifier_string_size: temp_bytes.u32_le(24),
+                padding_size: temp_bytes.u32_le(28),
+                data_size: temp_bytes.u32_le(32),
+                data_checksum: temp_bytes.u64_le(40),
+                header_checksum: temp_bytes.u64_le(48),
+                header_len: 56
+            }
+        },
+        WindowsVersion::Win7 => EntryHeaderFields {
+            size,
+            entry_hash,
+            version_fields: VersionFields::Win7,
+            identifier_string_size: temp_bytes.u32_le(16),
+            padding_size: temp_bytes.u32_le(20),
+            data_size: temp_bytes.u32_le(24),
+            data_checksum: temp_bytes.u64_le(32),
+            header_checksum: temp_bytes.u64_le(40),
+            // The Windows 7 entry header is only 48 bytes long
+            header_len: 48
+        },
+        // Windows 8, 8.1 and every Windows 10 and 11 build share this layout
+        _ => EntryHeaderFields {
+            size,
+            entry_hash,
+            version_fields: VersionFields::Win8 {
+                width: temp_bytes.u32_le(28),
+                height: temp_bytes.u32_le(32),
+                flags: EntryFlags::from_bits_retain(temp_bytes.u32_le(36))
+            },
+            identifier_string_size: temp_bytes.u32_le(16),
+            padding_size: temp_bytes.u32_le(20),
+            data_size: temp_bytes.u32_le(24),
+            data_checksum: temp_bytes.u64_le(40),
+            header_checksum: temp_bytes.u64_le(48),
+            header_len: 56
+        }
+    }
+}
+
+// Decodes a UTF-16 identifier string
+pub(crate) fn decode_identifier(identifier_string_vec: &[u8]) -> String {
+    utf16_le(identifier_string_vec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_database;
+
+    #[test]
+    fn parses_databases_from_a_slice() {
+        let bytes = build_database(30, 2, &[(1, b"first"), (2, b""), (3, b"third")]);
+        let (header, entries) = parse_database(&bytes, ReadOptions::default()).unwrap();
+        assert_eq!(header.windows_version, Some(WindowsVersion::Win8));
+        assert_eq!(header.cache_type, Some(CacheType::Res48));
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[2].data, b"third");
+        let (_, entries) = parse_database(&bytes, ReadOptions { skip_empty: true, ..ReadOptions::default() }).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(matches!(parse_header(&bytes[..20]), Err(ThumbsError::Truncated { offset: 0, needed: 4 })));
+    }
+
+    #[test]
+    fn borrowed_entries_match_owned_ones() {
+        let bytes = build_database(21, 1, &[(1, b"first"), (2, b""), (3, b"third")]);
+        let (_, owned) = parse_database(&bytes, ReadOptions::default()).unwrap();
+        let (_, borrowed) = parse_database_ref(&bytes, ReadOptions { load_data: false, ..ReadOptions::default() }).unwrap();
+        assert_eq!(borrowed.len(), 3);
+        for (owned, borrowed) in owned.iter().zip(&borrowed) {
+            assert_eq!(borrowed.data, owned.data.as_slice());
+            assert_eq!(borrowed.identifier_string(), owned.identifier_string);
+            // The borrowed slices point into the database itself
+            assert_eq!(borrowed.data.as_ptr(), bytes[owned.data_offset as usize..].as_ptr());
+            let copy = borrowed.to_entry();
+            assert_eq!((copy.offset(), copy.entry_hash(), copy.header.as_slice()), (owned.offset(), owned.entry_hash(), owned.header.as_slice()));
+            assert!(copy.verify().data_valid && copy.verify().header_valid);
+        }
+    }
+
+    #[test]
+    fn summarizes_entry_headers() {
+        let bytes = build_database(31, 1, &[(1, b"first"), (2, b"")]);
+        let (_, owned) = parse_database(&bytes, ReadOptions::default()).unwrap();
+        let (header, summaries) = scan_database_headers(&bytes, ReadOptions::default()).unwrap();
+        assert_eq!(header.entry_count, Some(2));
+        let expected: Vec<EntrySummary> = owned.iter().map(|x| EntrySummary {
+            offset: x.offset,
+            entry_hash: x.entry_hash,
+            size: x.size,
+            identifier_string_size: x.identifier_string_size,
+            padding_size: x.padding_size,
+            data_size: x.data_size,
+            data_offset: x.data_offset,
+            data_checksum: x.data_checksum,
+            header_checksum: x.header_checksum
+        }).collect();
+        assert_eq!(summaries, expected);
+        #[cfg(feature = "std")]
+        assert_eq!(crate::Thumbscache::from_bytes(bytes).scan_headers().unwrap(), expected);
+    }
+
+    #[test]
+    fn resynchronizes_after_damaged_entries() {
+        let mut bytes = build_database(32, 1, &[(1, b"first"), (2, b"CMMM in the data"), (3, b"third"), (4, b"fourth")]);
+        let second_entry = 24 + 56 + 32 + 5;
+        // Break the signature of the second entry, the signature in its data has no valid header after it
+        bytes[second_entry..second_entry + 4].copy_from_slice(b"XXXX");
+        assert!(matches!(parse_database(&bytes, ReadOptions::default()), Err(ThumbsError::BadEntrySignature { .. })));
+        let options = ReadOptions { resync: true, ..ReadOptions::default() };
+        let (_, entries) = parse_database(&bytes, options).unwrap();
+        let found: Vec<(u64, EntryOrigin)> = entries.iter().map(|x| (x.entry_hash, x.origin)).collect();
+        assert_eq!(found, [(1, EntryOrigin::Live), (3, EntryOrigin::Carved), (4, EntryOrigin::Carved)]);
+
+        // Without a valid first available entry the chain used to end at the damaged entry
+        bytes[16..20].copy_from_slice(&0u32.to_le_bytes());
+        assert_eq!(parse_database(&bytes, ReadOptions::default()).unwrap().1.len(), 1);
+        assert_eq!(parse_database_ref(&bytes, options).unwrap().1.len(), 3);
+    }
+
+    #[test]
+    fn validates_header_offsets() {
+        let mut bytes = build_database(32, 1, &[(1, b"first")]);
+        let len = bytes.len() as u64;
+        let header = parse_header(&bytes).unwrap();
+        assert!(header.validate(len).is_valid());
+        assert_eq!(header.validate(len - 1).first_available_entry, OffsetStatus::PastEnd);
+        let moved = CacheHeader { first_entry: 8, first_available_entry: 28, ..header };
+        assert_eq!(moved.validate(len).first_available_entry, OffsetStatus::BeforeFirstEntry);
+        bytes[16..20].copy_from_slice(&8u32.to_le_bytes());
+        assert_eq!(parse_header(&bytes).unwrap().validate(len).first_available_entry, OffsetStatus::InsideHeader);
+
+        // An entry can't be found past the end of the database
+        bytes[12..16].copy_from_slice(&(len as u32).to_le_bytes());
+        let header = parse_header(&bytes).unwrap();
+        assert_eq!(header.validate(len).first_entry, OffsetStatus::PastEnd);
+        assert!(matches!(parse_database(&bytes, ReadOptions::default()), Err(ThumbsError::InvalidHeader(x)) if !x.is_valid()));
+        #[cfg(feature = "std")]
+        {
+            let mut database = crate::Thumbscache::from_bytes(bytes);
+            let warnings = database.read_lossy().unwrap();
+            assert!(matches!(warnings[..], [crate::ParseWarning { offset: 12, error: ThumbsError::InvalidHeader(_) }]));
+        }
+    }
+
+    #[test]
+    fn parses_single_entries_by_offset() {
+        let bytes = build_database(21, 1, &[(1, b"first"), (2, b"second")]);
+        let header = parse_header(&bytes).unwrap();
+        let (_, entries) = parse_database(&bytes, ReadOptions::default()).unwrap();
+        let second = parse_entry_at(&bytes, &header, entries[1].offset).unwrap();
+        assert_eq!((second.entry_hash(), second.data), (2, &b"second"[..]));
+        assert!(matches!(parse_entry_at(&bytes, &header, entries[1].offset + 1), Err(ThumbsError::BadEntrySignature { .. })));
+        assert!(matches!(parse_entry_at(&bytes, &header, bytes.len() as u64 + 10), Err(ThumbsError::Truncated { .. })));
+        #[cfg(feature = "std")]
+        assert_eq!(crate::Thumbscache::from_bytes(bytes).entry_at_offset(entries[0].offset).unwrap().data, b"first");
+    }
+
+    #[test]
+    fn rejects_entries_smaller_than_their_content() {
+        let mut bytes = build_database(32, 1, &[(1, b"first"), (2, b"second")]);
+        // The size of the first entry, shorter than its own header
+        bytes[24 + 4..24 + 8].copy_from_slice(&8u32.to_le_bytes());
+        assert!(matches!(parse_database(&bytes, ReadOptions::default()), Err(ThumbsError::MalformedEntry { offset: 24 })));
+        // Corrupting any single byte has to end in an error or a parsed database
+        let bytes = build_database(21, 1, &[(1, b"first"), (2, b"second")]);
+        for index in 0..bytes.len() {
+            for value in [0x00, 0x7F, 0xFF] {
+                let mut bytes = bytes.clone();
+                bytes[index] = value;
+                let _ = parse_database(&bytes, ReadOptions::default());
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_data_over_the_limit() {
+        let bytes = build_database(32, 1, &[(1, b"first"), (2, b"second")]);
+        let options = ReadOptions { max_data_size: 5, ..ReadOptions::default() };
+        let mut entries = Vec::new();
+        let header = parse_header(&bytes).unwrap();
+        let result = parse_entries(&bytes, &header, header.first_entry_offset(), options, &mut entries, |_, _, _| true);
+        assert!(matches!(result, Err(ThumbsError::DataTooLarge { data_size: 6, max: 5, .. })));
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[test]
+    fn fails_on_every_truncation_without_panicking() {
+        for version in [20, 21, 32] {
+            let bytes = build_database(version, 1, &[(1, b"first"), (2, b""), (3, b"third")]);
+            for len in 0..bytes.len() {
+                let result = parse_database(&bytes[..len], ReadOptions::default());
+                assert!(matches!(result, Err(ThumbsError::Truncated { .. }) | Err(ThumbsError::TruncatedEntry { .. })) || result.is_ok(), "{} {}", version, len);
+            }
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! Perceptual hashes of thumbnails, enabled with the `phash` feature
+//!
+//! Unlike content hashes, perceptual hashes of two images stay close when one of them was resized or recompressed,
+//! so a cached thumbnail can be matched against the full-size images of a known-image database.
+
+use image::{imageops::FilterType, DynamicImage};
+
+use crate::{CacheEntry, Thumbscache, ThumbsError};
+
+/// Perceptual hash
+///
+/// The average hash (aHash) and difference hash (dHash) of an image, 64 bits each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PerceptualHash {
+    /// Which pixels of the 8x8 grayscale image are brighter than their average
+    pub ahash: u64,
+    /// Which pixels of the 9x8 grayscale image are brighter than their right neighbour
+    pub dhash: u64
+}
+
+impl PerceptualHash {
+    /// Computes the hashes of an image
+    pub fn from_image(image: &DynamicImage) -> PerceptualHash {
+        let small = image.resize_exact(8, 8, FilterType::Triangle).to_luma8();
+        let average = small.pixels().map(|x| x.0[0] as u32).sum::<u32>() / 64;
+        let ahash = small.pixels().enumerate().filter(|(_, x)| x.0[0] as u32 > average).fold(0u64, |hash, (i, _)| hash | 1 << i);
+        let wide = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+        let mut dhash = 0u64;
+        for y in 0..8 {
+            for x in 0..8 {
+                if wide.get_pixel(x, y).0[0] > wide.get_pixel(x + 1, y).0[0] {
+                    dhash |= 1 << (y * 8 + x);
+                }
+            }
+        }
+        PerceptualHash {
+            ahash,
+            dhash
+        }
+    }
+
+    /// Decodes the image and computes its hashes
+    pub fn from_bytes(bytes: &[u8]) -> Result<PerceptualHash, ThumbsError> {
+        let image = image::load_from_memory(bytes).map_err(|x| ThumbsError::ImageError(x.to_string()))?;
+        Ok(PerceptualHash::from_image(&image))
+    }
+
+    /// The number of differing bits of both hashes, from 0 for identical images up to 128
+    pub fn distance(&self, other: &PerceptualHash) -> u32 {
+        (self.ahash ^ other.ahash).count_ones() + (self.dhash ^ other.dhash).count_ones()
+    }
+}
+
+impl CacheEntry {
+    /// Decodes the thumbnail and computes its perceptual hashes
+    pub fn perceptual_hash(&self) -> Result<PerceptualHash, ThumbsError> {
+        Ok(PerceptualHash::from_image(&self.decode_image()?))
+    }
+}
+
+impl Thumbscache {
+    /// Returns the entries whose thumbnail is within the distance threshold of the known hash, closest first.
+    ///
+    /// Entries that can't be decoded are left out.
+    pub fn find_similar(&self, known: &PerceptualHash, threshold: u32) -> Vec<(&CacheEntry, u32)> {
+        let mut similar: Vec<(&CacheEntry, u32)> = self.iter()
+            .filter_map(|entry| Some((entry, entry.perceptual_hash().ok()?.distance(known))))
+            .filter(|(_, distance)| *distance <= threshold)
+            .collect();
+        similar.sort_by_key(|(_, distance)| *distance);
+        similar
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_database;
+    use std::io::Cursor;
+
+    fn gradient(width: u32, height: u32, flipped: bool) -> DynamicImage {
+        DynamicImage::ImageLuma8(image::GrayImage::from_fn(width, height, |x, _| {
+            let value = (x * 255 / (width - 1)) as u8;
+            image::Luma([if flipped { 255 - value } else { value }])
+        }))
+    }
+
+    fn encode(image: &DynamicImage, format: image::ImageFormat) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image.write_to(&mut Cursor::new(&mut bytes), format).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn matches_resized_and_recompressed_images() {
+        // The known image is larger than its cached thumbnails
+        let known = PerceptualHash::from_image(&gradient(256, 256, false));
+        let thumbnail = encode(&gradient(32, 32, false), image::ImageFormat::Jpeg);
+        let other = encode(&gradient(32, 32, true), image::ImageFormat::Png);
+        let mut database = Thumbscache::from_bytes(build_database(32, 1, &[(1, &other), (2, &thumbnail), (3, b"garbage")]));
+        database.read().unwrap();
+        let similar = database.find_similar(&known, 10);
+        assert_eq!(similar.len(), 1);
+        assert_eq!(similar[0].0.entry_hash(), 2);
+        assert!(database.entries()[0].perceptual_hash().unwrap().distance(&known) > 64);
+        assert!(database.entries()[2].perceptual_hash().is_err());
+    }
+}
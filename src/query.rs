@@ -0,0 +1,57 @@
+//! Common queries on the parsed entries
+
+use crate::{CacheEntry, EntryFlags, ImageFormat, Thumbscache};
+
+impl Thumbscache {
+    /// Returns the entries matching the predicate, in file order
+    pub fn find(&self, predicate: impl Fn(&CacheEntry) -> bool) -> Vec<&CacheEntry> {
+        self.iter().filter(|x| predicate(x)).collect()
+    }
+
+    /// Returns the first entry identified by the hash
+    pub fn find_by_hash(&self, entry_hash: u64) -> Option<&CacheEntry> {
+        self.iter().find(|x| x.entry_hash == entry_hash)
+    }
+
+    /// Returns the entries holding at least the given number of bytes of data
+    pub fn filter_by_min_size(&self, bytes: u32) -> Vec<&CacheEntry> {
+        self.find(|x| x.data_size >= bytes)
+    }
+
+    /// Returns the entries whose thumbnail is larger than the resolution of the database, see `CacheEntry::fits_cache_type()`
+    pub fn dimension_mismatches(&self) -> Vec<&CacheEntry> {
+        self.find(|x| x.fits_cache_type() == Some(false))
+    }
+
+    /// Returns the entries whose data is in the given image format
+    pub fn filter_by_format(&self, format: ImageFormat) -> Vec<&CacheEntry> {
+        self.find(|x| x.detect_format() == format)
+    }
+
+    /// Returns the entries whose header stores every flag given, entries written before Windows 8 store none
+    pub fn filter_by_flags(&self, flags: EntryFlags) -> Vec<&CacheEntry> {
+        self.find(|x| x.has_flags(flags))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_database;
+    use std::io::Cursor;
+
+    #[test]
+    fn queries_entries() {
+        let mut database = Thumbscache::from_reader(Cursor::new(build_database(32, 1, &[(1, b"BMsmall"), (2, b"\xFF\xD8\xFFlarger jpeg"), (3, b"BMlarger bitmap")]))).unwrap();
+        database.read().unwrap();
+        assert_eq!(database.find(|x| x.identifier_string.ends_with('2')).len(), 1);
+        assert_eq!(database.find_by_hash(3).unwrap().data, b"BMlarger bitmap");
+        assert!(database.find_by_hash(4).is_none());
+        assert_eq!(database.filter_by_min_size(8).len(), 2);
+        let bitmaps = database.filter_by_format(ImageFormat::Bmp);
+        assert_eq!(bitmaps.len(), 2);
+        assert_eq!(bitmaps[0].entry_hash(), 1);
+        assert_eq!(database.filter_by_flags(EntryFlags::empty()).len(), 3);
+        assert!(database.filter_by_flags(EntryFlags::from_bits_retain(1)).is_empty());
+    }
+}
@@ -0,0 +1,156 @@
+//! Exports that never copy the cached images, for cases where the images can't leave the examination machine
+//!
+//! The metadata report is always written. The image content is either left out or replaced by a placeholder:
+//! a gray bitmap of the same size, or with the `image` feature a copy reduced to a few coarse blocks.
+
+use std::path::Path;
+
+use crate::{bitmap::bmp_from_bgra, write_data, CacheEntry, ExportSummary, NamingStrategy, Thumbscache, ThumbsError};
+
+// The number of blocks along the larger side of a pixelated placeholder
+#[cfg(feature = "image")]
+const PIXELATED_BLOCKS: u32 = 8;
+
+/// What replaces the image content in `Thumbscache::export_redacted()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Redaction {
+    /// Only the metadata report is written
+    MetadataOnly,
+    /// A uniform gray bitmap with the dimensions of the thumbnail
+    SizeOnly,
+    /// The thumbnail reduced to at most 8 blocks along its larger side and scaled back up, written as a PNG
+    #[cfg(feature = "image")]
+    Pixelated
+}
+
+// The largest side of a gray placeholder when the cache type doesn't tell it, the one of the largest thumbnails Windows stores
+const MAX_PLACEHOLDER_DIMENSION: u32 = 2560;
+
+// A uniform gray bitmap of the given size.
+// The size comes from the entry header or the image data, so it is checked before anything gets allocated.
+fn gray_placeholder(width: u32, height: u32, max_dimension: u32) -> Result<Vec<u8>, ThumbsError> {
+    if width == 0 || height == 0 || width > max_dimension || height > max_dimension || width > i32::MAX as u32 || height > i32::MAX as u32 {
+        return Err(ThumbsError::ImageError(format!("the thumbnail claims a size of {}x{}, more than the limit of {}", width, height, max_dimension)));
+    }
+    let too_large = || ThumbsError::ImageError(format!("a placeholder of {}x{} doesn't fit into memory", width, height));
+    let pixel_count = (width as usize).checked_mul(height as usize).ok_or_else(too_large)?;
+    pixel_count.checked_mul(4).ok_or_else(too_large)?;
+    let pixels = [0x80, 0x80, 0x80, 0xFF].repeat(pixel_count);
+    bmp_from_bgra(&pixels, width, height).ok_or_else(too_large)
+}
+
+#[cfg(feature = "image")]
+fn pixelated_placeholder(entry: &CacheEntry) -> Result<Vec<u8>, ThumbsError> {
+    let image = entry.decode_image()?;
+    let (width, height) = (image.width(), image.height());
+    let blocks = image.thumbnail(PIXELATED_BLOCKS, PIXELATED_BLOCKS).resize_exact(width, height, image::imageops::FilterType::Nearest);
+    let mut png = Vec::new();
+    blocks.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png).map_err(|x| ThumbsError::ImageError(x.to_string()))?;
+    Ok(png)
+}
+
+impl CacheEntry {
+    // The placeholder replacing the image, along with its extension. None when only the metadata is written
+    fn placeholder(&self, redaction: Redaction, max_dimension: u32) -> Option<Result<(Vec<u8>, &'static str), ThumbsError>> {
+        match redaction {
+            Redaction::MetadataOnly => None,
+            Redaction::SizeOnly => Some(self.dimensions().or_else(|| self.raw_pixel_dimensions())
+                .ok_or_else(|| ThumbsError::ImageError(String::from("the dimensions of the thumbnail are unknown")))
+                .and_then(|(width, height)| gray_placeholder(width, height, max_dimension))
+                .map(|x| (x, "bmp"))),
+            #[cfg(feature = "image")]
+            Redaction::Pixelated => Some(pixelated_placeholder(self).map(|x| (x, "png")))
+        }
+    }
+}
+
+impl Thumbscache {
+    /// Writes the CSV report of the entries into the directory as metadata.csv, along with a placeholder for every entry that has data.
+    ///
+    /// The data of the entries is never written. Entries whose placeholder can't be made, because their dimensions are unknown or
+    /// they can't be decoded, are reported as failed. The report isn't part of the summary.
+    pub fn export_redacted(&self, dir: impl AsRef<Path>, naming: NamingStrategy, redaction: Redaction) -> Result<ExportSummary, ThumbsError> {
+        std::fs::create_dir_all(&dir).map_err(ThumbsError::IoError)?;
+        write_data(dir.as_ref().join("metadata.csv"), self.to_csv_report().as_bytes())?;
+        let mut summary = ExportSummary::default();
+        let max_dimension = self.cache_type.and_then(|x| x.max_dimension()).unwrap_or(MAX_PLACEHOLDER_DIMENSION);
+        for (index, entry) in self.iter().enumerate() {
+            if entry.data_size == 0 {
+                summary.skipped.push(index);
+                continue;
+            }
+            let entry = match self.loaded_entry(entry) {
+                Ok(entry) => entry,
+                Err(error) => {
+                    summary.failed.push((index, error));
+                    continue;
+                }
+            };
+            match entry.placeholder(redaction, max_dimension) {
+                None => (),
+                Some(Ok((placeholder, extension))) => {
+                    let path = dir.as_ref().join(naming.file_name(index, &entry, extension));
+                    match write_data(&path, &placeholder) {
+                        Ok(()) => summary.written.push(path),
+                        Err(error) => summary.failed.push((index, error))
+                    }
+                },
+                Some(Err(error)) => summary.failed.push((index, error))
+            }
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{build_database, temp_dir};
+
+    #[test]
+    fn never_writes_the_images() {
+        let bmp = bmp_from_bgra(&(0..16).collect::<Vec<u8>>(), 2, 2).unwrap();
+        let mut database = Thumbscache::from_bytes(build_database(32, 3, &[(1, &bmp), (2, b"BMunknown"), (3, b"")]));
+        database.read().unwrap();
+
+        let dir = temp_dir("redacted_metadata");
+        let summary = database.export_redacted(&dir, NamingStrategy::Index, Redaction::MetadataOnly).unwrap();
+        assert!(summary.written.is_empty());
+        assert_eq!(summary.skipped, [2]);
+        assert!(std::fs::read_to_string(dir.join("metadata.csv")).unwrap().contains("0000000000000002"));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        let dir = temp_dir("redacted_size");
+        let summary = database.export_redacted(&dir, NamingStrategy::Index, Redaction::SizeOnly).unwrap();
+        assert_eq!(summary.written, [dir.join("0.bmp")]);
+        assert_eq!(summary.failed.iter().map(|x| x.0).collect::<Vec<_>>(), [1]);
+        let placeholder = std::fs::read(dir.join("0.bmp")).unwrap();
+        assert_eq!(&placeholder[54..], [0x80, 0x80, 0x80, 0xFF].repeat(4));
+
+        #[cfg(feature = "image")]
+        {
+            let dir = temp_dir("redacted_pixelated");
+            let summary = database.export_redacted(&dir, NamingStrategy::Index, Redaction::Pixelated).unwrap();
+            assert_eq!(summary.written, [dir.join("0.png")]);
+            let image = image::open(dir.join("0.png")).unwrap();
+            assert_eq!((image.width(), image.height()), (2, 2));
+        }
+    }
+
+    #[test]
+    fn rejects_forged_dimensions() {
+        let mut png = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 13];
+        png.extend_from_slice(b"IHDR");
+        png.extend_from_slice(&u32::MAX.to_be_bytes());
+        png.extend_from_slice(&u32::MAX.to_be_bytes());
+        let mut database = Thumbscache::from_bytes(build_database(32, 3, &[(1, &png)]));
+        database.read().unwrap();
+
+        let dir = temp_dir("redacted_forged");
+        let summary = database.export_redacted(&dir, NamingStrategy::Index, Redaction::SizeOnly).unwrap();
+        assert!(summary.written.is_empty());
+        assert!(matches!(summary.failed[..], [(0, ThumbsError::ImageError(_))]));
+        assert!(gray_placeholder(300, 200, 256).is_err());
+        assert!(gray_placeholder(256, 200, 256).is_ok());
+    }
+}
@@ -0,0 +1,142 @@
+//! Reading the entries appended to a database file since it was read
+
+use std::{fs::File, io::{ErrorKind, Read, Seek, SeekFrom}};
+
+use crate::{parse::{self, parse_header, HEADER_SIZE}, Backing, CacheEntry, EntryOrigin, ReadOptions, Thumbscache, ThumbsError};
+
+impl Thumbscache {
+    /// Reads the entries appended to the database file since it was read, like `refresh_with_options()` with the default options
+    pub fn refresh(&mut self) -> Result<u32, ThumbsError> {
+        self.refresh_with_options(ReadOptions::default())
+    }
+
+    /// Reads the bytes appended to the database file since it was read and adds the entries they hold after the parsed entries.
+    ///
+    /// Windows only appends entries to a database, so the entries already parsed are kept instead of reading gigabytes again.
+    /// A file that shrank, or whose header or first and last entries changed, was rewritten. It is then read again as a whole
+    /// and its entries replace the parsed ones.
+    /// Returns the number of entries added, fails if the database wasn't opened from a path.
+    pub fn refresh_with_options(&mut self, options: ReadOptions) -> Result<u32, ThumbsError> {
+        let path = self.path.clone().ok_or_else(|| ThumbsError::IoError(std::io::Error::new(ErrorKind::Unsupported, "the database wasn't opened from a path")))?;
+        let mut file = File::open(&path).map_err(|_| ThumbsError::InvalidFile)?;
+        let metadata = file.metadata().map_err(ThumbsError::IoError)?;
+        self.modified = metadata.modified().ok();
+        self.created = metadata.created().ok();
+        let known = self.bytes().len() as u64;
+        let grown = metadata.len() >= known && known >= HEADER_SIZE as u64 && self.prefix_unchanged(&mut file)?;
+        file.rewind().map_err(ThumbsError::IoError)?;
+        match self.stream.get_mut() {
+            // The header changes with every entry appended, the bytes in between are kept
+            Backing::Owned(bytes) if grown => {
+                file.read_exact(&mut bytes[..HEADER_SIZE]).map_err(ThumbsError::IoError)?;
+                file.seek(SeekFrom::Start(known)).map_err(ThumbsError::IoError)?;
+                file.read_to_end(bytes).map_err(ThumbsError::IoError)?;
+            },
+            Backing::Owned(bytes) => {
+                bytes.clear();
+                file.read_to_end(bytes).map_err(ThumbsError::IoError)?;
+            },
+            #[cfg(feature = "mmap")]
+            Backing::Mapped(map) => {
+                // Safety : see open_thumbscache_mmap()
+                *map = std::sync::Arc::new(unsafe { memmap2::Mmap::map(&file) }.map_err(ThumbsError::IoError)?);
+            }
+        }
+        if !grown {
+            #[allow(deprecated)]
+            self.cache_entires.clear();
+            return self.read_with_options(options);
+        }
+        let header = self.header()?;
+        self.windows_version = header.windows_version;
+        self.cache_type = header.cache_type;
+        // Parsing resumes after the last entry of the chain
+        let start = self.entries().iter().filter(|x| x.origin == EntryOrigin::Live).map(|x| x.record_range().end).max().unwrap_or(header.first_entry_offset());
+        let bytes = self.stream.get_ref().as_ref();
+        #[allow(deprecated)]
+        parse::parse_entries(bytes, &header, start, options, &mut self.cache_entires, |_, _, _| true)
+    }
+
+    // Returns true if the file still starts with the bytes that were read, so the entries appended to it can be read on their own.
+    // The header fields that don't change when an entry is appended are compared, along with the headers of the first and last entries,
+    // a database compacted or rewritten in place fails one of these.
+    fn prefix_unchanged(&self, file: &mut File) -> Result<bool, ThumbsError> {
+        let mut raw_header = [0u8; HEADER_SIZE];
+        file.rewind().map_err(ThumbsError::IoError)?;
+        file.read_exact(&mut raw_header).map_err(ThumbsError::IoError)?;
+        let (Ok(old), Ok(new)) = (parse_header(self.bytes()), parse_header(&raw_header)) else {
+            return Ok(false);
+        };
+        if (old.format_version, old.cache_type_number, old.first_entry) != (new.format_version, new.cache_type_number, new.first_entry)
+            || new.first_available_entry < old.first_available_entry {
+            return Ok(false);
+        }
+        let live = || self.iter().filter(|x| x.origin == EntryOrigin::Live);
+        let first = live().min_by_key(|x| x.offset);
+        let last = live().max_by_key(|x| x.record_range().end);
+        for entry in first.into_iter().chain(last) {
+            if !entry_header_unchanged(file, entry)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+// Returns true if the file holds the header of the entry at its offset
+fn entry_header_unchanged(file: &mut File, entry: &CacheEntry) -> Result<bool, ThumbsError> {
+    let mut header = vec![0u8; entry.header.len()];
+    file.seek(SeekFrom::Start(entry.offset)).map_err(ThumbsError::IoError)?;
+    match file.read_exact(&mut header) {
+        Ok(()) => Ok(header == entry.header),
+        Err(error) if error.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(error) => Err(ThumbsError::IoError(error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{build_database, temp_dir};
+
+    #[test]
+    fn parses_appended_entries() {
+        let path = temp_dir("refresh").join("thumbcache_32.db");
+        std::fs::write(&path, build_database(32, 1, &[(1, b"first")])).unwrap();
+        let mut database = Thumbscache::from_path(&path).unwrap();
+        database.read().unwrap();
+        assert_eq!(database.refresh().unwrap(), 0);
+
+        std::fs::write(&path, build_database(32, 1, &[(1, b"first"), (2, b"second"), (3, b"third")])).unwrap();
+        assert_eq!(database.refresh().unwrap(), 2);
+        let hashes: Vec<u64> = database.iter().map(|x| x.entry_hash()).collect();
+        assert_eq!(hashes, [1, 2, 3]);
+        assert_eq!(database.entries()[2].data, b"third");
+        assert_eq!(database.header().unwrap().entry_count, Some(3));
+
+        // A smaller file replaces the entries
+        std::fs::write(&path, build_database(32, 1, &[(4, b"rewritten")])).unwrap();
+        assert_eq!(database.refresh().unwrap(), 1);
+        assert_eq!(database.entries()[0].entry_hash(), 4);
+        assert_eq!(database.len(), 1);
+
+        assert!(matches!(Thumbscache::from_bytes(build_database(32, 1, &[])).refresh(), Err(ThumbsError::IoError(_))));
+    }
+
+    #[test]
+    fn reads_a_database_rewritten_in_place_again() {
+        let path = temp_dir("refresh_rewritten").join("thumbcache_32.db");
+        std::fs::write(&path, build_database(32, 1, &[(1, b"first"), (2, b"second")])).unwrap();
+        let mut database = Thumbscache::from_path(&path).unwrap();
+        database.read().unwrap();
+
+        // Compacted to the same size, then rewritten with a larger entry
+        std::fs::write(&path, build_database(32, 1, &[(3, b"FIRST"), (4, b"SECOND")])).unwrap();
+        assert_eq!(database.refresh().unwrap(), 2);
+        assert_eq!(database.iter().map(|x| x.entry_hash()).collect::<Vec<_>>(), [3, 4]);
+        std::fs::write(&path, build_database(32, 1, &[(3, b"FIRST"), (5, b"a larger second entry"), (6, b"third")])).unwrap();
+        assert_eq!(database.refresh().unwrap(), 3);
+        assert_eq!(database.iter().map(|x| x.entry_hash()).collect::<Vec<_>>(), [3, 5, 6]);
+        assert_eq!(database.entries()[1].data, b"a larger second entry");
+    }
+}
@@ -0,0 +1,363 @@
+//! Registry values controlling the thumbnail cache
+//!
+//! An empty or missing database doesn't mean nothing was viewed when Explorer was told not to cache thumbnails.
+//! The values are read from registry hives (NTUSER.DAT or SOFTWARE) or from .reg files exported with regedit,
+//! both supplied as bytes, and can be attached to a report with `ReportV1::with_registry()`.
+
+use crate::ThumbsError;
+
+/// A registry value affecting which thumbnails get cached
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub enum CacheSetting {
+    /// "Do not cache thumbnails" in the folder options
+    DisableThumbnailCache,
+    /// "Always show icons, never thumbnails" in the folder options, no thumbnail is generated at all
+    IconsOnly,
+    /// The "Turn off caching of thumbnail pictures" group policy
+    NoThumbnailCache,
+    /// The "Turn off the display of thumbnails and only display icons" group policy
+    DisableThumbnails,
+    /// The "Turn off the display of thumbnails and only display icons on network folders" group policy
+    DisableThumbnailsOnNetworkFolders,
+    /// The "Turn off the caching of thumbnails in hidden thumbs.db files" group policy, about network folders
+    DisableThumbsDBOnNetworkFolders
+}
+
+impl CacheSetting {
+    /// Every known setting
+    pub const ALL: [CacheSetting; 6] = [
+        CacheSetting::DisableThumbnailCache,
+        CacheSetting::IconsOnly,
+        CacheSetting::NoThumbnailCache,
+        CacheSetting::DisableThumbnails,
+        CacheSetting::DisableThumbnailsOnNetworkFolders,
+        CacheSetting::DisableThumbsDBOnNetworkFolders
+    ];
+
+    /// The key holding the value, relative to the Software key of the hive
+    pub fn key(&self) -> &'static str {
+        match self {
+            CacheSetting::DisableThumbnailCache | CacheSetting::IconsOnly => "Microsoft\\Windows\\CurrentVersion\\Explorer\\Advanced",
+            CacheSetting::NoThumbnailCache | CacheSetting::DisableThumbnails | CacheSetting::DisableThumbnailsOnNetworkFolders => "Microsoft\\Windows\\CurrentVersion\\Policies\\Explorer",
+            CacheSetting::DisableThumbsDBOnNetworkFolders => "Policies\\Microsoft\\Windows\\Explorer"
+        }
+    }
+
+    /// The name of the value
+    pub fn value_name(&self) -> &'static str {
+        match self {
+            CacheSetting::DisableThumbnailCache => "DisableThumbnailCache",
+            CacheSetting::IconsOnly => "IconsOnly",
+            CacheSetting::NoThumbnailCache => "NoThumbnailCache",
+            CacheSetting::DisableThumbnails => "DisableThumbnails",
+            CacheSetting::DisableThumbnailsOnNetworkFolders => "DisableThumbnailsOnNetworkFolders",
+            CacheSetting::DisableThumbsDBOnNetworkFolders => "DisableThumbsDBOnNetworkFolders"
+        }
+    }
+
+    /// Returns true if the setting only applies to files on network folders
+    pub fn network_only(&self) -> bool {
+        matches!(self, CacheSetting::DisableThumbnailsOnNetworkFolders | CacheSetting::DisableThumbsDBOnNetworkFolders)
+    }
+}
+
+/// The thumbnail cache settings found in a registry hive or a .reg file, see `RegistrySettings::parse()`
+///
+/// Values that aren't set are missing, Windows then uses its defaults and caches thumbnails.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RegistrySettings {
+    values: Vec<(CacheSetting, u32)>
+}
+
+impl RegistrySettings {
+    /// Reads the settings from the bytes of a registry hive (NTUSER.DAT or SOFTWARE) or of a .reg file exported with regedit.
+    ///
+    /// Only DWORD values are read. Fails if the bytes are neither a hive nor a .reg file.
+    pub fn parse(bytes: &[u8]) -> Result<RegistrySettings, ThumbsError> {
+        if bytes.starts_with(b"regf") {
+            return parse_hive(bytes);
+        }
+        let text = if let Some(utf16) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+            String::from_utf16_lossy(&utf16.chunks_exact(2).map(|x| u16::from_le_bytes([x[0], x[1]])).collect::<Vec<u16>>())
+        }else {
+            String::from_utf8_lossy(bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes)).into_owned()
+        };
+        let header = text.lines().next().unwrap_or_default().trim();
+        if header != "Windows Registry Editor Version 5.00" && header != "REGEDIT4" {
+            return Err(ThumbsError::InvalidFile);
+        }
+        Ok(parse_reg_export(&text))
+    }
+
+    /// The data of the value, None if it isn't set
+    pub fn get(&self, setting: CacheSetting) -> Option<u32> {
+        self.values.iter().find(|(x, _)| *x == setting).map(|(_, data)| *data)
+    }
+
+    /// Iterates over the values that are set, in the order of `CacheSetting::ALL`
+    pub fn iter(&self) -> impl Iterator<Item = (CacheSetting, u32)> + '_ {
+        self.values.iter().copied()
+    }
+
+    /// Returns true if no value is set
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    fn enabled(&self, setting: CacheSetting) -> bool {
+        self.get(setting).is_some_and(|x| x != 0)
+    }
+
+    /// Returns true if Explorer was told not to write thumbnails to the cache
+    pub fn caching_disabled(&self) -> bool {
+        self.enabled(CacheSetting::DisableThumbnailCache) || self.enabled(CacheSetting::NoThumbnailCache)
+    }
+
+    /// Returns true if Explorer shows icons instead of thumbnails, so no thumbnail is generated
+    pub fn thumbnails_disabled(&self) -> bool {
+        self.enabled(CacheSetting::IconsOnly) || self.enabled(CacheSetting::DisableThumbnails)
+    }
+
+    /// Returns true if a setting explains a thumbnail cache holding no entries
+    pub fn explains_empty_cache(&self) -> bool {
+        self.caching_disabled() || self.thumbnails_disabled()
+    }
+
+    // Keeps the values in the order of CacheSetting::ALL, the last one read wins
+    fn set(&mut self, setting: CacheSetting, data: u32) {
+        self.values.retain(|(x, _)| *x != setting);
+        self.values.push((setting, data));
+        self.values.sort_by_key(|(x, _)| CacheSetting::ALL.iter().position(|y| y == x));
+    }
+}
+
+// A key path ends with the relative path of the setting, whatever the hive it was exported from
+fn key_matches(path: &str, setting: CacheSetting) -> bool {
+    let path = path.to_ascii_lowercase();
+    let key = format!("\\software\\{}", setting.key().to_ascii_lowercase());
+    path.ends_with(&key) || path == key[1..]
+}
+
+fn parse_reg_export(text: &str) -> RegistrySettings {
+    let mut settings = RegistrySettings::default();
+    let mut key = String::new();
+    for line in text.lines().map(str::trim) {
+        if let Some(path) = line.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+            key = path.to_string();
+            continue;
+        }
+        // "DisableThumbnailCache"=dword:00000001
+        let Some((name, data)) = line.strip_prefix('"').and_then(|x| x.split_once("\"=")) else {
+            continue;
+        };
+        let Some(data) = data.strip_prefix("dword:").and_then(|x| u32::from_str_radix(x.trim(), 16).ok()) else {
+            continue;
+        };
+        for setting in CacheSetting::ALL {
+            if key_matches(&key, setting) && name.eq_ignore_ascii_case(setting.value_name()) {
+                settings.set(setting, data);
+            }
+        }
+    }
+    settings
+}
+
+// The cells of a hive start after the 4096 bytes of its base block
+const HBINS_START: usize = 0x1000;
+const REG_DWORD: u32 = 4;
+
+fn u16_at(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(offset..offset.checked_add(2)?)?.try_into().ok()?))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(offset..offset.checked_add(4)?)?.try_into().ok()?))
+}
+
+// Reads the names of keys and values, which are stored in Latin-1 when the flag is set and in UTF-16 otherwise
+fn cell_name(bytes: &[u8], latin1: bool) -> String {
+    if latin1 {
+        bytes.iter().map(|&x| x as char).collect()
+    }else {
+        String::from_utf16_lossy(&bytes.chunks_exact(2).map(|x| u16::from_le_bytes([x[0], x[1]])).collect::<Vec<u16>>())
+    }
+}
+
+struct Hive<'a> {
+    bytes: &'a [u8]
+}
+
+impl<'a> Hive<'a> {
+    // The content of an allocated cell, whose size is stored negated
+    fn cell(&self, offset: u32) -> Option<&'a [u8]> {
+        let start = HBINS_START.checked_add(offset as usize)?;
+        let size = u32_at(self.bytes, start)? as i32;
+        if size >= 0 {
+            return None;
+        }
+        self.bytes.get(start + 4..start.checked_add(size.unsigned_abs() as usize)?)
+    }
+
+    fn key(&self, offset: u32) -> Option<&'a [u8]> {
+        self.cell(offset).filter(|x| x.starts_with(b"nk"))
+    }
+
+    fn subkey(&self, key: &[u8], name: &str) -> Option<&'a [u8]> {
+        if u32_at(key, 20)? == 0 {
+            return None;
+        }
+        self.find_in_list(u32_at(key, 28)?, name, true)
+    }
+
+    // Subkey lists are lf, lh and li lists, or an ri list of those
+    fn find_in_list(&self, offset: u32, name: &str, follow_index: bool) -> Option<&'a [u8]> {
+        let list = self.cell(offset)?;
+        let stride = match list.get(..2)? {
+            b"lf" | b"lh" => 8,
+            b"li" | b"ri" => 4,
+            _ => return None
+        };
+        for i in 0..u16_at(list, 2)? as usize {
+            let offset = u32_at(list, 4 + i * stride)?;
+            if list.starts_with(b"ri") {
+                if let Some(key) = self.find_in_list(offset, name, false).filter(|_| follow_index) {
+                    return Some(key);
+                }
+                continue;
+            }
+            let Some(key) = self.key(offset) else {
+                continue;
+            };
+            let len = u16_at(key, 72)? as usize;
+            let latin1 = u16_at(key, 2)? & 0x20 != 0;
+            if key.get(76..76 + len).is_some_and(|x| cell_name(x, latin1).eq_ignore_ascii_case(name)) {
+                return Some(key);
+            }
+        }
+        None
+    }
+
+    fn path(&self, mut key: &'a [u8], path: &str) -> Option<&'a [u8]> {
+        for name in path.split('\\') {
+            key = self.subkey(key, name)?;
+        }
+        Some(key)
+    }
+
+    fn dword(&self, key: &[u8], name: &str) -> Option<u32> {
+        let count = u32_at(key, 36)? as usize;
+        let list = self.cell(u32_at(key, 40)?)?;
+        for i in 0..count {
+            let Some(value) = self.cell(u32_at(list, i * 4)?).filter(|x| x.starts_with(b"vk")) else {
+                continue;
+            };
+            let len = u16_at(value, 2)? as usize;
+            let latin1 = u16_at(value, 16)? & 1 != 0;
+            if !value.get(20..20 + len).is_some_and(|x| cell_name(x, latin1).eq_ignore_ascii_case(name)) {
+                continue;
+            }
+            let size = u32_at(value, 4)?;
+            if u32_at(value, 12)? != REG_DWORD || size & 0x7FFF_FFFF != 4 {
+                return None;
+            }
+            // Data of 4 bytes or less is stored in place of its offset
+            return if size & 0x8000_0000 != 0 { u32_at(value, 8) } else { u32_at(self.cell(u32_at(value, 8)?)?, 0) };
+        }
+        None
+    }
+}
+
+fn parse_hive(bytes: &[u8]) -> Result<RegistrySettings, ThumbsError> {
+    let hive = Hive { bytes };
+    let root = u32_at(bytes, 0x24).and_then(|x| hive.key(x)).ok_or(ThumbsError::InvalidFile)?;
+    // The settings are under Software in NTUSER.DAT, the SOFTWARE hive is that key itself
+    let software = hive.subkey(root, "Software").unwrap_or(root);
+    let mut settings = RegistrySettings::default();
+    for setting in CacheSetting::ALL {
+        if let Some(data) = hive.path(software, setting.key()).and_then(|x| hive.dword(x, setting.value_name())) {
+            settings.set(setting, data);
+        }
+    }
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Appends an allocated cell to the hive bin and returns its offset
+    fn cell(hbin: &mut Vec<u8>, content: &[u8]) -> u32 {
+        let offset = hbin.len() as u32;
+        let size = (content.len() + 4).next_multiple_of(8);
+        hbin.extend_from_slice(&(-(size as i32)).to_le_bytes());
+        hbin.extend_from_slice(content);
+        hbin.resize(offset as usize + size, 0);
+        offset
+    }
+
+    fn key(hbin: &mut Vec<u8>, name: &str, subkeys: &[u32], values: &[u32]) -> u32 {
+        let mut list = b"lf".to_vec();
+        list.extend_from_slice(&(subkeys.len() as u16).to_le_bytes());
+        subkeys.iter().for_each(|x| list.extend_from_slice(&[x.to_le_bytes(), [0; 4]].concat()));
+        let list = cell(hbin, &list);
+        let values_list = cell(hbin, &values.iter().flat_map(|x| x.to_le_bytes()).collect::<Vec<u8>>());
+        let mut nk = vec![0; 76];
+        nk[..4].copy_from_slice(b"nk\x20\x00");
+        nk[20..24].copy_from_slice(&(subkeys.len() as u32).to_le_bytes());
+        nk[28..32].copy_from_slice(&list.to_le_bytes());
+        nk[36..40].copy_from_slice(&(values.len() as u32).to_le_bytes());
+        nk[40..44].copy_from_slice(&values_list.to_le_bytes());
+        nk[72..74].copy_from_slice(&(name.len() as u16).to_le_bytes());
+        nk.extend_from_slice(name.as_bytes());
+        cell(hbin, &nk)
+    }
+
+    fn dword(hbin: &mut Vec<u8>, name: &str, data: u32) -> u32 {
+        let mut vk = vec![0; 20];
+        vk[..2].copy_from_slice(b"vk");
+        vk[2..4].copy_from_slice(&(name.len() as u16).to_le_bytes());
+        vk[4..8].copy_from_slice(&0x8000_0004u32.to_le_bytes());
+        vk[8..12].copy_from_slice(&data.to_le_bytes());
+        vk[12..16].copy_from_slice(&REG_DWORD.to_le_bytes());
+        vk[16] = 1;
+        vk.extend_from_slice(name.as_bytes());
+        cell(hbin, &vk)
+    }
+
+    #[test]
+    fn reads_hives_and_exports() {
+        // NTUSER.DAT with Software\Microsoft\Windows\CurrentVersion\Explorer\Advanced\DisableThumbnailCache set
+        let mut hbin = b"hbin".to_vec();
+        hbin.resize(32, 0);
+        let value = dword(&mut hbin, "DisableThumbnailCache", 1);
+        let other = dword(&mut hbin, "IconsOnly", 0);
+        let mut child = key(&mut hbin, "Advanced", &[], &[other, value]);
+        for name in ["Explorer", "CurrentVersion", "Windows", "Microsoft", "Software", "ROOT"] {
+            child = key(&mut hbin, name, &[child], &[]);
+        }
+        let mut hive = b"regf".to_vec();
+        hive.resize(HBINS_START, 0);
+        hive[0x24..0x28].copy_from_slice(&child.to_le_bytes());
+        hive.extend_from_slice(&hbin);
+        let settings = RegistrySettings::parse(&hive).unwrap();
+        assert_eq!(settings.iter().collect::<Vec<_>>(), [(CacheSetting::DisableThumbnailCache, 1), (CacheSetting::IconsOnly, 0)]);
+        assert!(settings.caching_disabled() && !settings.thumbnails_disabled() && settings.explains_empty_cache());
+        for len in 0..hive.len() {
+            let _ = RegistrySettings::parse(&hive[..len]);
+        }
+
+        let export = "Windows Registry Editor Version 5.00\r\n\r\n\
+            [HKEY_CURRENT_USER\\Software\\Policies\\Microsoft\\Windows\\Explorer]\r\n\
+            \"DisableThumbsDBOnNetworkFolders\"=dword:00000001\r\n\
+            \"DisableThumbnailCache\"=dword:00000001\r\n";
+        let utf16: Vec<u8> = [0xFF, 0xFE].into_iter().chain(export.encode_utf16().flat_map(|x| x.to_le_bytes())).collect();
+        let settings = RegistrySettings::parse(&utf16).unwrap();
+        assert_eq!(settings.get(CacheSetting::DisableThumbsDBOnNetworkFolders), Some(1));
+        // The value is only read from the key it belongs to
+        assert_eq!(settings.get(CacheSetting::DisableThumbnailCache), None);
+        assert!(!settings.explains_empty_cache());
+        assert!(RegistrySettings::parse(b"not a registry file").is_err());
+    }
+}
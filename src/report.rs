@@ -0,0 +1,115 @@
+//! Metadata reports for forensic pipelines
+//!
+//! The CSV report is always available, the JSON report requires the `serde` feature.
+
+use crate::Thumbscache;
+
+// Quotes a CSV field when it contains a separator, a quote or a line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    }else {
+        value.to_string()
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct JsonReport<'a> {
+    #[serde(flatten)]
+    database: &'a Thumbscache,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Vec<String>>,
+    #[cfg(feature = "hashes")]
+    hashes: Vec<crate::EntryHashes>
+}
+
+impl Thumbscache {
+    /// Serializes the database metadata and its entries, listed in the "entries" array, into a JSON document.
+    ///
+    /// When include_data is set, the data of every entry is added as a base64 string to the "data" array, in the same order as the entries.
+    /// With the `hashes` feature, the content hashes of every entry are added to the "hashes" array the same way.
+    #[cfg(feature = "serde")]
+    pub fn to_json_report(&self, include_data: bool) -> Result<String, crate::ThumbsError> {
+        use base64::Engine;
+        let data = include_data.then(|| self.iter().map(|x| base64::engine::general_purpose::STANDARD.encode(self.loaded_data(x))).collect());
+        serde_json::to_string_pretty(&JsonReport {
+            database: self,
+            data,
+            #[cfg(feature = "hashes")]
+            hashes: self.iter().map(|x| crate::EntryHashes::of(self.loaded_data(x))).collect()
+        }).map_err(|x| crate::ThumbsError::SerializationError(x.to_string()))
+    }
+
+    /// Writes one CSV line per entry with its metadata, preceded by a header line.
+    ///
+    /// With the `hashes` feature, the md5, sha1 and sha256 columns are added at the end.
+    pub fn to_csv_report(&self) -> String {
+        let mut csv = String::from("identifier_string,entry_hash,offset,size,data_size,data_checksum,header_checksum,format,file_extension,origin");
+        #[cfg(feature = "hashes")]
+        csv.push_str(",md5,sha1,sha256");
+        csv.push('\n');
+        for entry in self.iter() {
+            csv.push_str(&format!("{},{:016x},{},{},{},{:016x},{:016x},{},{},{:?}",
+                csv_field(&entry.identifier_string),
+                entry.entry_hash,
+                entry.offset,
+                entry.size,
+                entry.data_size,
+                entry.data_checksum,
+                entry.header_checksum,
+                crate::ImageFormat::detect(self.loaded_data(entry)).extension(),
+                csv_field(entry.file_extension().unwrap_or("")),
+                entry.origin));
+            #[cfg(feature = "hashes")]
+            {
+                let hashes = crate::EntryHashes::of(self.loaded_data(entry));
+                csv.push_str(&format!(",{},{},{}", hashes.md5, hashes.sha1, hashes.sha256));
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_database;
+    use std::io::Cursor;
+
+    #[test]
+    fn writes_csv_lines() {
+        let mut database = Thumbscache::from_reader(Cursor::new(build_database(32, 1, &[(0xAB, b"BMdata")]))).unwrap();
+        database.read().unwrap();
+        let csv = database.to_csv_report();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].starts_with("00000000000000ab,00000000000000ab,24,94,6,"));
+        #[cfg(not(feature = "hashes"))]
+        assert!(lines[1].ends_with(",bmp,,Live"));
+        #[cfg(feature = "hashes")]
+        {
+            let hashes = database.entries()[0].hashes();
+            assert!(lines[0].ends_with(",file_extension,origin,md5,sha1,sha256"));
+            assert!(lines[1].ends_with(&format!(",bmp,,Live,{},{},{}", hashes.md5, hashes.sha1, hashes.sha256)));
+        }
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn writes_json_with_optional_data() {
+        let mut database = Thumbscache::from_reader(Cursor::new(build_database(32, 1, &[(0xAB, b"BMdata")]))).unwrap();
+        database.read().unwrap();
+        let json: serde_json::Value = serde_json::from_str(&database.to_json_report(false).unwrap()).unwrap();
+        assert_eq!(json["cache_type"], "Res32");
+        assert_eq!(json["entries"][0]["identifier_string"], "00000000000000ab");
+        assert_eq!(json["entries"][0]["origin"], "Live");
+        assert_eq!(json["entries"][0]["offset"], 24);
+        assert!(json.get("cache_entires").is_none());
+        assert!(json.get("data").is_none());
+        let json: serde_json::Value = serde_json::from_str(&database.to_json_report(true).unwrap()).unwrap();
+        assert_eq!(json["data"][0], "Qk1kYXRh");
+    }
+}
@@ -0,0 +1,89 @@
+//! What each kind of database holds
+//!
+//! Only the numbered databases (thumbcache_16.db to thumbcache_2560.db) hold thumbnails bounded by a fixed size.
+//! thumbcache_sr.db, thumbcache_wide.db and thumbcache_wide_alternate.db hold images of other shapes and sizes,
+//! the EXIF and CustomStream databases don't hold images at all, so none of them can be read as a resolution cache.
+
+use crate::{CacheEntry, CacheType, PayloadKind};
+
+/// The content of a database, see `CacheType::role()`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub enum DatabaseRole {
+    /// Thumbnails whose larger side is at most this number of pixels
+    Resolution(u32),
+    /// thumbcache_sr.db, images that aren't bound to one of the fixed resolutions
+    SuperResolution,
+    /// thumbcache_wide.db, thumbnails with a wide aspect ratio instead of fitting a square
+    Wide,
+    /// thumbcache_wide_alternate.db, a second set of wide thumbnails
+    WideAlternate,
+    /// thumbcache_exif.db, the EXIF metadata of the cached images rather than images
+    Exif,
+    /// thumbcache_custom_stream.db, streams whose format depends on the application that wrote them
+    CustomStream
+}
+
+impl DatabaseRole {
+    /// Returns true if the database holds images, whatever their size
+    pub fn holds_images(&self) -> bool {
+        !matches!(self, DatabaseRole::Exif | DatabaseRole::CustomStream)
+    }
+
+    /// The payload expected from entries of this database whose data isn't in a known image format
+    pub fn expected_payload(&self) -> PayloadKind {
+        match self {
+            DatabaseRole::Exif => PayloadKind::Exif,
+            DatabaseRole::CustomStream => PayloadKind::Stream,
+            _ => PayloadKind::Unknown
+        }
+    }
+}
+
+impl CacheType {
+    /// What the databases of this cache type hold
+    pub fn role(&self) -> DatabaseRole {
+        match self {
+            CacheType::SR => DatabaseRole::SuperResolution,
+            CacheType::Wide => DatabaseRole::Wide,
+            CacheType::WideAlternate => DatabaseRole::WideAlternate,
+            CacheType::EXIF => DatabaseRole::Exif,
+            CacheType::CustomStream => DatabaseRole::CustomStream,
+            // Every other cache type is a resolution
+            resolution => DatabaseRole::Resolution(resolution.max_dimension().unwrap_or_default())
+        }
+    }
+}
+
+impl CacheEntry {
+    /// What the database the entry was read from holds, None if its cache type isn't known
+    pub fn database_role(&self) -> Option<DatabaseRole> {
+        self.cache_type.map(|x| x.role())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_database, tests::build_database, ReadOptions};
+
+    #[test]
+    fn tells_special_databases_apart() {
+        assert_eq!(CacheType::Res768.role(), DatabaseRole::Resolution(768));
+        assert_eq!(CacheType::SR.role(), DatabaseRole::SuperResolution);
+        assert!(CacheType::WideAlternate.role().holds_images());
+        assert!(!CacheType::EXIF.role().holds_images());
+        assert_eq!(CacheType::CustomStream.role().expected_payload(), PayloadKind::Stream);
+
+        // Headerless pixels of a 256px square are only rebuilt in a resolution cache, never in the SR or wide caches
+        let pixels = vec![0u8; 256 * 256 * 4];
+        for (cache_type_number, role, rebuilt) in [(4, DatabaseRole::Resolution(256), true), (9, DatabaseRole::SuperResolution, false), (10, DatabaseRole::Wide, false)] {
+            let bytes = build_database(32, cache_type_number, &[(1, &pixels)]);
+            let (_, entries) = parse_database(&bytes, ReadOptions::default()).unwrap();
+            assert_eq!(entries[0].database_role(), Some(role));
+            assert_eq!(entries[0].raw_pixel_dimensions().is_some(), rebuilt);
+            assert_eq!(entries[0].fits_cache_type(), None);
+        }
+    }
+}
@@ -0,0 +1,409 @@
+//! A versioned report of databases and their entries, enabled with the `serde` feature
+//!
+//! The structures of the crate follow its API, which changes between versions. `ReportV1` doesn't:
+//! its fields only hold plain numbers, strings and booleans, and a change to its layout gets a new version (`ReportV2`)
+//! instead, so tools reading the JSON can rely on it across crate versions. Fields may only be added to V1 in minor versions.
+//!
+//! Large reports can be encoded with CBOR (`cbor` feature) or MessagePack (`msgpack` feature) and compressed with zstd (`zstd` feature).
+
+use serde::{Deserialize, Serialize};
+
+use crate::{CacheEntry, DatabaseKind, EntryOrigin, RegistrySettings, ThumbcacheSet, Thumbscache, ThumbsError};
+
+/// The value of `ReportV1::schema_version`
+pub const REPORT_SCHEMA_VERSION: u32 = 1;
+
+/// Version 1 of the report schema, see `Thumbscache::to_report_v1()`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportV1 {
+    /// Always 1, tools should check it before reading the rest
+    pub schema_version: u32,
+    /// The crate that wrote the report and its version, "thumbscache 1.2.0" for instance
+    pub generator: String,
+    pub databases: Vec<DatabaseReportV1>,
+    /// The registry values controlling the thumbnail cache, added with `with_registry()`, missing from reports without them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub registry: Vec<RegistryValueV1>
+}
+
+/// A registry value of a `ReportV1`, see `RegistrySettings`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegistryValueV1 {
+    /// The key holding the value, relative to the root of the hive, "Software\Policies\Microsoft\Windows\Explorer" for instance
+    pub key: String,
+    pub name: String,
+    pub data: u32
+}
+
+/// A database of a `ReportV1`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DatabaseReportV1 {
+    /// The file the database was read from, None when it was read from memory
+    pub path: Option<String>,
+    /// "thumbnail" for thumbcache_*.db, "icon" for iconcache_*.db
+    pub kind: String,
+    /// The format version of the header, from 20 (Windows Vista) to 32 (Windows 10 and later)
+    pub format_version: Option<u32>,
+    /// The cache type as named in database file names, "256" or "wide_alternate" for instance
+    pub cache_type: Option<String>,
+    /// Size of the database in bytes
+    pub size: u64,
+    pub entries: Vec<EntryReportV1>
+}
+
+/// An entry of a `DatabaseReportV1`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryReportV1 {
+    /// Offset of the entry from the start of the database
+    pub offset: u64,
+    /// Size of the whole entry in bytes
+    pub size: u32,
+    /// The entry hash as 16 lowercase hexadecimal digits
+    pub entry_hash: String,
+    pub identifier: String,
+    /// The cleaned name of the cached item held by Windows Vista identifiers, missing for hashes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_name: Option<String>,
+    pub data_size: u32,
+    /// The data checksum stored in the entry header, as 16 lowercase hexadecimal digits
+    pub data_checksum: String,
+    /// The header checksum stored in the entry header, as 16 lowercase hexadecimal digits
+    pub header_checksum: String,
+    /// Whether the data matches its checksum
+    pub data_valid: bool,
+    /// Whether the entry header matches its checksum
+    pub header_valid: bool,
+    /// "live" for entries of the chain, "carved" for entries found by scanning and "unallocated" for entries left past the live ones
+    pub origin: String,
+    /// The extension of the payload: "bmp", "jpg", "png", "exif", "stream" or "bin"
+    pub payload: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// The flags stored in the entry header by Windows 8 and later, missing for earlier versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flags: Option<u32>,
+    /// The data of the entry, only present in reports made with `ReportV1::with_data()`.
+    /// It is a base64 string in JSON and a byte string in CBOR and MessagePack
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "entry_data")]
+    pub data: Option<Vec<u8>>
+}
+
+// The data of the entries, base64 encoded for text formats and kept as bytes for binary ones
+mod entry_data {
+    use core::fmt;
+
+    use base64::Engine;
+    use serde::{de, Deserializer, Serialize, Serializer};
+
+    struct Bytes<'a>(&'a [u8]);
+
+    impl Serialize for Bytes<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+
+    pub fn serialize<S: Serializer>(data: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match data {
+            Some(data) if serializer.is_human_readable() => serializer.serialize_some(&base64::engine::general_purpose::STANDARD.encode(data)),
+            Some(data) => serializer.serialize_some(&Bytes(data)),
+            None => serializer.serialize_none()
+        }
+    }
+
+    struct DataVisitor;
+
+    impl<'de> de::Visitor<'de> for DataVisitor {
+        type Value = Option<Vec<u8>>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a base64 string or a byte string")
+        }
+
+        fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+            deserializer.deserialize_any(self)
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+            base64::engine::general_purpose::STANDARD.decode(value).map(Some).map_err(E::custom)
+        }
+
+        fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<Self::Value, E> {
+            Ok(Some(value.to_vec()))
+        }
+
+        fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut data = Vec::new();
+            while let Some(byte) = seq.next_element()? {
+                data.push(byte);
+            }
+            Ok(Some(data))
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error> {
+        deserializer.deserialize_option(DataVisitor)
+    }
+}
+
+fn serialization_error(error: impl core::fmt::Display) -> ThumbsError {
+    ThumbsError::SerializationError(error.to_string())
+}
+
+/// How `ReportV1::encode()` writes a report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportEncoding {
+    /// Pretty-printed JSON, the same as `ReportV1::to_json()`
+    Json,
+    /// CBOR (RFC 8949)
+    #[cfg(feature = "cbor")]
+    Cbor,
+    /// MessagePack, structures are written as maps keyed by field name
+    #[cfg(feature = "msgpack")]
+    MessagePack
+}
+
+impl ReportEncoding {
+    /// The usual extension of files in this encoding
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReportEncoding::Json => "json",
+            #[cfg(feature = "cbor")]
+            ReportEncoding::Cbor => "cbor",
+            #[cfg(feature = "msgpack")]
+            ReportEncoding::MessagePack => "msgpack"
+        }
+    }
+}
+
+impl EntryReportV1 {
+    fn new(database: &Thumbscache, entry: &CacheEntry) -> EntryReportV1 {
+        // Entries read without their data are described from the data in the database
+        let mut loaded = None;
+        if !entry.is_data_loaded() {
+            let mut copy = entry.clone();
+            if copy.load_data(database).is_ok() {
+                loaded = Some(copy);
+            }
+        }
+        let entry = loaded.as_ref().unwrap_or(entry);
+        let status = entry.verify();
+        let dimensions = entry.dimensions();
+        EntryReportV1 {
+            offset: entry.offset(),
+            size: entry.size,
+            entry_hash: entry.entry_hash_hex(),
+            identifier: entry.identifier_string.clone(),
+            original_name: entry.original_name(),
+            data_size: entry.data_size,
+            data_checksum: format!("{:016x}", entry.data_checksum),
+            header_checksum: format!("{:016x}", entry.header_checksum),
+            data_valid: status.data_valid,
+            header_valid: status.header_valid,
+            origin: String::from(match entry.origin {
+                EntryOrigin::Live => "live",
+                EntryOrigin::Carved => "carved",
+                EntryOrigin::Unallocated => "unallocated"
+            }),
+            payload: String::from(entry.payload_kind().extension()),
+            width: dimensions.map(|x| x.0),
+            height: dimensions.map(|x| x.1),
+            flags: entry.flags().map(|x| x.bits()),
+            data: None
+        }
+    }
+}
+
+impl DatabaseReportV1 {
+    fn new(database: &Thumbscache) -> DatabaseReportV1 {
+        #[cfg(feature = "fs")]
+        let path = database.path.as_ref().map(|x| x.to_string_lossy().into_owned());
+        #[cfg(not(feature = "fs"))]
+        let path = None;
+        DatabaseReportV1 {
+            path,
+            kind: String::from(match database.kind {
+                DatabaseKind::Thumbnail => "thumbnail",
+                DatabaseKind::IconCache => "icon"
+            }),
+            format_version: database.windows_version.map(|x| x.format_version()),
+            cache_type: database.cache_type.map(|x| String::from(x.name())),
+            size: database.bytes().len() as u64,
+            entries: database.iter().map(|entry| EntryReportV1::new(database, entry)).collect()
+        }
+    }
+}
+
+impl ReportV1 {
+    /// Reports the parsed entries of the databases, in the given order
+    pub fn new<'a>(databases: impl IntoIterator<Item = &'a Thumbscache>) -> ReportV1 {
+        ReportV1 {
+            schema_version: REPORT_SCHEMA_VERSION,
+            generator: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            databases: databases.into_iter().map(DatabaseReportV1::new).collect(),
+            registry: Vec::new()
+        }
+    }
+
+    /// Adds the registry values that were set, telling whether an empty cache means nothing was viewed
+    pub fn with_registry(mut self, settings: &RegistrySettings) -> ReportV1 {
+        self.registry = settings.iter().map(|(setting, data)| RegistryValueV1 {
+            key: format!("Software\\{}", setting.key()),
+            name: setting.value_name().to_string(),
+            data
+        }).collect();
+        self
+    }
+
+    /// Reports the databases like `new()` does, adding the data of every entry
+    pub fn with_data<'a>(databases: impl IntoIterator<Item = &'a Thumbscache>) -> ReportV1 {
+        let databases: Vec<&Thumbscache> = databases.into_iter().collect();
+        let mut report = ReportV1::new(databases.iter().copied());
+        for (database, database_report) in databases.iter().zip(&mut report.databases) {
+            for (entry, entry_report) in database.iter().zip(&mut database_report.entries) {
+                let data = if entry.is_data_loaded() { Some(entry.data.as_slice()) } else { database.entry_data(entry) };
+                entry_report.data = data.map(|x| x.to_vec());
+            }
+        }
+        report
+    }
+
+    /// Serializes the report into a JSON document
+    pub fn to_json(&self) -> Result<String, ThumbsError> {
+        serde_json::to_string_pretty(self).map_err(serialization_error)
+    }
+
+    /// Reads a report written by `to_json()`, failing if it isn't a version 1 report
+    pub fn from_json(json: &str) -> Result<ReportV1, ThumbsError> {
+        ReportV1::decode(json.as_bytes(), ReportEncoding::Json)
+    }
+
+    /// Serializes the report in the given encoding
+    pub fn encode(&self, encoding: ReportEncoding) -> Result<Vec<u8>, ThumbsError> {
+        match encoding {
+            ReportEncoding::Json => self.to_json().map(String::into_bytes),
+            #[cfg(feature = "cbor")]
+            ReportEncoding::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(self, &mut bytes).map_err(serialization_error)?;
+                Ok(bytes)
+            },
+            #[cfg(feature = "msgpack")]
+            ReportEncoding::MessagePack => rmp_serde::to_vec_named(self).map_err(serialization_error)
+        }
+    }
+
+    /// Reads a report written by `encode()` in the same encoding, failing if it isn't a version 1 report
+    pub fn decode(bytes: &[u8], encoding: ReportEncoding) -> Result<ReportV1, ThumbsError> {
+        let report: ReportV1 = match encoding {
+            ReportEncoding::Json => serde_json::from_slice(bytes).map_err(serialization_error)?,
+            #[cfg(feature = "cbor")]
+            ReportEncoding::Cbor => ciborium::from_reader(bytes).map_err(serialization_error)?,
+            #[cfg(feature = "msgpack")]
+            ReportEncoding::MessagePack => rmp_serde::from_slice(bytes).map_err(serialization_error)?
+        };
+        if report.schema_version != REPORT_SCHEMA_VERSION {
+            return Err(ThumbsError::SerializationError(format!("unsupported report schema version {}", report.schema_version)));
+        }
+        Ok(report)
+    }
+
+    /// Serializes the report in the given encoding and compresses it with zstd at the given level, from 1 to 22 (0 picks the default level)
+    #[cfg(feature = "zstd")]
+    pub fn encode_zstd(&self, encoding: ReportEncoding, level: i32) -> Result<Vec<u8>, ThumbsError> {
+        zstd::encode_all(self.encode(encoding)?.as_slice(), level).map_err(ThumbsError::IoError)
+    }
+
+    /// Decompresses and reads a report written by `encode_zstd()` in the same encoding
+    #[cfg(feature = "zstd")]
+    pub fn decode_zstd(bytes: &[u8], encoding: ReportEncoding) -> Result<ReportV1, ThumbsError> {
+        ReportV1::decode(&zstd::decode_all(bytes).map_err(ThumbsError::IoError)?, encoding)
+    }
+}
+
+impl Thumbscache {
+    /// Reports the database and its parsed entries in the stable `ReportV1` schema
+    pub fn to_report_v1(&self) -> ReportV1 {
+        ReportV1::new([self])
+    }
+}
+
+impl ThumbcacheSet {
+    /// Reports every database of the set in the stable `ReportV1` schema
+    pub fn to_report_v1(&self) -> ReportV1 {
+        ReportV1::new(self.databases())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_database;
+
+    #[test]
+    fn reports_in_a_stable_schema() {
+        let mut bytes = build_database(32, 4, &[(0xAB, b"BMfirst"), (0xCD, b"\xFF\xD8\xFFsecond")]);
+        bytes[24 + 56 + 32] ^= 0xFF;
+        let mut database = Thumbscache::from_bytes(bytes);
+        database.read_with_options(crate::ReadOptions::lazy()).unwrap();
+        database.entries_mut()[1].origin = EntryOrigin::Carved;
+        let report = database.to_report_v1();
+        assert_eq!(report.schema_version, 1);
+        let database_report = &report.databases[0];
+        assert_eq!((database_report.format_version, database_report.cache_type.as_deref(), database_report.kind.as_str()), (Some(32), Some("256"), "thumbnail"));
+        let entries = &database_report.entries;
+        assert_eq!((entries[0].entry_hash.as_str(), entries[0].data_valid, entries[0].header_valid), ("00000000000000ab", false, true));
+        assert_eq!((entries[1].origin.as_str(), entries[1].payload.as_str(), entries[1].data_valid), ("carved", "jpg", true));
+
+        let json = report.to_json().unwrap();
+        assert!(json.contains("\"schema_version\": 1"));
+        assert_eq!(ReportV1::from_json(&json).unwrap(), report);
+        assert!(ReportV1::from_json(&json.replace("\"schema_version\": 1", "\"schema_version\": 2")).is_err());
+        assert!(!json.contains("registry"));
+
+        let export = b"REGEDIT4\n[HKEY_CURRENT_USER\\Software\\Microsoft\\Windows\\CurrentVersion\\Policies\\Explorer]\n\"NoThumbnailCache\"=dword:00000001\n";
+        let report = report.with_registry(&RegistrySettings::parse(export).unwrap());
+        assert_eq!(report.registry[0].key, "Software\\Microsoft\\Windows\\CurrentVersion\\Policies\\Explorer");
+        assert_eq!(ReportV1::from_json(&report.to_json().unwrap()).unwrap(), report);
+    }
+
+    #[test]
+    fn encodes_the_data_of_the_entries() {
+        let mut database = Thumbscache::from_bytes(build_database(32, 4, &[(0xAB, b"BMfirst"), (0xCD, b"")]));
+        database.read_with_options(crate::ReadOptions::lazy()).unwrap();
+        let report = ReportV1::with_data([&database]);
+        assert_eq!(report.databases[0].entries[0].data.as_deref(), Some(&b"BMfirst"[..]));
+        let json = report.to_json().unwrap();
+        assert!(json.contains("\"data\": \"Qk1maXJzdA==\""));
+        assert_eq!(ReportV1::from_json(&json).unwrap(), report);
+        assert!(!database.to_report_v1().to_json().unwrap().contains("\"data\""));
+
+        #[cfg(feature = "cbor")]
+        {
+            let cbor = report.encode(ReportEncoding::Cbor).unwrap();
+            // The data is a byte string, not base64
+            assert!(cbor.windows(7).any(|x| x == b"BMfirst"));
+            assert_eq!(ReportV1::decode(&cbor, ReportEncoding::Cbor).unwrap(), report);
+        }
+        #[cfg(feature = "msgpack")]
+        {
+            let msgpack = report.encode(ReportEncoding::MessagePack).unwrap();
+            assert!(msgpack.windows(7).any(|x| x == b"BMfirst"));
+            assert_eq!(ReportV1::decode(&msgpack, ReportEncoding::MessagePack).unwrap(), report);
+        }
+        #[cfg(feature = "zstd")]
+        {
+            let compressed = report.encode_zstd(ReportEncoding::Json, 0).unwrap();
+            assert!(compressed.len() < json.len());
+            assert_eq!(ReportV1::decode_zstd(&compressed, ReportEncoding::Json).unwrap(), report);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Correlation with the Windows Search database (Windows.edb)
+//!
+//! The SystemIndex_PropertyStore table records the ThumbnailCacheId of every indexed file, which is the entry hash of its thumbnail.
+//! The rows have to be extracted from the database beforehand, with esedbexport for instance.
+
+use std::collections::HashMap;
+
+use crate::{CacheEntry, ThumbcacheSet, Thumbscache};
+
+/// A row extracted from Windows.edb, pairing a ThumbnailCacheId with the path of the indexed file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchRow {
+    pub thumbnail_cache_id: u64,
+    pub path: String
+}
+
+impl SearchRow {
+    /// Parses the ThumbnailCacheId column as exported, 8 bytes in hexadecimal stored in little-endian byte order.
+    ///
+    /// Returns None if the column isn't 16 hexadecimal digits.
+    pub fn from_exported(thumbnail_cache_id: &str, path: impl Into<String>) -> Option<SearchRow> {
+        let thumbnail_cache_id = thumbnail_cache_id.trim();
+        if thumbnail_cache_id.len() != 16 {
+            return None;
+        }
+        let mut bytes = [0u8; 8];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(thumbnail_cache_id.get(i * 2..i * 2 + 2)?, 16).ok()?;
+        }
+        Some(SearchRow {
+            thumbnail_cache_id: u64::from_le_bytes(bytes),
+            path: path.into()
+        })
+    }
+}
+
+/// A cache entry along with the path of the file it's the thumbnail of
+#[derive(Debug, Clone)]
+pub struct ResolvedEntry<'a> {
+    pub path: String,
+    pub entry: &'a CacheEntry
+}
+
+// Indexes the rows by ThumbnailCacheId, a file indexed several times keeps every path
+fn paths_by_id(rows: &[SearchRow]) -> HashMap<u64, Vec<&str>> {
+    let mut paths: HashMap<u64, Vec<&str>> = HashMap::new();
+    for row in rows {
+        paths.entry(row.thumbnail_cache_id).or_default().push(&row.path);
+    }
+    paths
+}
+
+fn resolve<'a>(entries: impl Iterator<Item = &'a CacheEntry>, paths: &HashMap<u64, Vec<&str>>) -> Vec<ResolvedEntry<'a>> {
+    let mut resolved = Vec::new();
+    for entry in entries {
+        for path in paths.get(&entry.entry_hash).into_iter().flatten() {
+            resolved.push(ResolvedEntry {
+                path: path.to_string(),
+                entry
+            });
+        }
+    }
+    resolved
+}
+
+impl Thumbscache {
+    /// Maps the entries back to the paths of their original files, using the rows extracted from Windows.edb.
+    ///
+    /// Entries without a matching row are left out.
+    pub fn resolve_paths(&self, rows: &[SearchRow]) -> Vec<ResolvedEntry<'_>> {
+        resolve(self.iter(), &paths_by_id(rows))
+    }
+}
+
+impl ThumbcacheSet {
+    /// Maps the entries of every database back to the paths of their original files, see `Thumbscache::resolve_paths()`
+    pub fn resolve_paths(&self, rows: &[SearchRow]) -> Vec<ResolvedEntry<'_>> {
+        resolve(self.databases().iter().flat_map(|x| x.iter()), &paths_by_id(rows))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_database;
+    use std::io::Cursor;
+
+    #[test]
+    fn resolves_paths_of_entries() {
+        let mut database = Thumbscache::from_reader(Cursor::new(build_database(32, 1, &[(0x1122334455667788, b"first"), (2, b"second")]))).unwrap();
+        database.read().unwrap();
+        let rows = [
+            SearchRow::from_exported("8877665544332211", "C:\\Users\\z\\Pictures\\cat.jpg").unwrap(),
+            SearchRow { thumbnail_cache_id: 3, path: "C:\\unrelated.png".to_string() }
+        ];
+        assert!(SearchRow::from_exported("88776655", "C:\\short").is_none());
+        let resolved = database.resolve_paths(&rows);
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].path, "C:\\Users\\z\\Pictures\\cat.jpg");
+        assert_eq!(resolved[0].entry.data, b"first");
+    }
+}
@@ -0,0 +1,217 @@
+//! Loading every database of an Explorer cache directory at once
+
+use std::collections::HashMap;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use crate::{checksum::crc64, CacheEntry, CacheType, ThumbcacheIndex, Thumbscache};
+#[cfg(feature = "fs")]
+use crate::{DatabaseKind, ThumbsError};
+
+/// Thumbcache set
+///
+/// Represents all the thumbcache_*.db databases of a directory together with their thumbcache_idx.db index file.
+/// The iconcache_*.db databases of the same directory can be loaded as a set too, see `ThumbcacheSet::open_kind()`.
+///
+/// ```no_run
+/// use thumbscache::ThumbcacheSet;
+/// let set = ThumbcacheSet::open("C:\\Users\\z\\AppData\\Local\\Microsoft\\Windows\\Explorer").unwrap();
+/// for (cache_type, entry) in set.lookup(0x1f2b3c4d5e6f7a8b) {
+///     println!("{:?} : {} bytes", cache_type, entry.data_size);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ThumbcacheSet {
+    pub index: Option<ThumbcacheIndex>,
+    databases: Vec<Thumbscache>
+}
+
+impl ThumbcacheSet {
+    /// Groups databases that were already parsed, along with their index file if there is one
+    pub fn from_databases(index: Option<ThumbcacheIndex>, databases: Vec<Thumbscache>) -> ThumbcacheSet {
+        ThumbcacheSet {
+            index,
+            databases
+        }
+    }
+
+    /// Opens and parses the index file and every sibling thumbnail database found in the directory.
+    ///
+    /// A missing index file is not an error, lookups fall back to scanning the databases in that case.
+    #[cfg(feature = "fs")]
+    pub fn open(dir: impl AsRef<Path>) -> Result<ThumbcacheSet, ThumbsError> {
+        ThumbcacheSet::open_kind(dir, DatabaseKind::Thumbnail)
+    }
+
+    /// Opens and parses the index file and every sibling database of the given kind found in the directory.
+    #[cfg(feature = "fs")]
+    pub fn open_kind(dir: impl AsRef<Path>, kind: DatabaseKind) -> Result<ThumbcacheSet, ThumbsError> {
+        let mut paths = Vec::new();
+        for dir_entry in std::fs::read_dir(dir).map_err(ThumbsError::IoError)? {
+            let path = dir_entry.map_err(ThumbsError::IoError)?.path();
+            let file_name = path.file_name().map(|x| x.to_string_lossy().to_lowercase()).unwrap_or_default();
+            if file_name.starts_with(kind.file_prefix()) && file_name.ends_with(".db") {
+                paths.push((file_name, path));
+            }
+        }
+        paths.sort();
+        let index_name = format!("{}idx.db", kind.file_prefix());
+        let mut index = None;
+        let mut databases = Vec::new();
+        for (file_name, path) in paths {
+            if file_name == index_name {
+                index = Some(ThumbcacheIndex::open(&path)?);
+            }else {
+                span!(info_span, "database", path = %path.display());
+                let mut database = Thumbscache::from_path(&path)?;
+                database.read()?;
+                databases.push(database);
+            }
+        }
+        Ok(ThumbcacheSet {
+            index,
+            databases
+        })
+    }
+
+    /// Returns the parsed databases
+    pub fn databases(&self) -> &[Thumbscache] {
+        &self.databases
+    }
+
+    /// Returns the database of the given cache type
+    pub fn database(&self, cache_type: CacheType) -> Option<&Thumbscache> {
+        self.databases.iter().find(|x| x.cache_type == Some(cache_type))
+    }
+
+    /// Looks up every cached resolution of the given entry hash.
+    ///
+    /// When the index knows the hash, only the databases it points to are searched, using the recorded offsets.
+    /// Databases with an unknown cache type are skipped.
+    pub fn lookup(&self, entry_hash: u64) -> Vec<(CacheType, &CacheEntry)> {
+        let index_entry = self.index.as_ref().and_then(|x| x.get(entry_hash));
+        let mut found = Vec::new();
+        for database in &self.databases {
+            let Some(cache_type) = database.cache_type else {
+                continue;
+            };
+            let entry = match index_entry {
+                Some(index_entry) => index_entry.offset_for(cache_type).and_then(|offset| {
+                    // Entries are stored in file order, so the offset can be binary searched
+                    database.entries().binary_search_by_key(&(offset as u64), |x| x.offset).ok()
+                        .map(|i| &database.entries()[i])
+                        .filter(|x| x.entry_hash == entry_hash)
+                        .or_else(|| database.iter().find(|x| x.entry_hash == entry_hash))
+                }),
+                None => database.iter().find(|x| x.entry_hash == entry_hash)
+            };
+            if let Some(entry) = entry {
+                found.push((cache_type, entry));
+            }
+        }
+        found
+    }
+
+    /// Groups the entries of every database that hold identical data, in the order they were first found.
+    ///
+    /// Only groups of at least two entries are returned. Entries without data and databases with an unknown cache type are skipped.
+    pub fn dedupe(&self) -> Vec<Vec<(CacheType, &CacheEntry)>> {
+        let mut clusters: Vec<Vec<(CacheType, &CacheEntry)>> = Vec::new();
+        // The data held by the entries of every cluster
+        let mut cluster_data: Vec<&[u8]> = Vec::new();
+        // Clusters sharing the checksum of their data, the data is compared too in case of a collision
+        let mut by_checksum: HashMap<u64, Vec<usize>> = HashMap::new();
+        for database in &self.databases {
+            let Some(cache_type) = database.cache_type else {
+                continue;
+            };
+            for entry in database.iter().filter(|x| x.data_size != 0) {
+                // Entries read without their data are compared with the data in the database
+                let data = database.loaded_data(entry);
+                let candidates = by_checksum.entry(crc64(data)).or_default();
+                match candidates.iter().find(|&&i| cluster_data[i] == data) {
+                    Some(&i) => clusters[i].push((cache_type, entry)),
+                    None => {
+                        candidates.push(clusters.len());
+                        clusters.push(vec![(cache_type, entry)]);
+                        cluster_data.push(data);
+                    }
+                }
+            }
+        }
+        clusters.retain(|x| x.len() > 1);
+        clusters
+    }
+}
+
+#[cfg(all(test, feature = "fs"))]
+mod tests {
+    use super::*;
+    use crate::tests::{build_database, build_index, temp_dir};
+
+    #[test]
+    fn looks_up_hash_across_databases() {
+        let dir = temp_dir("set_lookup");
+        std::fs::write(dir.join("thumbcache_16.db"), build_database(32, 0, &[(1, b"small"), (2, b"other")])).unwrap();
+        std::fs::write(dir.join("thumbcache_32.db"), build_database(32, 1, &[(2, b"medium")])).unwrap();
+        let set = ThumbcacheSet::open(&dir).unwrap();
+        assert!(set.index.is_none());
+        let found = set.lookup(2);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].0, CacheType::Res16);
+        assert_eq!(found[0].1.data, b"other");
+        assert_eq!(found[1].1.data, b"medium");
+    }
+
+    #[test]
+    fn opens_icon_caches_separately() {
+        let dir = temp_dir("set_icons");
+        std::fs::write(dir.join("thumbcache_16.db"), build_database(32, 0, &[(1, b"thumbnail")])).unwrap();
+        std::fs::write(dir.join("iconcache_16.db"), build_database(32, 0, &[(1, b"icon")])).unwrap();
+        let set = ThumbcacheSet::open_kind(&dir, DatabaseKind::IconCache).unwrap();
+        assert_eq!(set.databases().len(), 1);
+        assert_eq!(set.databases()[0].kind, DatabaseKind::IconCache);
+        assert_eq!(set.lookup(1)[0].1.data, b"icon");
+    }
+
+    #[test]
+    fn follows_index_offsets() {
+        let dir = temp_dir("set_index");
+        let database = build_database(32, 0, &[(1, b"small"), (2, b"other")]);
+        std::fs::write(dir.join("thumbcache_16.db"), &database).unwrap();
+        std::fs::write(dir.join("thumbcache_32.db"), build_database(32, 1, &[(2, b"medium")])).unwrap();
+        // The index claims the item is only stored in the 16px database
+        let second_entry_offset = 24 + (56 + 32 + 5) as u32;
+        std::fs::write(dir.join("thumbcache_idx.db"), build_index(32, &[(2, &[(0, second_entry_offset)])])).unwrap();
+        let set = ThumbcacheSet::open(&dir).unwrap();
+        let found = set.lookup(2);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].1.data, b"other");
+    }
+
+    #[test]
+    fn groups_identical_data() {
+        let dir = temp_dir("set_dedupe");
+        std::fs::write(dir.join("thumbcache_16.db"), build_database(32, 0, &[(1, b"same"), (2, b"unique"), (3, b"")])).unwrap();
+        std::fs::write(dir.join("thumbcache_32.db"), build_database(32, 1, &[(1, b"same"), (4, b"")])).unwrap();
+        std::fs::write(dir.join("thumbcache_48.db"), build_database(32, 2, &[(5, b"same")])).unwrap();
+        let set = ThumbcacheSet::open(&dir).unwrap();
+        let clusters = set.dedupe();
+        assert_eq!(clusters.len(), 1);
+        let cache_types: Vec<CacheType> = clusters[0].iter().map(|x| x.0).collect();
+        assert_eq!(cache_types, [CacheType::Res16, CacheType::Res32, CacheType::Res48]);
+        assert_eq!(clusters[0][2].1.entry_hash(), 5);
+    }
+
+    #[test]
+    fn groups_identical_data_of_lazily_read_databases() {
+        let mut small = Thumbscache::from_bytes(build_database(32, 0, &[(1, b"same"), (2, b"unique"), (3, b"")]));
+        small.read_with_options(crate::ReadOptions::lazy()).unwrap();
+        let mut large = Thumbscache::from_bytes(build_database(32, 1, &[(1, b"same"), (4, b"")]));
+        large.read_with_options(crate::ReadOptions::lazy()).unwrap();
+        let set = ThumbcacheSet::from_databases(None, vec![small, large]);
+        let clusters = set.dedupe();
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].iter().map(|x| (x.0, x.1.entry_hash())).collect::<Vec<_>>(), [(CacheType::Res16, 1), (CacheType::Res32, 1)]);
+    }
+}
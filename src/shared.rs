@@ -0,0 +1,183 @@
+//! Read-only parsed databases, shared between threads
+
+use std::{collections::HashMap, slice::Iter, time::SystemTime};
+
+use crate::{Backing, CacheEntry, CacheType, DatabaseKind, ReadOptions, Thumbscache, ThumbsError, WindowsVersion};
+
+/// A parsed database that can no longer be read into, see `Thumbscache::into_parsed()`
+///
+/// It only hands out shared references, so it is `Send + Sync` and a single instance can serve thumbnails
+/// to several threads from behind an `Arc`.
+///
+/// ```
+/// use std::sync::Arc;
+/// use thumbscache::ParsedCache;
+/// # let bytes = {
+/// #     let mut writer = thumbscache::ThumbscacheWriter::new(thumbscache::WindowsVersion::Win10, thumbscache::CacheType::Res256).unwrap();
+/// #     writer.append(1, b"BM...");
+/// #     writer.into_bytes()
+/// # };
+/// let cache = Arc::new(ParsedCache::from_bytes(bytes).unwrap());
+/// let worker = {
+///     let cache = Arc::clone(&cache);
+///     std::thread::spawn(move || cache.data_by_hash(1).map(|x| x.to_vec()))
+/// };
+/// assert_eq!(worker.join().unwrap().as_deref(), Some(&b"BM..."[..]));
+/// ```
+pub struct ParsedCache {
+    bytes: Backing,
+    windows_version: Option<WindowsVersion>,
+    cache_type: Option<CacheType>,
+    kind: DatabaseKind,
+    modified: Option<SystemTime>,
+    entries: Vec<CacheEntry>,
+    // Position of the first entry of every hash
+    by_hash: HashMap<u64, usize>
+}
+
+impl core::fmt::Debug for ParsedCache {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ParsedCache").field("Windows version", &self.windows_version).field("Number of cache entries", &self.len()).field("Cache type", &self.cache_type).field("Kind", &self.kind).finish()
+    }
+}
+
+impl ParsedCache {
+    /// Parses the bytes of a whole database without copying the data of its entries, see `ReadOptions::lazy()`
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<ParsedCache, ThumbsError> {
+        let mut database = Thumbscache::from_bytes(bytes);
+        database.read_with_options(ReadOptions::lazy())?;
+        Ok(database.into_parsed())
+    }
+
+    /// Opens the database found at the path and parses it like `from_bytes()` does
+    #[cfg(feature = "fs")]
+    pub fn from_path(file: impl AsRef<std::path::Path>) -> Result<ParsedCache, ThumbsError> {
+        let mut database = Thumbscache::from_path(file)?;
+        database.read_with_options(ReadOptions::lazy())?;
+        Ok(database.into_parsed())
+    }
+
+    /// The Windows version of the database, None if it wasn't read
+    pub fn windows_version(&self) -> Option<WindowsVersion> {
+        self.windows_version
+    }
+
+    /// The cache type of the database, None if it wasn't read
+    pub fn cache_type(&self) -> Option<CacheType> {
+        self.cache_type
+    }
+
+    /// Whether the database holds thumbnails or icons
+    pub fn kind(&self) -> DatabaseKind {
+        self.kind
+    }
+
+    /// When the database file was last modified, if it was opened from a path
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    /// Returns the parsed cache entries, in file order
+    pub fn entries(&self) -> &[CacheEntry] {
+        &self.entries
+    }
+
+    /// Iterates over the parsed cache entries, in file order
+    pub fn iter(&self) -> Iter<'_, CacheEntry> {
+        self.entries.iter()
+    }
+
+    /// The number of parsed cache entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if no cache entries were parsed
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the raw bytes of the database
+    pub fn bytes(&self) -> &[u8] {
+        self.bytes.as_ref()
+    }
+
+    /// Returns the first entry identified by the hash, without scanning the entries
+    pub fn get(&self, entry_hash: u64) -> Option<&CacheEntry> {
+        self.by_hash.get(&entry_hash).map(|x| &self.entries[*x])
+    }
+
+    /// Returns the data of a cache entry, borrowed from the database when it wasn't loaded
+    ///
+    /// Returns None if the entry doesn't belong to this database.
+    pub fn entry_data<'a>(&'a self, entry: &'a CacheEntry) -> Option<&'a [u8]> {
+        if entry.is_data_loaded() {
+            return Some(&entry.data);
+        }
+        crate::data_in(self.bytes(), entry)
+    }
+
+    /// Returns the data of the first entry identified by the hash
+    pub fn data_by_hash(&self, entry_hash: u64) -> Option<&[u8]> {
+        self.entry_data(self.get(entry_hash)?)
+    }
+}
+
+impl From<Thumbscache> for ParsedCache {
+    fn from(database: Thumbscache) -> ParsedCache {
+        database.into_parsed()
+    }
+}
+
+impl Thumbscache {
+    /// Separates the parsed entries from the read cursor, giving a read-only database that can be shared between threads
+    #[allow(deprecated)]
+    pub fn into_parsed(self) -> ParsedCache {
+        let Thumbscache { stream, windows_version, cache_entires: entries, cache_type, kind, modified, .. } = self;
+        let mut by_hash = HashMap::with_capacity(entries.len());
+        for (index, entry) in entries.iter().enumerate() {
+            by_hash.entry(entry.entry_hash()).or_insert(index);
+        }
+        ParsedCache {
+            bytes: stream.into_inner(),
+            windows_version,
+            cache_type,
+            kind,
+            modified,
+            entries,
+            by_hash
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_database;
+    use std::sync::Arc;
+
+    #[test]
+    fn serves_entries_from_several_threads() {
+        let cache = Arc::new(ParsedCache::from_bytes(build_database(32, 1, &[(1, b"first"), (2, b"second"), (1, b"again")])).unwrap());
+        assert_eq!((cache.len(), cache.windows_version(), cache.cache_type()), (3, Some(WindowsVersion::Win10), Some(CacheType::Res32)));
+        let workers: Vec<_> = (0..4).map(|_| {
+            let cache = Arc::clone(&cache);
+            std::thread::spawn(move || [1, 2, 3].map(|x| cache.data_by_hash(x).map(|x| x.to_vec())))
+        }).collect();
+        for worker in workers {
+            assert_eq!(worker.join().unwrap(), [Some(b"first".to_vec()), Some(b"second".to_vec()), None]);
+        }
+
+        let mut database = Thumbscache::from_bytes(build_database(32, 1, &[(1, b"loaded")]));
+        database.read().unwrap();
+        let cache = ParsedCache::from(database);
+        assert_eq!(cache.get(1).unwrap().data, b"loaded");
+        assert_eq!(cache.data_by_hash(1), Some(&b"loaded"[..]));
+
+        // Entries pointing past the end of the database have no data
+        let mut forged = cache.entries()[0].clone();
+        forged.data.clear();
+        forged.data_offset = u64::MAX - 2;
+        assert_eq!(cache.entry_data(&forged), None);
+    }
+}
@@ -0,0 +1,174 @@
+//! Where every parsed field lies in the database, for hex viewers highlighting the bytes behind a value
+//!
+//! The spans are derived from the layout of the header and of the entry, nothing is read again.
+
+use core::ops::Range;
+
+use crate::{parse::{CacheEntryRef, CacheHeader}, CacheEntry, VersionFields};
+
+/// A range of bytes of the database, its offset counting from the start of the database
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Span {
+    pub offset: u64,
+    pub len: u64
+}
+
+impl Span {
+    fn at(base: u64, offset: u64, len: u64) -> Span {
+        Span {
+            offset: base.saturating_add(offset),
+            len
+        }
+    }
+
+    /// The offset of the first byte after the span
+    pub fn end(&self) -> u64 {
+        self.offset.saturating_add(self.len)
+    }
+
+    /// The span as a range of offsets
+    pub fn range(&self) -> Range<u64> {
+        self.offset..self.end()
+    }
+}
+
+/// The fields of the database header, see `CacheHeader::spans()`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct HeaderSpans {
+    pub magic: Span,
+    pub format_version: Span,
+    pub cache_type: Span,
+    pub first_entry: Span,
+    pub first_available_entry: Span,
+    /// None when the layout of the header isn't known
+    pub entry_count: Option<Span>
+}
+
+/// The fields of a cache entry, see `CacheEntry::spans()`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EntrySpans {
+    /// The "CMMM" signature
+    pub signature: Span,
+    pub size: Span,
+    pub entry_hash: Span,
+    /// Only stored by Windows Vista
+    pub file_extension: Option<Span>,
+    pub identifier_string_size: Span,
+    pub padding_size: Span,
+    pub data_size: Span,
+    /// Only stored by Windows 8 and later
+    pub width: Option<Span>,
+    /// Only stored by Windows 8 and later
+    pub height: Option<Span>,
+    /// Only stored by Windows 8 and later
+    pub flags: Option<Span>,
+    pub data_checksum: Span,
+    pub header_checksum: Span,
+    pub identifier: Span,
+    pub padding: Span,
+    pub data: Span,
+    /// The bytes between the end of the data and the end of the entry, as given by its size
+    pub slack: Span
+}
+
+// Lays the fields out from the position of the entry and the sizes stored in its header
+fn entry_spans(offset: u64, version_fields: &VersionFields, size: u32, identifier_string_size: u32, padding_size: u32, data_size: u32) -> EntrySpans {
+    let field = |position: u64, len: u64| Span::at(offset, position, len);
+    // Positions of the identifier size, the data checksum and the header checksum, and the length of the header
+    let (sizes, data_checksum, header_checksum, header_len) = match version_fields {
+        VersionFields::Vista { .. } => (24, 40, 48, 56),
+        VersionFields::Win7 => (16, 32, 40, 48),
+        VersionFields::Win8 { .. } => (16, 40, 48, 56)
+    };
+    let identifier = field(header_len, identifier_string_size as u64);
+    let padding = Span::at(identifier.end(), 0, padding_size as u64);
+    let data = Span::at(padding.end(), 0, data_size as u64);
+    let is_win8 = matches!(version_fields, VersionFields::Win8 { .. });
+    EntrySpans {
+        signature: field(0, 4),
+        size: field(4, 4),
+        entry_hash: field(8, 8),
+        file_extension: matches!(version_fields, VersionFields::Vista { .. }).then(|| field(16, 8)),
+        identifier_string_size: field(sizes, 4),
+        padding_size: field(sizes + 4, 4),
+        data_size: field(sizes + 8, 4),
+        width: is_win8.then(|| field(28, 4)),
+        height: is_win8.then(|| field(32, 4)),
+        flags: is_win8.then(|| field(36, 4)),
+        data_checksum: field(data_checksum, 8),
+        header_checksum: field(header_checksum, 8),
+        identifier,
+        padding,
+        data,
+        slack: Span::at(data.end(), 0, offset.saturating_add(size as u64).saturating_sub(data.end()))
+    }
+}
+
+impl CacheHeader {
+    /// Where every field of the header lies
+    pub fn spans(&self) -> HeaderSpans {
+        HeaderSpans {
+            magic: Span::at(0, 0, 4),
+            format_version: Span::at(0, 4, 4),
+            cache_type: Span::at(0, 8, 4),
+            first_entry: Span::at(0, 12, 4),
+            first_available_entry: Span::at(0, 16, 4),
+            entry_count: self.entry_count.map(|_| Span::at(0, 20, 4))
+        }
+    }
+}
+
+impl CacheEntry {
+    /// Where every field of the entry lies in the database it was read from
+    pub fn spans(&self) -> EntrySpans {
+        entry_spans(self.offset, &self.version_fields, self.size, self.identifier_string_size, self.padding_size, self.data_size)
+    }
+}
+
+impl CacheEntryRef<'_> {
+    /// Where every field of the entry lies in the database it was read from
+    pub fn spans(&self) -> EntrySpans {
+        let summary = self.summary();
+        entry_spans(self.offset(), &self.version_fields, summary.size, summary.identifier_string_size, summary.padding_size, self.data_size)
+    }
+}
+
+#[cfg(feature = "std")]
+impl crate::Thumbscache {
+    /// Pairs every parsed entry with the spans of its fields, the spans of the database header are given by `CacheHeader::spans()`
+    pub fn with_spans(&self) -> impl Iterator<Item = (&CacheEntry, EntrySpans)> {
+        self.iter().map(|entry| (entry, entry.spans()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_database, parse_database_ref, tests::build_database, ReadOptions};
+
+    #[test]
+    fn locates_every_field() {
+        for format_version in [20, 21, 32] {
+            let bytes = build_database(format_version, 0, &[(0xAB, b"first"), (0xCD, b"second")]);
+            let (header, entries) = parse_database(&bytes, ReadOptions::default()).unwrap();
+            assert_eq!(header.spans().first_available_entry.range(), 16..20);
+            let entry = &entries[1];
+            let spans = entry.spans();
+            let field = |span: Span| &bytes[span.offset as usize..span.end() as usize];
+            assert_eq!(field(spans.signature), b"CMMM");
+            assert_eq!(field(spans.entry_hash), 0xCDu64.to_le_bytes());
+            assert_eq!(field(spans.size), entry.size.to_le_bytes());
+            assert_eq!(field(spans.data_size), 6u32.to_le_bytes());
+            assert_eq!(field(spans.data_checksum), entry.data_checksum.to_le_bytes());
+            assert_eq!(field(spans.header_checksum), entry.header_checksum.to_le_bytes());
+            assert_eq!(field(spans.identifier), entry.identifier.as_slice());
+            assert_eq!(field(spans.data), b"second");
+            assert_eq!(spans.slack.end(), entry.record_range().end);
+            assert_eq!((spans.file_extension.is_some(), spans.width.is_some(), spans.flags.is_some()), (format_version == 20, format_version == 32, format_version == 32));
+            assert_eq!(parse_database_ref(&bytes, ReadOptions::default()).unwrap().1[1].spans(), spans);
+        }
+    }
+}
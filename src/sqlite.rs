@@ -0,0 +1,138 @@
+//! Exporting parsed databases into an SQLite evidence database, enabled with the `sqlite` feature
+//!
+//! Several caches can be exported into the same file, each one gets a row in the caches table that its entries refer to.
+//! Hashes are stored as lowercase hexadecimal text, since entry hashes don't fit into the signed integers of SQLite.
+
+use std::path::Path;
+
+use rusqlite::{params, Connection};
+
+use crate::{Thumbscache, ThumbsError};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS caches (
+    id INTEGER PRIMARY KEY,
+    kind TEXT NOT NULL,
+    windows_version TEXT,
+    cache_type TEXT,
+    size INTEGER NOT NULL,
+    entry_count INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS entries (
+    id INTEGER PRIMARY KEY,
+    cache_id INTEGER NOT NULL REFERENCES caches(id),
+    position INTEGER NOT NULL,
+    identifier_string TEXT NOT NULL,
+    entry_hash TEXT NOT NULL,
+    offset INTEGER NOT NULL,
+    size INTEGER NOT NULL,
+    data_size INTEGER NOT NULL,
+    data_checksum TEXT NOT NULL,
+    header_checksum TEXT NOT NULL,
+    data_valid INTEGER NOT NULL,
+    header_valid INTEGER NOT NULL,
+    format TEXT NOT NULL,
+    origin TEXT NOT NULL,
+    md5 TEXT,
+    sha1 TEXT,
+    sha256 TEXT,
+    data BLOB
+);
+CREATE INDEX IF NOT EXISTS entries_entry_hash ON entries(entry_hash);
+";
+
+fn sqlite_error(error: rusqlite::Error) -> ThumbsError {
+    ThumbsError::DatabaseError(error.to_string())
+}
+
+impl Thumbscache {
+    /// Adds the database and its parsed entries to the SQLite database at the path, creating the file and its tables if needed.
+    ///
+    /// Every entry gets its metadata and checksum status, the md5, sha1 and sha256 columns are only filled with the `hashes` feature.
+    /// When include_data is set, the data of every entry is stored as a BLOB.
+    /// Returns the id of the row added to the caches table.
+    pub fn export_sqlite(&self, path: impl AsRef<Path>, include_data: bool) -> Result<i64, ThumbsError> {
+        let mut connection = Connection::open(path).map_err(sqlite_error)?;
+        connection.execute_batch(SCHEMA).map_err(sqlite_error)?;
+        let transaction = connection.transaction().map_err(sqlite_error)?;
+        transaction.execute(
+            "INSERT INTO caches (kind, windows_version, cache_type, size, entry_count) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                format!("{:?}", self.kind),
+                self.windows_version.map(|x| format!("{:?}", x)),
+                self.cache_type.map(|x| format!("{:?}", x)),
+                self.bytes().len() as i64,
+                self.len() as i64
+            ]
+        ).map_err(sqlite_error)?;
+        let cache_id = transaction.last_insert_rowid();
+        {
+            let mut statement = transaction.prepare(
+                "INSERT INTO entries (cache_id, position, identifier_string, entry_hash, offset, size, data_size, data_checksum, header_checksum,
+                    data_valid, header_valid, format, origin, md5, sha1, sha256, data)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)"
+            ).map_err(sqlite_error)?;
+            for (position, entry) in self.iter().enumerate() {
+                // Entries read without their data are described from the data in the database
+                let entry = self.loaded_entry(entry)?;
+                let status = entry.verify();
+                #[cfg(feature = "hashes")]
+                let (md5, sha1, sha256) = {
+                    let hashes = entry.hashes();
+                    (Some(hashes.md5), Some(hashes.sha1), Some(hashes.sha256))
+                };
+                #[cfg(not(feature = "hashes"))]
+                let (md5, sha1, sha256): (Option<String>, Option<String>, Option<String>) = (None, None, None);
+                statement.execute(params![
+                    cache_id,
+                    position as i64,
+                    entry.identifier_string,
+                    entry.entry_hash_hex(),
+                    entry.offset as i64,
+                    entry.size,
+                    entry.data_size,
+                    format!("{:016x}", entry.data_checksum),
+                    format!("{:016x}", entry.header_checksum),
+                    status.data_valid,
+                    status.header_valid,
+                    entry.payload_kind().extension(),
+                    format!("{:?}", entry.origin),
+                    md5,
+                    sha1,
+                    sha256,
+                    include_data.then_some(entry.data.as_slice())
+                ]).map_err(sqlite_error)?;
+            }
+        }
+        transaction.commit().map_err(sqlite_error)?;
+        Ok(cache_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{build_database, temp_dir};
+
+    #[test]
+    fn exports_caches_and_entries() {
+        let path = temp_dir("sqlite").join("evidence.sqlite");
+        let mut first = Thumbscache::from_bytes(build_database(32, 1, &[(0xAB, b"BMfirst"), (0xCD, b"")]));
+        first.read().unwrap();
+        let mut second = Thumbscache::from_bytes(build_database(32, 4, &[(0xEF, b"\x89PNG\r\n\x1a\nsecond")]));
+        second.read().unwrap();
+        assert_eq!(first.export_sqlite(&path, true).unwrap(), 1);
+        assert_eq!(second.export_sqlite(&path, false).unwrap(), 2);
+
+        let connection = Connection::open(&path).unwrap();
+        let count: i64 = connection.query_row("SELECT COUNT(*) FROM entries", [], |x| x.get(0)).unwrap();
+        assert_eq!(count, 3);
+        let (cache_type, format, data): (String, String, Option<Vec<u8>>) = connection.query_row(
+            "SELECT caches.cache_type, format, data FROM entries JOIN caches ON caches.id = entries.cache_id WHERE entry_hash = '00000000000000ab'",
+            [], |x| Ok((x.get(0)?, x.get(1)?, x.get(2)?))).unwrap();
+        assert_eq!((cache_type.as_str(), format.as_str(), data.as_deref()), ("Res32", "bmp", Some(&b"BMfirst"[..])));
+        let (data, data_valid): (Option<Vec<u8>>, bool) = connection.query_row(
+            "SELECT data, data_valid FROM entries WHERE entry_hash = '00000000000000ef'", [], |x| Ok((x.get(0)?, x.get(1)?))).unwrap();
+        assert_eq!((data, data_valid), (None, true));
+    }
+}
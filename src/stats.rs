@@ -0,0 +1,88 @@
+//! Summary statistics of a parsed database, to decide whether a full extraction is worth it
+
+use std::collections::HashMap;
+
+use crate::{ImageFormat, Thumbscache};
+
+/// Summary statistics
+///
+/// Returned by `Thumbscache::stats()`, data sizes are taken from the entry headers so entries read without their data are counted as well.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CacheStats {
+    pub total_entries: usize,
+    /// Entries without data, which Windows keeps as placeholders
+    pub empty_entries: usize,
+    /// The data of every entry added together
+    pub total_data_bytes: u64,
+    /// Number of entries per detected image format, empty entries are left out
+    pub formats: HashMap<ImageFormat, usize>,
+    /// Smallest, average and largest data size of the entries holding data
+    pub min_data_size: Option<u32>,
+    pub average_data_size: Option<f64>,
+    pub max_data_size: Option<u32>,
+    /// Bytes inside the entries that belong to neither their header, identifier, padding nor data
+    pub entry_slack_bytes: u64,
+    /// Bytes between the end of the live entries and the end of the file, where stale entries may remain
+    pub unallocated_bytes: u64
+}
+
+impl Thumbscache {
+    /// Computes summary statistics of the parsed entries
+    pub fn stats(&self) -> CacheStats {
+        let mut stats = CacheStats {
+            total_entries: self.len(),
+            ..CacheStats::default()
+        };
+        for entry in self.iter() {
+            let content_len = entry.header.len() as u64 + entry.identifier_string_size as u64 + entry.padding_size as u64 + entry.data_size as u64;
+            stats.entry_slack_bytes += (entry.size as u64).saturating_sub(content_len);
+            if entry.data_size == 0 {
+                stats.empty_entries += 1;
+                continue;
+            }
+            stats.total_data_bytes += entry.data_size as u64;
+            stats.min_data_size = Some(stats.min_data_size.map_or(entry.data_size, |x| x.min(entry.data_size)));
+            stats.max_data_size = Some(stats.max_data_size.map_or(entry.data_size, |x| x.max(entry.data_size)));
+            // The data may not be loaded, it's still in the bytes of the database
+            let format = ImageFormat::detect(self.entry_data(entry).unwrap_or(&entry.data));
+            *stats.formats.entry(format).or_default() += 1;
+        }
+        let with_data = stats.total_entries - stats.empty_entries;
+        if with_data > 0 {
+            stats.average_data_size = Some(stats.total_data_bytes as f64 / with_data as f64);
+        }
+        if let Ok(header) = self.header() {
+            let len = self.bytes().len() as u64;
+            if let (end, true) = header.entries_end(len) {
+                stats.unallocated_bytes = len - end;
+            }
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::build_database, ReadOptions};
+
+    #[test]
+    fn summarizes_entries() {
+        let mut bytes = build_database(32, 1, &[(1, b"BMfirst"), (2, b""), (3, b"\x89PNG\r\n\x1a\nthird"), (4, b"BM")]);
+        // Stale bytes after the live entries
+        bytes.extend_from_slice(&[0u8; 10]);
+        let mut database = Thumbscache::from_bytes(bytes);
+        database.read_with_options(ReadOptions::lazy()).unwrap();
+        let stats = database.stats();
+        assert_eq!(stats.total_entries, 4);
+        assert_eq!(stats.empty_entries, 1);
+        assert_eq!(stats.total_data_bytes, 7 + 13 + 2);
+        assert_eq!(stats.formats.get(&ImageFormat::Bmp), Some(&2));
+        assert_eq!(stats.formats.get(&ImageFormat::Png), Some(&1));
+        assert_eq!(stats.min_data_size, Some(2));
+        assert_eq!(stats.max_data_size, Some(13));
+        assert_eq!(stats.average_data_size, Some(22.0 / 3.0));
+        assert_eq!(stats.entry_slack_bytes, 0);
+        assert_eq!(stats.unallocated_bytes, 10);
+    }
+}
@@ -0,0 +1,269 @@
+//! Streaming access to a database without loading it into memory
+//!
+//! The `ThumbscacheReader` only reads entry headers while iterating, the thumbnail data is fetched on request.
+
+use std::io::{Read, Seek, SeekFrom, Write};
+#[cfg(feature = "fs")]
+use std::{fs::File, io::BufReader, path::Path};
+
+use crate::{parse::{decode_identifier, parse_entry_header}, parse_header, CacheEntry, CacheType, EntryOrigin, Thumbscache, ThumbsError, VersionFields, WindowsVersion};
+
+/// Entry header
+///
+/// The metadata of a cache entry, along with the location of its data in the database.
+#[derive(Debug, Clone)]
+pub struct EntryHeader {
+    pub offset: u64,
+    pub size: u32,
+    pub entry_hash: u64,
+    pub version_fields: VersionFields,
+    pub identifier_string: String,
+    pub data_offset: u64,
+    pub data_size: u32,
+    identifier_string_size: u32,
+    padding_size: u32,
+    data_checksum: u64,
+    header_checksum: u64,
+    header: Vec<u8>,
+    identifier: Vec<u8>
+}
+
+/// Thumbscache reader
+///
+/// Reads a database from any seekable source, one entry header at a time.
+///
+/// ```no_run
+/// use thumbscache::ThumbscacheReader;
+/// let mut reader = ThumbscacheReader::open("C:\\Users\\z\\AppData\\Local\\Microsoft\\Windows\\Explorer\\thumbcache_2560.db").unwrap();
+/// for header in reader.entries() {
+///     let header = header.unwrap();
+///     println!("{} : {} bytes", header.identifier_string, header.data_size);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ThumbscacheReader<R: Read + Seek> {
+    reader: R,
+    len: u64,
+    first_entry: u64,
+    end: u64,
+    strict: bool,
+    format_version: u32,
+    /// The layout used to parse the entries, it can be set to force the layout of a known Windows version
+    pub windows_version: Option<WindowsVersion>,
+    pub cache_type: Option<CacheType>
+}
+
+#[cfg(feature = "fs")]
+impl ThumbscacheReader<BufReader<File>> {
+    /// Opens the database and reads its header
+    pub fn open(file: impl AsRef<Path>) -> Result<ThumbscacheReader<BufReader<File>>, ThumbsError> {
+        if let Ok(opened_file) = std::fs::OpenOptions::new().read(true).open(file) {
+            ThumbscacheReader::new(BufReader::new(opened_file))
+        }else {
+            Err(ThumbsError::InvalidFile)
+        }
+    }
+}
+
+impl<R: Read + Seek> ThumbscacheReader<R> {
+    /// Reads the header of the database from the source
+    pub fn new(mut reader: R) -> Result<ThumbscacheReader<R>, ThumbsError> {
+        let len = reader.seek(SeekFrom::End(0)).map_err(ThumbsError::IoError)?;
+        reader.seek(SeekFrom::Start(0)).map_err(ThumbsError::IoError)?;
+        let mut read_bytes: [u8; 24] = [0; 24];
+        reader.read_exact(&mut read_bytes).map_err(ThumbsError::IoError)?;
+        let header = parse_header(&read_bytes)?;
+        let (end, strict) = header.entries_end(len);
+        Ok(ThumbscacheReader {
+            reader,
+            len,
+            first_entry: 24 + header.first_entry as u64,
+            end,
+            strict,
+            format_version: header.format_version,
+            windows_version: header.windows_version,
+            cache_type: header.cache_type
+        })
+    }
+
+    /// Returns an iterator over the entry headers, without reading the thumbnail data
+    pub fn entries(&mut self) -> Entries<'_, R> {
+        let position = self.first_entry;
+        Entries {
+            reader: self,
+            position,
+            finished: false
+        }
+    }
+
+    /// Reads the data of the entry
+    pub fn read_data(&mut self, header: &EntryHeader) -> Result<Vec<u8>, ThumbsError> {
+        self.reader.seek(SeekFrom::Start(header.data_offset)).map_err(ThumbsError::IoError)?;
+        if header.data_offset + header.data_size as u64 > self.len {
+            return Err(ThumbsError::TruncatedEntry { offset: header.offset, needed: header.data_offset + header.data_size as u64 - self.len });
+        }
+        let mut data = vec![0u8; header.data_size as usize];
+        self.reader.read_exact(&mut data).map_err(ThumbsError::IoError)?;
+        Ok(data)
+    }
+
+    /// Copies the data of the entry into the writer in small chunks, so even the largest thumbnails are extracted with constant memory.
+    /// Returns the number of bytes copied.
+    pub fn copy_data_to(&mut self, header: &EntryHeader, writer: &mut impl Write) -> Result<u64, ThumbsError> {
+        if header.data_offset + header.data_size as u64 > self.len {
+            return Err(ThumbsError::TruncatedEntry { offset: header.offset, needed: header.data_offset + header.data_size as u64 - self.len });
+        }
+        self.reader.seek(SeekFrom::Start(header.data_offset)).map_err(ThumbsError::IoError)?;
+        std::io::copy(&mut (&mut self.reader).take(header.data_size as u64), writer).map_err(ThumbsError::IoError)
+    }
+
+    /// Reads the data of the entry and turns it into a full cache entry
+    pub fn read_entry(&mut self, header: &EntryHeader) -> Result<CacheEntry, ThumbsError> {
+        let data = self.read_data(header)?;
+        Ok(CacheEntry {
+            offset: header.offset,
+            size: header.size,
+            entry_hash: header.entry_hash,
+            version_fields: header.version_fields.clone(),
+            identifier_string_size: header.identifier_string_size,
+            padding_size: header.padding_size,
+            data_size: header.data_size,
+            data_checksum: header.data_checksum,
+            header_checksum: header.header_checksum,
+            header: header.header.clone(),
+            data_offset: header.data_offset,
+            identifier_string: header.identifier_string.clone(),
+            identifier: header.identifier.clone(),
+            origin: EntryOrigin::Live,
+            cache_type: self.cache_type,
+            data
+        })
+    }
+
+    fn read_header_at(&mut self, offset: u64) -> Result<Option<EntryHeader>, ThumbsError> {
+        let version = self.windows_version.ok_or(ThumbsError::UnknownVersion { version: self.format_version })?;
+        self.reader.seek(SeekFrom::Start(offset)).map_err(ThumbsError::IoError)?;
+        let mut temp_bytes: [u8; 56] = [0; 56];
+        let available = (self.len - offset).min(56) as usize;
+        self.reader.read_exact(&mut temp_bytes[..available]).map_err(ThumbsError::IoError)?;
+        if &temp_bytes[0..4] != b"CMMM" {
+            if self.strict {
+                return Err(ThumbsError::BadEntrySignature { offset });
+            }
+            return Ok(None);
+        }
+        let fields = parse_entry_header(version, &temp_bytes);
+        let identifier_end = offset + fields.header_len as u64 + fields.identifier_string_size as u64;
+        if identifier_end > self.len {
+            return Err(ThumbsError::TruncatedEntry { offset, needed: identifier_end - self.len });
+        }
+        self.reader.seek(SeekFrom::Start(offset + fields.header_len as u64)).map_err(ThumbsError::IoError)?;
+        let mut identifier_string_vec: Vec<u8> = vec![0u8; fields.identifier_string_size as usize];
+        self.reader.read_exact(&mut identifier_string_vec).map_err(ThumbsError::IoError)?;
+        Ok(Some(EntryHeader {
+            offset,
+            size: fields.size,
+            entry_hash: fields.entry_hash,
+            version_fields: fields.version_fields,
+            identifier_string: decode_identifier(&identifier_string_vec),
+            data_offset: offset + fields.header_len as u64 + fields.identifier_string_size as u64 + fields.padding_size as u64,
+            data_size: fields.data_size,
+            identifier_string_size: fields.identifier_string_size,
+            padding_size: fields.padding_size,
+            data_checksum: fields.data_checksum,
+            header_checksum: fields.header_checksum,
+            header: temp_bytes[..fields.header_len].to_vec(),
+            identifier: identifier_string_vec
+        }))
+    }
+}
+
+impl Thumbscache {
+    /// Writes the data of the first parsed entry with the entry hash into the writer, straight from the bytes of the database.
+    ///
+    /// The data doesn't need to be loaded (see `ReadOptions::lazy()`), so no copy of it is made in memory.
+    /// Returns the number of bytes written, or ThumbsError::EntryHashNotFound if no parsed entry has this hash.
+    pub fn stream_entry_to(&self, entry_hash: u64, mut writer: impl Write) -> Result<u64, ThumbsError> {
+        let entry = self.iter().find(|x| x.entry_hash() == entry_hash).ok_or(ThumbsError::EntryHashNotFound { entry_hash })?;
+        let data = self.entry_data(entry).ok_or(ThumbsError::TruncatedEntry {
+            offset: entry.offset(),
+            needed: (entry.data_offset + entry.data_size as u64).saturating_sub(self.bytes().len() as u64)
+        })?;
+        writer.write_all(data).map_err(ThumbsError::IoError)?;
+        Ok(data.len() as u64)
+    }
+}
+
+/// Iterator over the entry headers of a database, see `ThumbscacheReader::entries()`
+pub struct Entries<'a, R: Read + Seek> {
+    reader: &'a mut ThumbscacheReader<R>,
+    position: u64,
+    finished: bool
+}
+
+impl<R: Read + Seek> Iterator for Entries<'_, R> {
+    type Item = Result<EntryHeader, ThumbsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished || self.position >= self.reader.end {
+            return None;
+        }
+        match self.reader.read_header_at(self.position) {
+            Ok(Some(header)) => {
+                // An entry smaller than its own content would move us backwards or never forward
+                if (header.size as u64) < header.data_offset + header.data_size as u64 - header.offset {
+                    self.finished = true;
+                    return Some(Err(ThumbsError::MalformedEntry { offset: header.offset }));
+                }
+                self.position += header.size as u64;
+                Some(Ok(header))
+            },
+            Ok(None) => {
+                self.finished = true;
+                None
+            },
+            Err(error) => {
+                self.finished = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::build_database;
+    use std::io::Cursor;
+
+    #[test]
+    fn iterates_headers_and_reads_data_on_demand() {
+        let mut reader = ThumbscacheReader::new(Cursor::new(build_database(32, 8, &[(1, b"first"), (2, b"second")]))).unwrap();
+        assert_eq!(reader.cache_type, Some(CacheType::Res2560));
+        let headers: Vec<EntryHeader> = reader.entries().collect::<Result<_, _>>().unwrap();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[1].entry_hash, 2);
+        assert_eq!(headers[1].data_size, 6);
+        assert_eq!(reader.read_data(&headers[1]).unwrap(), b"second");
+        let entry = reader.read_entry(&headers[0]).unwrap();
+        assert_eq!(entry.data, b"first");
+        assert!(entry.verify().is_valid());
+    }
+
+    #[test]
+    fn streams_data_into_writers() {
+        let bytes = build_database(32, 8, &[(1, b"first"), (2, b"second")]);
+        let mut reader = ThumbscacheReader::new(Cursor::new(bytes.clone())).unwrap();
+        let headers: Vec<EntryHeader> = reader.entries().collect::<Result<_, _>>().unwrap();
+        let mut sink = Vec::new();
+        assert_eq!(reader.copy_data_to(&headers[1], &mut sink).unwrap(), 6);
+        assert_eq!(sink, b"second");
+
+        let mut database = Thumbscache::from_bytes(bytes);
+        database.read_with_options(crate::ReadOptions::lazy()).unwrap();
+        let mut sink = Vec::new();
+        assert_eq!(database.stream_entry_to(1, &mut sink).unwrap(), 5);
+        assert_eq!(sink, b"first");
+        assert!(matches!(database.stream_entry_to(3, &mut sink), Err(ThumbsError::EntryHashNotFound { entry_hash: 3 })));
+    }
+}
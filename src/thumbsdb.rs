@@ -0,0 +1,206 @@
+//! Legacy Thumbs.db files used by Windows XP and Windows Server 2003
+//!
+//! These per-folder databases are OLE2 compound files. The "Catalog" stream lists the original file names and modification times,
+//! every thumbnail is kept in its own stream named after the reversed digits of its catalog index.
+
+use std::io::{Read, Seek};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use crate::{endian::LittleEndian, parse::slice_at, ImageFormat, ThumbsError};
+
+/// Thumbs.db entry
+///
+/// Represents a thumbnail stored in a Thumbs.db file, along with the catalog information of the original file.
+#[derive(Debug, Clone)]
+pub struct ThumbsDbEntry {
+    /// The catalog index of the thumbnail
+    pub id: u32,
+    /// The name of the original file
+    pub file_name: String,
+    /// Last modification time of the original file as a FILETIME
+    pub modified: u64,
+    /// The thumbnail itself, usually in JPEG format
+    pub data: Vec<u8>
+}
+
+impl ThumbsDbEntry {
+    /// Detects the image format of the data by looking at its magic number
+    pub fn detect_format(&self) -> ImageFormat {
+        ImageFormat::detect(&self.data)
+    }
+
+    // The name of the original file made safe to use as a file name, the catalog index when nothing of it is usable
+    #[cfg(feature = "fs")]
+    fn file_stem(&self) -> String {
+        crate::export::sanitize_file_name(&self.file_name).unwrap_or_else(|| self.id.to_string())
+    }
+
+    /// Writes the thumbnail into a file.
+    /// The file path defaults to the current directory unless stated otherwise, named after the original file with the extension of the detected image format.
+    /// Only ASCII letters, digits, '.', '_' and '-' are kept from the original name, the catalog index is used when nothing is left.
+    #[cfg(feature = "fs")]
+    pub fn write_to_file(&self, file_path: Option<&Path>) -> Result<crate::ExportedFile, ThumbsError> {
+        let format = crate::PayloadKind::detect(&self.data, None);
+        let path = file_path.map(Path::to_path_buf).unwrap_or_else(|| std::path::PathBuf::from(format!("./{}.{}", self.file_stem(), format.extension())));
+        crate::write_data(&path, &self.data)?;
+        Ok(crate::ExportedFile {
+            path,
+            bytes_written: self.data.len() as u64,
+            format
+        })
+    }
+}
+
+/// Thumbs.db
+///
+/// Represents a parsed Thumbs.db file.
+///
+/// ```no_run
+/// use thumbscache::thumbsdb::ThumbsDb;
+/// let thumbs = ThumbsDb::open("D:\\Evidence\\Pictures\\Thumbs.db").unwrap();
+/// for entry in &thumbs.entries {
+///     println!("{} : {} bytes", entry.file_name, entry.data.len());
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ThumbsDb {
+    /// Maximum thumbnail width stored in the catalog
+    pub width: u32,
+    /// Maximum thumbnail height stored in the catalog
+    pub height: u32,
+    pub entries: Vec<ThumbsDbEntry>
+}
+
+impl ThumbsDb {
+    /// Opens and parses the Thumbs.db file
+    #[cfg(feature = "fs")]
+    pub fn open(file: impl AsRef<Path>) -> Result<ThumbsDb, ThumbsError> {
+        if let Ok(opened_file) = std::fs::OpenOptions::new().read(true).open(file) {
+            ThumbsDb::from_reader(opened_file)
+        }else {
+            Err(ThumbsError::InvalidFile)
+        }
+    }
+
+    /// Parses a Thumbs.db file from any seekable byte source
+    pub fn from_reader(reader: impl Read + Seek) -> Result<ThumbsDb, ThumbsError> {
+        let mut compound_file = cfb::CompoundFile::open(reader).map_err(ThumbsError::IoError)?;
+        let catalog = read_stream(&mut compound_file, "/Catalog")?;
+        let header = slice_at(&catalog, 0, 16)?;
+        let header_size = header.u16_le(0) as usize;
+        let count = header.u32_le(4);
+        let width = header.u32_le(8);
+        let height = header.u32_le(12);
+        let mut entries = Vec::new();
+        let mut position = header_size;
+        for _ in 0..count {
+            let Some(record) = catalog.get(position..position + 16) else {
+                break;
+            };
+            let record_size = record.u32_le(0) as usize;
+            let id = record.u32_le(4);
+            let modified = record.u64_le(8);
+            let Some(name_bytes) = catalog.get(position + 16..position + record_size.max(16)) else {
+                break;
+            };
+            // The file name is a null-terminated UTF-16 string
+            let name: Vec<u16> = name_bytes.chunks_exact(2).map(|a| a.u16_le(0)).take_while(|x| *x != 0).collect();
+            let stream_name: String = id.to_string().chars().rev().collect();
+            let data = match read_stream(&mut compound_file, &format!("/{}", stream_name)) {
+                Ok(stream) => strip_stream_header(stream),
+                // Catalog records can outlive their thumbnail stream
+                Err(_) => Vec::new()
+            };
+            entries.push(ThumbsDbEntry {
+                id,
+                file_name: String::from_utf16_lossy(&name),
+                modified,
+                data
+            });
+            if record_size == 0 {
+                break;
+            }
+            position += record_size;
+        }
+        Ok(ThumbsDb {
+            width,
+            height,
+            entries
+        })
+    }
+}
+
+fn read_stream<F: Read + Seek>(compound_file: &mut cfb::CompoundFile<F>, path: &str) -> Result<Vec<u8>, ThumbsError> {
+    let mut stream = compound_file.open_stream(path).map_err(ThumbsError::IoError)?;
+    let mut bytes = Vec::new();
+    stream.read_to_end(&mut bytes).map_err(ThumbsError::IoError)?;
+    Ok(bytes)
+}
+
+// Thumbnail streams start with a small header : its own size, an unknown field and the size of the image
+fn strip_stream_header(stream: Vec<u8>) -> Vec<u8> {
+    if stream.len() < 12 {
+        return stream;
+    }
+    let header_size = stream.u32_le(0) as usize;
+    let data_size = stream.u32_le(8) as usize;
+    let start = header_size.min(stream.len());
+    let end = start.saturating_add(data_size).min(stream.len());
+    stream[start..end].to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+
+    fn build_thumbs_db(entries: &[(u32, &str, &[u8])]) -> Vec<u8> {
+        let mut compound_file = cfb::CompoundFile::create(Cursor::new(Vec::new())).unwrap();
+        let mut catalog = Vec::new();
+        catalog.extend_from_slice(&16u16.to_le_bytes());
+        catalog.extend_from_slice(&7u16.to_le_bytes());
+        catalog.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        catalog.extend_from_slice(&96u32.to_le_bytes());
+        catalog.extend_from_slice(&96u32.to_le_bytes());
+        for (id, name, data) in entries {
+            let mut name: Vec<u8> = name.encode_utf16().flat_map(|x| x.to_le_bytes()).collect();
+            name.extend_from_slice(&[0, 0, 0, 0]);
+            catalog.extend_from_slice(&(16 + name.len() as u32).to_le_bytes());
+            catalog.extend_from_slice(&id.to_le_bytes());
+            catalog.extend_from_slice(&0x01C0_0000_0000_0000u64.to_le_bytes());
+            catalog.extend_from_slice(&name);
+            let stream_name: String = id.to_string().chars().rev().collect();
+            let mut stream = compound_file.create_stream(format!("/{}", stream_name)).unwrap();
+            stream.write_all(&12u32.to_le_bytes()).unwrap();
+            stream.write_all(&1u32.to_le_bytes()).unwrap();
+            stream.write_all(&(data.len() as u32).to_le_bytes()).unwrap();
+            stream.write_all(data).unwrap();
+        }
+        compound_file.create_stream("/Catalog").unwrap().write_all(&catalog).unwrap();
+        compound_file.flush().unwrap();
+        compound_file.into_inner().into_inner()
+    }
+
+    #[test]
+    fn reads_catalog_and_thumbnails() {
+        let bytes = build_thumbs_db(&[(1, "holiday.jpg", &[0xFF, 0xD8, 0xFF, 0xE0]), (12, "report.png", &[0xFF, 0xD8, 0xFF, 0xDB])]);
+        let thumbs = ThumbsDb::from_reader(Cursor::new(bytes)).unwrap();
+        assert_eq!(thumbs.width, 96);
+        assert_eq!(thumbs.entries.len(), 2);
+        assert_eq!(thumbs.entries[0].file_name, "holiday.jpg");
+        assert_eq!(thumbs.entries[0].modified, 0x01C0_0000_0000_0000);
+        assert_eq!(thumbs.entries[1].id, 12);
+        assert_eq!(thumbs.entries[1].data, [0xFF, 0xD8, 0xFF, 0xDB]);
+        assert_eq!(thumbs.entries[1].detect_format(), ImageFormat::Jpeg);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn keeps_crafted_file_names_inside_the_directory() {
+        let bytes = build_thumbs_db(&[(1, "..\\..\\evil.jpg", &[0xFF, 0xD8, 0xFF, 0xE0]), (2, "..", &[0xFF, 0xD8, 0xFF, 0xE0])]);
+        let thumbs = ThumbsDb::from_reader(Cursor::new(bytes)).unwrap();
+        assert_eq!(thumbs.entries[0].file_stem(), "....evil.jpg");
+        assert_eq!(thumbs.entries[1].file_stem(), "2");
+    }
+}
@@ -0,0 +1,97 @@
+//! Timestamps of the entries, for super-timeline tools
+//!
+//! Cache entries don't store a timestamp. The Windows Vista index file records when every item was last modified,
+//! otherwise the modification time of the database file is the closest available timestamp.
+
+use std::time::{Duration, SystemTime};
+
+use crate::{CacheEntry, IndexEntry, ThumbcacheSet, Thumbscache};
+
+// Seconds between the FILETIME epoch (1601-01-01) and the Unix epoch
+const FILETIME_UNIX_OFFSET: u64 = 11_644_473_600;
+
+// Converts a FILETIME, counting 100 nanosecond intervals since 1601, into a system time
+pub(crate) fn filetime_to_system_time(filetime: u64) -> Option<SystemTime> {
+    let since_1601 = Duration::from_secs(filetime / 10_000_000) + Duration::from_nanos(filetime % 10_000_000 * 100);
+    SystemTime::UNIX_EPOCH.checked_sub(Duration::from_secs(FILETIME_UNIX_OFFSET))?.checked_add(since_1601)
+}
+
+impl IndexEntry {
+    /// The last modification time as a system time (only applicable for Windows Vista)
+    pub fn last_modified_time(&self) -> Option<SystemTime> {
+        self.last_modified.filter(|x| *x != 0).and_then(filetime_to_system_time)
+    }
+}
+
+impl Thumbscache {
+    /// The modification time of the database file, known when opened from a path
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    /// The creation time of the database file, known when opened from a path on a filesystem that records it
+    pub fn created(&self) -> Option<SystemTime> {
+        self.created
+    }
+
+    /// Pairs every entry with the modification time of the database file, in file order.
+    ///
+    /// Empty if the modification time is unknown.
+    pub fn timeline(&self) -> Vec<(SystemTime, &CacheEntry)> {
+        match self.modified {
+            Some(modified) => self.iter().map(|x| (modified, x)).collect(),
+            None => Vec::new()
+        }
+    }
+}
+
+impl ThumbcacheSet {
+    /// Pairs the entries of every database with a timestamp, sorted chronologically.
+    ///
+    /// The last modification time recorded by the index is used when available (Windows Vista),
+    /// the modification time of the database file otherwise. Entries without either are left out.
+    pub fn timeline(&self) -> Vec<(SystemTime, &CacheEntry)> {
+        let mut timeline = Vec::new();
+        for database in self.databases() {
+            for entry in database.iter() {
+                let indexed = self.index.as_ref().and_then(|x| x.get(entry.entry_hash)).and_then(|x| x.last_modified_time());
+                if let Some(timestamp) = indexed.or(database.modified) {
+                    timeline.push((timestamp, entry));
+                }
+            }
+        }
+        timeline.sort_by_key(|x| x.0);
+        timeline
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "fs")]
+    use crate::tests::{build_database, temp_dir};
+
+    #[test]
+    fn converts_filetimes() {
+        assert_eq!(filetime_to_system_time(FILETIME_UNIX_OFFSET * 10_000_000), Some(SystemTime::UNIX_EPOCH));
+        assert_eq!(filetime_to_system_time((FILETIME_UNIX_OFFSET + 1) * 10_000_000 + 5), Some(SystemTime::UNIX_EPOCH + Duration::from_nanos(1_000_000_500)));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn uses_the_database_modification_time() {
+        let dir = temp_dir("timeline");
+        let path = dir.join("thumbcache_32.db");
+        std::fs::write(&path, build_database(32, 1, &[(1, b"first"), (2, b"second")])).unwrap();
+        let mut database = Thumbscache::from_path(&path).unwrap();
+        database.read().unwrap();
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        assert_eq!(database.modified(), Some(modified));
+        let timeline = database.timeline();
+        assert_eq!(timeline.len(), 2);
+        assert_eq!(timeline[1].0, modified);
+        assert_eq!(timeline[1].1.entry_hash(), 2);
+        let set = ThumbcacheSet::open(&dir).unwrap();
+        assert_eq!(set.timeline().len(), 2);
+    }
+}
@@ -0,0 +1,91 @@
+//! Diagnostics through the `tracing` crate, enabled with the `tracing` feature
+//!
+//! These macros expand to nothing without the feature, so instrumented code doesn't need a cfg of its own.
+//! Only pass values the surrounding code uses anyway, otherwise builds without the feature warn about unused variables.
+
+// Emits an event, the level is the name of a tracing macro such as debug or warn
+macro_rules! event {
+    ($level:ident, $($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arg)*);
+    };
+}
+
+// Enters a span until the end of the enclosing block, the level is the name of a tracing span macro such as debug_span
+macro_rules! span {
+    ($level:ident, $($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::$level!($($arg)*).entered();
+    };
+}
+
+#[cfg(all(test, feature = "tracing", feature = "std"))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tracing::{field::{Field, Visit}, span, Event, Level, Metadata, Subscriber};
+
+    use crate::{tests::build_database, Thumbscache};
+
+    // The level and offset of an event
+    type Recorded = (Level, Option<u64>);
+
+    // Records every event
+    #[derive(Default, Clone)]
+    struct Recorder(Arc<Mutex<Vec<Recorded>>>);
+
+    struct OffsetVisitor(Option<u64>);
+
+    impl Visit for OffsetVisitor {
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            if field.name() == "offset" {
+                self.0 = Some(value);
+            }
+        }
+
+        fn record_debug(&mut self, _: &Field, _: &dyn std::fmt::Debug) {}
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+            span::Id::from_u64(1)
+        }
+
+        fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut visitor = OffsetVisitor(None);
+            event.record(&mut visitor);
+            self.0.lock().unwrap().push((*event.metadata().level(), visitor.0));
+        }
+
+        fn enter(&self, _: &span::Id) {}
+
+        fn exit(&self, _: &span::Id) {}
+    }
+
+    #[test]
+    fn reports_damaged_entries() {
+        let mut bytes = build_database(32, 1, &[(1, b"first"), (2, b"second")]);
+        let second_entry = 24 + 56 + 32 + 5;
+        bytes[second_entry..second_entry + 4].copy_from_slice(b"XXXX");
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let mut database = Thumbscache::from_bytes(bytes.clone());
+            assert!(database.read().is_err());
+            let mut database = Thumbscache::from_bytes(bytes);
+            database.read_lossy().unwrap();
+        });
+        let events = recorder.0.lock().unwrap();
+        let warnings: Vec<Option<u64>> = events.iter().filter(|x| x.0 == Level::WARN).map(|x| x.1).collect();
+        // Once for the strict read failing, once for the warning of the lossy read
+        assert_eq!(warnings, [Some(second_entry as u64), Some(second_entry as u64)]);
+        assert!(events.iter().any(|x| x.0 == Level::TRACE && x.1 == Some(24)));
+    }
+}
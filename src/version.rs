@@ -0,0 +1,78 @@
+//! The releases of Windows behind every format version, Windows Server included
+//!
+//! Server releases share the Explorer of the client release they were built from, so they write the same databases.
+//! Builds are the first build number of each release, as found in the CurrentBuild registry value or in `ver`.
+
+use core::fmt;
+
+use crate::WindowsVersion;
+
+/// The releases writing a format version, see `WindowsVersion::detail()`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct VersionDetail {
+    /// The format version written into the database header
+    pub format_version: u32,
+    /// The build number of the first release writing the format version
+    pub first_build: u32,
+    /// The client releases, in release order
+    pub clients: &'static [&'static str],
+    /// The server releases, in release order
+    pub servers: &'static [&'static str]
+}
+
+/// "format version 32 (Windows 10, Windows 11 / Server 2016, Server 2019, Server 2022, Server 2025)"
+impl fmt::Display for VersionDetail {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "format version {} (", self.format_version)?;
+        for (i, client) in self.clients.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { "" } else { ", " }, client)?;
+        }
+        for (i, server) in self.servers.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { " / " } else { ", " }, server)?;
+        }
+        f.write_str(")")
+    }
+}
+
+impl WindowsVersion {
+    /// The format version of this Windows version along with the client and server releases writing it
+    pub fn detail(&self) -> VersionDetail {
+        let (first_build, clients, servers): (u32, &'static [&'static str], &'static [&'static str]) = match self {
+            WindowsVersion::WinVista => (6000, &["Windows Vista"], &["Server 2008"]),
+            WindowsVersion::Win7 => (7600, &["Windows 7"], &["Server 2008 R2"]),
+            WindowsVersion::Win8 => (9200, &["Windows 8"], &["Server 2012"]),
+            WindowsVersion::Win81 => (9600, &["Windows 8.1"], &["Server 2012 R2"]),
+            // Windows 11 kept the format of Windows 10
+            WindowsVersion::Win10 => (10240, &["Windows 10", "Windows 11"], &["Server 2016", "Server 2019", "Server 2022", "Server 2025"])
+        };
+        VersionDetail {
+            format_version: self.format_version(),
+            first_build,
+            clients,
+            servers
+        }
+    }
+
+    /// Maps an OS build number to the Windows version writing the databases, None before Windows Vista
+    pub fn from_build(build: u32) -> Option<WindowsVersion> {
+        [WindowsVersion::Win10, WindowsVersion::Win81, WindowsVersion::Win8, WindowsVersion::Win7, WindowsVersion::WinVista].into_iter().find(|x| build >= x.detail().first_build)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn maps_builds_and_servers() {
+        assert_eq!(WindowsVersion::Win81.detail().to_string(), "format version 31 (Windows 8.1 / Server 2012 R2)");
+        assert_eq!(WindowsVersion::Win10.detail().format_version, 32);
+        assert!(WindowsVersion::Win10.detail().to_string().ends_with("Windows 11 / Server 2016, Server 2019, Server 2022, Server 2025)"));
+        // Server 2008 RTM was build 6001, Windows 11 24H2 and Server 2025 are build 26100
+        for (build, version) in [(6001, Some(WindowsVersion::WinVista)), (7601, Some(WindowsVersion::Win7)), (9200, Some(WindowsVersion::Win8)), (26100, Some(WindowsVersion::Win10)), (2600, None)] {
+            assert_eq!(WindowsVersion::from_build(build), version, "{}", build);
+        }
+    }
+}
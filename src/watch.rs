@@ -0,0 +1,231 @@
+//! Live monitoring of an Explorer cache directory, enabled with the `watch` feature
+//!
+//! The databases of the directory are read when the watcher starts, afterwards every change to one of them
+//! reports the entries that weren't there before, telling which thumbnails Explorer just generated.
+//! Changed databases are read incrementally with `Thumbscache::refresh_with_options()`.
+
+use std::{collections::{HashMap, HashSet, VecDeque}, path::{Path, PathBuf}, sync::mpsc::{channel, Receiver, RecvTimeoutError}, time::Duration};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::{CacheEntry, CacheType, ReadOptions, Thumbscache, ThumbsError};
+
+/// A change noticed by a `CacheWatcher`
+#[derive(Debug)]
+pub enum WatchEvent {
+    /// An entry appeared in a database since it was last read
+    NewEntry {
+        database: PathBuf,
+        entry: CacheEntry
+    },
+    /// A database changed but couldn't be read, it is read again on its next change
+    Error {
+        database: PathBuf,
+        error: ThumbsError
+    }
+}
+
+/// Watches the thumbnail and icon databases (thumbcache_*.db and iconcache_*.db) of a directory for new entries
+///
+/// Entries are told apart by their offset and hash, the entries present when the watcher starts aren't reported.
+///
+/// ```no_run
+/// use thumbscache::{CacheWatcher, WatchEvent};
+/// let watcher = CacheWatcher::new("C:\\Users\\z\\AppData\\Local\\Microsoft\\Windows\\Explorer").unwrap();
+/// for event in watcher {
+///     if let WatchEvent::NewEntry { database, entry } = event {
+///         println!("{} : {}", database.display(), entry.entry_hash_hex());
+///     }
+/// }
+/// ```
+pub struct CacheWatcher {
+    dir: PathBuf,
+    // Dropping the watcher stops the notifications
+    _watcher: RecommendedWatcher,
+    changes: Receiver<notify::Result<notify::Event>>,
+    // The databases read so far, without the data of their entries
+    databases: HashMap<PathBuf, Thumbscache>,
+    // The offset and hash of the entries already seen in every database, kept when a database is rewritten
+    seen: HashMap<PathBuf, HashSet<(u64, u64)>>,
+    pending: VecDeque<WatchEvent>
+}
+
+impl core::fmt::Debug for CacheWatcher {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("CacheWatcher").field("Directory", &self.dir).field("Number of databases", &self.databases.len()).finish()
+    }
+}
+
+fn watch_error(error: notify::Error) -> ThumbsError {
+    match error.kind {
+        notify::ErrorKind::Io(error) => ThumbsError::IoError(error),
+        kind => ThumbsError::IoError(std::io::Error::other(format!("{:?}", kind)))
+    }
+}
+
+fn is_database(path: &Path) -> bool {
+    path.file_name().is_some_and(|x| CacheType::from_filename(&x.to_string_lossy()).is_some())
+}
+
+fn read_database(path: &Path) -> Result<Thumbscache, ThumbsError> {
+    let mut database = Thumbscache::from_path(path)?;
+    database.read_with_options(ReadOptions::lazy())?;
+    Ok(database)
+}
+
+impl CacheWatcher {
+    /// Reads the databases of the directory and starts watching it
+    pub fn new(dir: impl AsRef<Path>) -> Result<CacheWatcher, ThumbsError> {
+        let dir = dir.as_ref().to_path_buf();
+        let (sender, changes) = channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(watch_error)?;
+        watcher.watch(&dir, RecursiveMode::NonRecursive).map_err(watch_error)?;
+        let mut cache_watcher = CacheWatcher {
+            dir,
+            _watcher: watcher,
+            changes,
+            databases: HashMap::new(),
+            seen: HashMap::new(),
+            pending: VecDeque::new()
+        };
+        // Databases that can't be read yet are picked up on their next change
+        for path in cache_watcher.databases()? {
+            if let Ok(database) = read_database(&path) {
+                cache_watcher.seen.insert(path.clone(), database.iter().map(|x| (x.offset(), x.entry_hash())).collect());
+                cache_watcher.databases.insert(path, database);
+            }
+        }
+        Ok(cache_watcher)
+    }
+
+    /// The watched directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn databases(&self) -> Result<Vec<PathBuf>, ThumbsError> {
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(&self.dir).map_err(ThumbsError::IoError)?
+            .filter_map(|x| x.ok()).map(|x| x.path()).filter(|x| is_database(x)).collect();
+        paths.sort();
+        Ok(paths)
+    }
+
+    // Reads the new part of the database and queues the entries that weren't seen before, a removed database is forgotten
+    fn check(&mut self, path: &Path) {
+        if !path.exists() {
+            self.databases.remove(path);
+            self.seen.remove(path);
+            return;
+        }
+        let read = match self.databases.get_mut(path) {
+            Some(database) => database.refresh_with_options(ReadOptions::lazy()).map(|x| x as usize),
+            None => read_database(path).map(|database| {
+                let added = database.len();
+                self.databases.insert(path.to_path_buf(), database);
+                added
+            })
+        };
+        let added = match read {
+            Ok(added) => added,
+            Err(error) => {
+                // Read from scratch on the next change, the file may have been caught halfway through being written
+                self.databases.remove(path);
+                self.pending.push_back(WatchEvent::Error { database: path.to_path_buf(), error });
+                return;
+            }
+        };
+        let Some(database) = self.databases.get(path) else {
+            return;
+        };
+        let seen = self.seen.entry(path.to_path_buf()).or_default();
+        for entry in &database.entries()[database.len() - added..] {
+            if seen.insert((entry.offset(), entry.entry_hash())) {
+                let mut entry = entry.clone();
+                match entry.load_data(database) {
+                    Ok(()) => self.pending.push_back(WatchEvent::NewEntry { database: path.to_path_buf(), entry }),
+                    Err(error) => self.pending.push_back(WatchEvent::Error { database: path.to_path_buf(), error })
+                }
+            }
+        }
+    }
+
+    /// Reads every database of the directory again and returns the events, without waiting for notifications.
+    ///
+    /// Useful on filesystems that don't report changes, such as network shares.
+    pub fn rescan(&mut self) -> Result<Vec<WatchEvent>, ThumbsError> {
+        for path in self.databases()? {
+            self.check(&path);
+        }
+        Ok(self.pending.drain(..).collect())
+    }
+
+    /// Waits for the next event, returns None if nothing happened before the timeout
+    pub fn next_timeout(&mut self, timeout: Duration) -> Option<WatchEvent> {
+        let deadline = std::time::Instant::now() + timeout;
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+            let change = match self.changes.recv_timeout(deadline.saturating_duration_since(std::time::Instant::now())) {
+                Ok(change) => change,
+                Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => return None
+            };
+            match change {
+                Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)) => {
+                    for path in event.paths.iter().filter(|x| is_database(x)) {
+                        self.check(path);
+                    }
+                },
+                Ok(_) => (),
+                Err(error) => self.pending.push_back(WatchEvent::Error { database: self.dir.clone(), error: watch_error(error) })
+            }
+        }
+    }
+}
+
+/// Blocks until the next event
+impl Iterator for CacheWatcher {
+    type Item = WatchEvent;
+
+    fn next(&mut self) -> Option<WatchEvent> {
+        loop {
+            if let Some(event) = self.next_timeout(Duration::from_secs(3600)) {
+                return Some(event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{build_database, temp_dir};
+
+    fn new_hashes(events: &[WatchEvent]) -> Vec<u64> {
+        events.iter().filter_map(|x| match x {
+            WatchEvent::NewEntry { entry, .. } => Some(entry.entry_hash()),
+            WatchEvent::Error { .. } => None
+        }).collect()
+    }
+
+    #[test]
+    fn reports_new_entries() {
+        let dir = temp_dir("watch");
+        let path = dir.join("thumbcache_32.db");
+        std::fs::write(&path, build_database(32, 1, &[(1, b"first")])).unwrap();
+        std::fs::write(dir.join("notes.txt"), b"ignored").unwrap();
+        let mut watcher = CacheWatcher::new(&dir).unwrap();
+        assert!(watcher.rescan().unwrap().is_empty());
+
+        std::fs::write(&path, build_database(32, 1, &[(1, b"first"), (2, b"second")])).unwrap();
+        // The file can be caught halfway through being written, which is reported as an error
+        let event = std::iter::from_fn(|| watcher.next_timeout(Duration::from_secs(10))).find(|x| matches!(x, WatchEvent::NewEntry { .. })).unwrap();
+        assert!(matches!(&event, WatchEvent::NewEntry { database, entry } if database == &path && entry.data == b"second"));
+
+        std::fs::write(dir.join("thumbcache_96.db"), build_database(32, 3, &[(3, b"third")])).unwrap();
+        std::fs::write(&path, b"CMMM").unwrap();
+        let events = watcher.rescan().unwrap();
+        assert_eq!(new_hashes(&events), [3]);
+        assert!(matches!(&events[..], [WatchEvent::Error { database, .. }, _] if database == &path));
+    }
+}
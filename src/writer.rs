@@ -0,0 +1,208 @@
+//! Creating thumbnail cache databases
+//!
+//! The writer lays out the database the same way Windows does, so the result can be read back by this library,
+//! used as a test fixture or placed into an Explorer cache directory for research.
+
+use alloc::vec::Vec;
+
+use crate::{checksum::crc64, dimensions::sniff_dimensions, endian::LittleEndian, format_entry_hash, CacheEntry, CacheType, ImageFormat, ThumbsError, WindowsVersion};
+
+// The identifier and padding are sized so the data starts on this boundary, relative to the entry
+const DATA_ALIGNMENT: usize = 8;
+
+/// Thumbscache writer
+///
+/// Builds a database in memory, one entry at a time.
+///
+/// ```
+/// use thumbscache::{parse_database, CacheType, ReadOptions, ThumbscacheWriter, WindowsVersion};
+/// let mut writer = ThumbscacheWriter::new(WindowsVersion::Win10, CacheType::Res256).unwrap();
+/// writer.append(0x1f2b3c4d5e6f7a8b, b"BM...");
+/// let (_, entries) = parse_database(writer.as_bytes(), ReadOptions::default()).unwrap();
+/// assert_eq!(entries.len(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ThumbscacheWriter {
+    windows_version: WindowsVersion,
+    cache_type: CacheType,
+    bytes: Vec<u8>
+}
+
+impl ThumbscacheWriter {
+    /// Starts an empty database of the given cache type, laid out for the given Windows version.
+    ///
+    /// Returns an error if that Windows version doesn't use the cache type.
+    pub fn new(windows_version: WindowsVersion, cache_type: CacheType) -> Result<ThumbscacheWriter, ThumbsError> {
+        let cache_type_number = windows_version.cache_types().iter().position(|x| *x == cache_type)
+            .ok_or(ThumbsError::UnsupportedCacheType(cache_type, windows_version))?;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"CMMM");
+        bytes.extend_from_slice(&windows_version.format_version().to_le_bytes());
+        bytes.extend_from_slice(&(cache_type_number as u32).to_le_bytes());
+        // First entry (relative to the end of the header), first available entry and entry count
+        bytes.extend_from_slice(&[0u8; 12]);
+        let mut writer = ThumbscacheWriter {
+            windows_version,
+            cache_type,
+            bytes
+        };
+        writer.update_header(0);
+        Ok(writer)
+    }
+
+    /// The Windows version the database is laid out for
+    pub fn windows_version(&self) -> WindowsVersion {
+        self.windows_version
+    }
+
+    /// The cache type written into the header
+    pub fn cache_type(&self) -> CacheType {
+        self.cache_type
+    }
+
+    /// Appends an entry holding the data, identified by the hash
+    pub fn append(&mut self, entry_hash: u64, data: &[u8]) -> &mut ThumbscacheWriter {
+        let identifier: Vec<u8> = format_entry_hash(entry_hash, false).encode_utf16().flat_map(|x| x.to_le_bytes()).collect();
+        self.append_raw(entry_hash, &identifier, data)
+    }
+
+    /// Appends a copy of an entry read from another database, keeping its identifier
+    pub fn append_entry(&mut self, entry: &CacheEntry) -> &mut ThumbscacheWriter {
+        self.append_raw(entry.entry_hash, &entry.identifier, &entry.data)
+    }
+
+    pub(crate) fn append_raw(&mut self, entry_hash: u64, identifier: &[u8], data: &[u8]) -> &mut ThumbscacheWriter {
+        let header_len = match self.windows_version {
+            WindowsVersion::Win7 => 48,
+            _ => 56
+        };
+        let padding_size = (DATA_ALIGNMENT - (header_len + identifier.len()) % DATA_ALIGNMENT) % DATA_ALIGNMENT;
+        let size = (header_len + identifier.len() + padding_size + data.len()) as u32;
+        let data_checksum = if data.is_empty() { 0 } else { crc64(data) };
+        let mut header = Vec::with_capacity(header_len);
+        header.extend_from_slice(b"CMMM");
+        header.extend_from_slice(&size.to_le_bytes());
+        header.extend_from_slice(&entry_hash.to_le_bytes());
+        if self.windows_version == WindowsVersion::WinVista {
+            // Up to 3 characters of extension, null-terminated
+            let mut extension: Vec<u8> = ImageFormat::detect(data).extension().encode_utf16().take(3).flat_map(|x| x.to_le_bytes()).collect();
+            extension.resize(8, 0);
+            header.extend_from_slice(&extension);
+        }
+        header.extend_from_slice(&(identifier.len() as u32).to_le_bytes());
+        header.extend_from_slice(&(padding_size as u32).to_le_bytes());
+        header.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        if matches!(self.windows_version, WindowsVersion::Win8 | WindowsVersion::Win81 | WindowsVersion::Win10) {
+            // Windows 8 and later store the dimensions of the thumbnail
+            let (width, height) = sniff_dimensions(data).unwrap_or_default();
+            header.extend_from_slice(&width.to_le_bytes());
+            header.extend_from_slice(&height.to_le_bytes());
+        }
+        // Unknown fields
+        header.resize(header_len - 16, 0);
+        header.extend_from_slice(&data_checksum.to_le_bytes());
+        let header_checksum = crc64(&header);
+        header.extend_from_slice(&header_checksum.to_le_bytes());
+
+        self.bytes.extend_from_slice(&header);
+        self.bytes.extend_from_slice(identifier);
+        self.bytes.resize(self.bytes.len() + padding_size, 0);
+        self.bytes.extend_from_slice(data);
+        let count = self.bytes.u32_le(20);
+        self.update_header(count + 1);
+        self
+    }
+
+    fn update_header(&mut self, count: u32) {
+        let first_available = self.bytes.len() as u32;
+        self.bytes[16..20].copy_from_slice(&first_available.to_le_bytes());
+        self.bytes[20..24].copy_from_slice(&count.to_le_bytes());
+    }
+
+    /// Returns the bytes of the database written so far
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Finishes the database and returns its bytes
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// Writes the database into a file, replacing its contents
+    #[cfg(feature = "fs")]
+    pub fn write_to_file(&self, file_path: impl AsRef<std::path::Path>) -> Result<(), ThumbsError> {
+        crate::write_data(file_path, &self.bytes)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::Thumbscache;
+    use std::io::Cursor;
+
+    #[test]
+    fn round_trips_every_windows_version() {
+        for version in [WindowsVersion::WinVista, WindowsVersion::Win7, WindowsVersion::Win8, WindowsVersion::Win81, WindowsVersion::Win10] {
+            let mut writer = ThumbscacheWriter::new(version, CacheType::Res256).unwrap();
+            writer.append(1, b"BMfirst").append(2, b"").append(3, b"\x89PNG\r\n\x1a\nthird");
+            let mut database = Thumbscache::from_reader(Cursor::new(writer.into_bytes())).unwrap();
+            assert_eq!(database.read().unwrap(), 3, "{:?}", version);
+            assert_eq!(database.windows_version, Some(version));
+            assert_eq!(database.cache_type, Some(CacheType::Res256));
+            assert_eq!(database.entries()[2].data, b"\x89PNG\r\n\x1a\nthird");
+            assert_eq!(database.entries()[1].identifier_string, "0000000000000002");
+            assert!(database.verify_all().is_clean(), "{:?}", version);
+        }
+    }
+
+    #[test]
+    fn vista_entries_store_the_extension() {
+        let mut writer = ThumbscacheWriter::new(WindowsVersion::WinVista, CacheType::Res96).unwrap();
+        writer.append(1, b"\xFF\xD8\xFFjpeg");
+        let mut database = Thumbscache::from_reader(Cursor::new(writer.into_bytes())).unwrap();
+        database.read().unwrap();
+        assert_eq!(database.entries()[0].file_extension(), Some("jpg\0"));
+    }
+
+    #[test]
+    fn stores_the_dimensions_from_windows_8() {
+        let mut png = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 13];
+        png.extend_from_slice(b"IHDR");
+        png.extend_from_slice(&256u32.to_be_bytes());
+        png.extend_from_slice(&144u32.to_be_bytes());
+        let mut writer = ThumbscacheWriter::new(WindowsVersion::Win8, CacheType::Res256).unwrap();
+        writer.append(1, &png).append(2, b"data");
+        let mut database = Thumbscache::from_reader(Cursor::new(writer.into_bytes())).unwrap();
+        database.read().unwrap();
+        assert_eq!(database.entries()[0].version_fields, crate::VersionFields::Win8 { width: 256, height: 144, flags: crate::EntryFlags::empty() });
+        assert_eq!(database.entries()[0].dimensions(), Some((256, 144)));
+        assert_eq!(database.entries()[1].dimensions(), None);
+        assert!(database.verify_all().is_clean());
+    }
+
+    #[test]
+    fn leaves_the_reserved_fields_of_vista_and_windows_7_empty() {
+        let mut png = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 13];
+        png.extend_from_slice(b"IHDR");
+        png.extend_from_slice(&256u32.to_be_bytes());
+        png.extend_from_slice(&144u32.to_be_bytes());
+        // The unknown fields lie between the data size and the data checksum
+        for (version, reserved) in [(WindowsVersion::WinVista, 36..40), (WindowsVersion::Win7, 28..32)] {
+            let mut writer = ThumbscacheWriter::new(version, CacheType::Res256).unwrap();
+            writer.append(1, &png);
+            let bytes = writer.into_bytes();
+            assert!(bytes[24 + reserved.start..24 + reserved.end].iter().all(|x| *x == 0), "{:?}", version);
+            let mut database = Thumbscache::from_reader(Cursor::new(bytes)).unwrap();
+            database.read().unwrap();
+            assert_eq!(database.entries()[0].data, png);
+            assert!(database.verify_all().is_clean(), "{:?}", version);
+        }
+    }
+
+    #[test]
+    fn rejects_cache_types_of_other_versions() {
+        assert!(ThumbscacheWriter::new(WindowsVersion::Win7, CacheType::Res16).is_err());
+    }
+}